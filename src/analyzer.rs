@@ -1,41 +1,71 @@
 use std::collections::{HashMap, HashSet};
 
-use crate::{ast::*, error::Error};
+use crate::{ast::*, error::Error, token::Span};
 
-pub struct Analyzer<'a>{
+pub struct Analyzer<'a> {
     ast: &'a Vec<Expr>,
     source: String,
     filename: String,
     variables: Vec<HashSet<String>>,
+    /// Names of the functions, which can be used as values
+    functions: HashSet<String>,
+    /// The closures being analyzed, with the number of scopes around them
+    /// and the variables they use from those scopes
+    closures: Vec<(usize, Vec<String>)>,
+    /// The variables every closure captures, by the span of the closure
+    captures: HashMap<Span, Vec<String>>,
+    /// The expression being analyzed
+    span: Span,
     errors: Vec<Error>,
 }
-impl <'a> Analyzer<'a> {
-    pub fn new(ast: &'a Vec<Expr>, source: String, filename: String,) -> Self {
+impl<'a> Analyzer<'a> {
+    pub fn new(ast: &'a Vec<Expr>, source: String, filename: String) -> Self {
         Self {
             ast,
             variables: vec![HashSet::new()],
+            functions: HashSet::new(),
+            closures: Vec::new(),
+            captures: HashMap::new(),
+            span: Span::from(0..0),
             source,
             filename,
             errors: Vec::new(),
         }
     }
-    pub fn analyze(&mut self) -> bool{
+    pub fn analyze(&mut self) -> bool {
+        for expr in self.ast {
+            match &expr.inner {
+                ExprKind::FunctionDeclaration(name, ..) => {
+                    self.functions.insert(name.clone());
+                }
+                ExprKind::Extern(_, sig, _) => {
+                    self.functions.insert(sig.name.clone());
+                }
+                _ => {}
+            }
+        }
         for expr in self.ast.clone() {
             self.handle(&expr);
         }
         self.has_errors()
     }
     pub fn handle(&mut self, expr: &Expr) {
+        self.span = expr.span;
         match &expr.inner {
-            ExprKind::Binary(lhs,_,rhs) => {
+            ExprKind::Binary(lhs, _, rhs) => {
                 self.handle(lhs);
                 self.handle(rhs);
             }
             ExprKind::Var(n, val, _) => {
                 self.handle(val);
-              
+
                 self.variables.last_mut().unwrap().insert(n.to_string());
             }
+            ExprKind::Destructure(names, val) => {
+                self.handle(val);
+                let scope = self.variables.last_mut().unwrap();
+                scope.extend(names.iter().cloned());
+            }
             ExprKind::Block(exprs) => {
                 self.start_scope();
                 for expr in exprs {
@@ -43,25 +73,158 @@ impl <'a> Analyzer<'a> {
                 }
                 self.end_scope();
             }
-            ExprKind::Ident(ident) => {
-                match self.get(ident) {
-                    Some(_) => {}
-                    None => {
-                        self.errors.push(Error {
-                            source: self.source.clone(),
-                            file_name: self.filename.clone(),
-                            message: "Variable not found in the current scope".to_owned(),
-                            span: expr.span,
-                            help: "".to_owned(),
-                        })
-                    }
+            ExprKind::If(condition, exprs) => {
+                self.handle(condition);
+                self.start_scope();
+                for expr in exprs {
+                    self.handle(expr);
+                }
+                self.end_scope();
+            }
+            ExprKind::IfLet(pattern, value, exprs) => {
+                self.handle(value);
+                self.start_scope();
+                self.variables
+                    .last_mut()
+                    .unwrap()
+                    .insert(pattern.name().to_string());
+                for expr in exprs {
+                    self.handle(expr);
+                }
+                self.end_scope();
+            }
+            ExprKind::FunctionDeclaration(_, _, params, _, exprs) => {
+                // Functions can't see the variables of the scope they are declared in
+                let outer = std::mem::replace(&mut self.variables, vec![HashSet::new()]);
+                let closures = std::mem::take(&mut self.closures);
+                // Default values are given by the callers, so they can't see the parameters
+                for default in params.iter().filter_map(|param| param.default.as_ref()) {
+                    self.handle(default);
+                }
+                self.variables[0].extend(params.iter().map(|param| param.name.clone()));
+                for expr in exprs {
+                    self.handle(expr);
+                }
+                self.variables = outer;
+                self.closures = closures;
+            }
+            ExprKind::Closure(params, _, exprs) => {
+                self.closures.push((self.variables.len(), Vec::new()));
+                self.start_scope();
+                let scope = self.variables.last_mut().unwrap();
+                scope.extend(params.iter().map(|param| param.name.clone()));
+                for expr in exprs {
+                    self.handle(expr);
+                }
+                self.end_scope();
+                let (_, captures) = self.closures.pop().unwrap();
+                self.captures.insert(expr.span, captures);
+            }
+            ExprKind::Impl(_, _, methods) => {
+                for method in methods {
+                    self.handle(method);
                 }
             }
+            ExprKind::FunctionCall(name, args) => {
+                // Calling a closure held in a variable
+                self.lookup(name);
+                for arg in args {
+                    self.handle(arg);
+                }
+            }
+            ExprKind::Puts(args) => {
+                for arg in args {
+                    self.handle(arg);
+                }
+            }
+            ExprKind::MethodCall(receiver, _, args) => {
+                self.handle(receiver);
+                for arg in args {
+                    self.handle(arg);
+                }
+            }
+            ExprKind::Return(Some(value)) => self.handle(value),
+            ExprKind::For(name, second, iterable, exprs) => {
+                self.handle(iterable);
+                self.start_scope();
+                self.variables.last_mut().unwrap().insert(name.to_string());
+                if let Some(second) = second {
+                    self.variables
+                        .last_mut()
+                        .unwrap()
+                        .insert(second.to_string());
+                }
+                for expr in exprs {
+                    self.handle(expr);
+                }
+                self.end_scope();
+            }
+            ExprKind::Array(elements)
+            | ExprKind::Tuple(elements)
+            | ExprKind::Vector(elements)
+            | ExprKind::Interpolation(elements) => {
+                for element in elements {
+                    self.handle(element);
+                }
+            }
+            ExprKind::Slice(value, start, end) => {
+                self.handle(value);
+                for bound in [start, end].into_iter().flatten() {
+                    self.handle(bound);
+                }
+            }
+            ExprKind::Convert(_, value)
+            | ExprKind::Unary(_, value)
+            | ExprKind::Ref(value)
+            | ExprKind::Try(value)
+            | ExprKind::TupleIndex(value, _)
+            | ExprKind::NamedArg(_, value)
+            | ExprKind::Deref(value) => self.handle(value),
+            ExprKind::Map(entries) => {
+                for (key, value) in entries {
+                    self.handle(key);
+                    self.handle(value);
+                }
+            }
+            ExprKind::Index(lhs, rhs) | ExprKind::Assign(lhs, rhs) => {
+                self.handle(lhs);
+                self.handle(rhs);
+            }
+            // Functions can be used as values too
+            ExprKind::Ident(ident) if !self.lookup(ident) && !self.functions.contains(ident) => {
+                self.errors.push(Error {
+                    source: self.source.clone(),
+                    file_name: self.filename.clone(),
+                    message: "Variable not found in the current scope".to_owned(),
+                    span: expr.span,
+                    help: "".to_owned(),
+                    labels: Vec::new(),
+                })
+            }
             _ => {}
         }
     }
-    fn get(&self, ident: &String) -> Option<String>{
-        self.variables.iter().rev().find_map(|map| map.get(ident)).cloned()
+    /// Whether the variable `ident` is in scope, closures that use it
+    /// from a scope around them capture it
+    fn lookup(&mut self, ident: &String) -> bool {
+        let depth = match self
+            .variables
+            .iter()
+            .rposition(|scope| scope.contains(ident))
+        {
+            Some(depth) => depth,
+            None => return false,
+        };
+        for (scopes, captures) in &mut self.closures {
+            if depth < *scopes && !captures.contains(ident) {
+                captures.push(ident.clone());
+            }
+        }
+        true
+    }
+    /// Gives the variables every closure captures, by the span of the closure
+    pub fn captures(&mut self) -> HashMap<Span, Vec<String>> {
+        std::mem::take(&mut self.captures)
     }
     fn start_scope(&mut self) {
         self.variables.push(HashSet::new());
@@ -70,11 +233,17 @@ impl <'a> Analyzer<'a> {
         self.variables.pop();
     }
     pub fn has_errors(&self) -> bool {
-        return !self.errors.is_empty()
+        !self.errors.is_empty()
     }
-    pub fn finish(&self) {
-        for err in self.errors.clone() {
-            err.show();
-        }
+    /// Declares a function that isn't in the code, like a host function
+    pub fn declare_function(&mut self, name: &str) {
+        self.functions.insert(name.to_owned());
     }
-}
\ No newline at end of file
+    /// Where the analysis is, a bug in it is reported there
+    pub fn span(&self) -> Span {
+        self.span
+    }
+    pub fn errors(&self) -> &[Error] {
+        &self.errors
+    }
+}
@@ -29,22 +29,118 @@ pub enum ExprKind {
     Float(f64),
     Ident(String),
     Str(String),
+    /// A string with embedded expressions, its text is stored as `Str` parts
+    Interpolation(Vec<Expr>),
     Var(String, Box<Expr>, Type),
     Int(i64),
     Binary(Box<Expr>, TokenKind, Box<Expr>),
     Unary(TokenKind, Box<Expr>),
+    /// The address of a variable, like `&x`
     Ref(Box<Expr>),
+    /// The value a pointer points to, like `*p`
+    Deref(Box<Expr>),
     If(Box<Expr>, Vec<Expr>),
+    /// Runs the body when an optional has a value or a result matches the pattern,
+    /// like `if let x = maybe ... end` or `if let err(e) = result ... end`
+    IfLet(Pattern, Box<Expr>, Vec<Expr>),
+    /// The value of a result, or returns its error from the function, like `parse(s)?`
+    Try(Box<Expr>),
+    /// The missing value of an optional
+    None,
     Enum(String,Vec<String>),
-    FunctionCall(String, Vec<Expr>), 
-    FunctionDeclaration(String, Vec<Param>,Type,Vec<Expr>),
+    FunctionCall(String, Vec<Expr>),
+    /// An argument given by the name of its parameter, like `greeting: "hello"`
+    NamedArg(String, Box<Expr>),
+    FunctionDeclaration(String, Vec<TypeParam>, Vec<Param>, Type, Vec<Expr>),
+    /// An anonymous function that can use the variables around it,
+    /// like `fun(x int) : int do x + n end`
+    Closure(Vec<Param>, Type, Vec<Expr>),
+    MethodCall(Box<Expr>, String, Vec<Expr>),
+    Interface(String, Vec<MethodSig>),
+    /// Another name for a type, like `type Meters = float`
+    TypeAlias(String, Type),
+    /// A distinct type with the values of another, like `newtype UserId = int`.
+    /// `UserId(5)` makes one, and `int(id)` gives its value back.
+    Newtype(String, Type),
+    Impl(String, Type, Vec<Expr>),
+    /// A C function, like `extern fun strlen(s str) : int`, which is looked up in the process
+    /// or in the shared library given before `fun`, like `extern "libm.so.6" fun cos(x float) : float`.
+    /// The flag is set when it takes more arguments after its parameters,
+    /// like `extern fun printf(format str, ...) : int`.
+    Extern(Option<String>, MethodSig, bool),
+    Return(Option<Box<Expr>>),
+    /// A tuple literal, like `(1, "one")`
+    Tuple(Vec<Expr>),
+    /// An element of a tuple, like `t.0`
+    TupleIndex(Box<Expr>, usize),
+    /// Declares a variable for every element of a tuple, like `a, b = divmod(7, 2)`
+    Destructure(Vec<String>, Box<Expr>),
+    /// An array literal, like `[1, 2, 3]`
+    Array(Vec<Expr>),
+    /// A growable vector, like `vec(1, 2, 3)`
+    Vector(Vec<Expr>),
+    /// A hash map, like `{"a": 1, "b": 2}`
+    Map(Vec<(Expr, Expr)>),
+    /// Indexing into a value, like `a[i]`
+    Index(Box<Expr>, Box<Expr>),
+    /// Part of a string between two optional indexes, like `s[1:3]` or `s[2:]`
+    Slice(Box<Expr>, Option<Box<Expr>>, Option<Box<Expr>>),
+    /// Converting a value to another type, like `int("42")` or `str(1.5)`
+    Convert(Type, Box<Expr>),
+    /// Assigning to a variable or an element, like `a[i] = 5`
+    Assign(Box<Expr>, Box<Expr>),
+    /// Looping over the elements of a value, like `for x in v ... end`.
+    /// With a second name, like `for k, v in m`, it loops over keys and values of maps,
+    /// and over indexes and elements of arrays and vectors.
+    For(String, Option<String>, Box<Expr>, Vec<Expr>),
     Bool(bool),
     Block(Vec<Expr>),
     Puts(Vec<Expr>),
     Error,
     Eof,
 }
-impl<'a> Item for ExprKind {
+impl ExprKind {
+    /// The expressions directly inside of this one
+    pub fn children(&self) -> Vec<&Expr> {
+        use ExprKind::*;
+        match self {
+            Interpolation(exprs)
+            | FunctionCall(_, exprs)
+            | FunctionDeclaration(.., exprs)
+            | Closure(_, _, exprs)
+            | Impl(_, _, exprs)
+            | Array(exprs)
+            | Tuple(exprs)
+            | Vector(exprs)
+            | Block(exprs)
+            | Puts(exprs) => exprs.iter().collect(),
+            Var(_, expr, _)
+            | Unary(_, expr)
+            | Ref(expr)
+            | Deref(expr)
+            | Try(expr)
+            | TupleIndex(expr, _)
+            | Destructure(_, expr)
+            | NamedArg(_, expr)
+            | Convert(_, expr) => vec![expr],
+            Binary(lhs, _, rhs) | Index(lhs, rhs) | Assign(lhs, rhs) => vec![lhs, rhs],
+            If(expr, exprs)
+            | IfLet(_, expr, exprs)
+            | MethodCall(expr, _, exprs)
+            | For(_, _, expr, exprs) => {
+                std::iter::once(&**expr).chain(exprs).collect()
+            }
+            Return(expr) => expr.iter().map(|expr| &**expr).collect(),
+            Map(entries) => entries.iter().flat_map(|(k, v)| [k, v]).collect(),
+            Slice(expr, start, end) => std::iter::once(&**expr)
+                .chain(start.iter().chain(end).map(|expr| &**expr))
+                .collect(),
+            Float(_) | Ident(_) | Str(_) | Int(_) | Bool(_) | None | Enum(..) | Interface(..)
+            | TypeAlias(..) | Newtype(..) | Extern(..) | Error | Eof => vec![],
+        }
+    }
+}
+impl Item for ExprKind {
     fn boxed(self) -> Box<Self> {
         Box::new(self)
     }
@@ -52,14 +148,67 @@ impl<'a> Item for ExprKind {
         println!("{:?}", self)
     }
 }
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum Type {
     String,
     Int,
     Float,
     Bool,
     Ptr(Box<Type>),
+    /// A value that can be missing, like `int?`
+    Optional(Box<Type>),
+    /// Either a value or an error, like `Result[int, str]`
+    Result(Box<Type>, Box<Type>),
+    /// A fixed list of values of different types, like `(int, str)`
+    Tuple(Vec<Type>),
+    /// A function or a closure, like `fun(int) : str`. The return type is `None`
+    /// for functions that don't return a value.
+    Function(Vec<Type>, Box<Type>),
+    /// A fixed-size array, like `[int; 3]`
+    Array(Box<Type>, usize),
+    /// A growable vector, like `vec[int]`
+    Vec(Box<Type>),
+    /// A hash map, like `map[str, int]`
+    Map(Box<Type>, Box<Type>),
+    /// A user defined type, like an enum, an interface, an alias or a newtype
+    Named(String),
+    /// The type of `self` inside of interfaces and impls
+    SelfType,
+    /// A type parameter of a generic function, like `T` in `fun max[T](a T, b T) : T`
+    Param(String),
     None,
+    /// The type of an expression with errors, nothing more is reported about it
+    Unknown,
+}
+/// Types are shown like they are written
+impl Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        /// Shows types separated by commas
+        fn list(types: &[Type]) -> String {
+            types.iter().map(|ty| ty.to_string()).collect::<Vec<_>>().join(", ")
+        }
+        match self {
+            Type::String => write!(f, "str"),
+            Type::Int => write!(f, "int"),
+            Type::Float => write!(f, "float"),
+            Type::Bool => write!(f, "bool"),
+            Type::Ptr(inner) => write!(f, "{}*", inner),
+            Type::Optional(inner) => write!(f, "{}?", inner),
+            Type::Result(value, error) => write!(f, "Result[{}, {}]", value, error),
+            Type::Tuple(fields) => write!(f, "({})", list(fields)),
+            Type::Function(params, return_type) => match **return_type {
+                Type::None => write!(f, "fun({})", list(params)),
+                _ => write!(f, "fun({}) : {}", list(params), return_type),
+            },
+            Type::Array(inner, size) => write!(f, "[{}; {}]", inner, size),
+            Type::Vec(inner) => write!(f, "vec[{}]", inner),
+            Type::Map(key, value) => write!(f, "map[{}, {}]", key, value),
+            Type::Named(name) | Type::Param(name) => write!(f, "{}", name),
+            Type::SelfType => write!(f, "self"),
+            Type::None => write!(f, "none"),
+            Type::Unknown => write!(f, "unknown"),
+        }
+    }
 }
 impl Item for Type {
     fn boxed(self) -> Box<Self> {
@@ -71,7 +220,7 @@ impl Item for Type {
 }
 pub type Expr = Spanned<ExprKind>;
 
-impl<'a> Item for Expr {
+impl Item for Expr {
     fn boxed(self) -> Box<Self> {
         Box::new(self)
     }
@@ -79,8 +228,44 @@ impl<'a> Item for Expr {
         println!("{:?}", self.inner);
     }
 }
+/// What `if let` matches, and the name it gives to the value
+#[derive(Debug, PartialEq, Clone)]
+pub enum Pattern {
+    /// The value of an optional, like `x`
+    Value(String),
+    /// The value of a result, like `ok(x)`
+    Ok(String),
+    /// The error of a result, like `err(e)`
+    Err(String),
+}
+impl Pattern {
+    pub fn name(&self) -> &String {
+        match self {
+            Pattern::Value(name) | Pattern::Ok(name) | Pattern::Err(name) => name,
+        }
+    }
+}
 #[derive(Debug, PartialEq, Clone)]
 pub struct Param {
     pub param_type: Type,
     pub name: String,
-}
\ No newline at end of file
+    /// The value used when a call doesn't give this parameter, like `greeting str = "hi"`
+    pub default: Option<Expr>,
+    /// Whether this parameter takes the remaining arguments of a call, like `numbers ...int`.
+    /// Its type is a vector of them.
+    pub variadic: bool,
+}
+/// A type parameter with the interfaces it has to implement, like `T: Show`
+#[derive(Debug, PartialEq, Clone)]
+pub struct TypeParam {
+    pub name: String,
+    pub bounds: Vec<String>,
+}
+/// A function signature without a body, used by interfaces and C functions
+#[derive(Debug, PartialEq, Clone)]
+pub struct MethodSig {
+    pub name: String,
+    pub params: Vec<Param>,
+    pub return_type: Type,
+    pub span: Span,
+}
@@ -1,18 +1,47 @@
-use super::{ir::{Stmt, Expr, IrType, Literal, IrValue, BinOp}, compiler::Compiler};
+use std::collections::HashMap;
+
+use crate::token::Span;
+
+use super::{
+    compiler::{Compiler, JitError, Program},
+    ir::{BinOp, Expr, Extern, Function, IrType, IrValue, Literal, Stmt, UnOp},
+    runtime::Host,
+};
 
 pub struct Builder {
-    pub code: Vec<Stmt>
+    pub code: Vec<Stmt>,
+    pub functions: Vec<Function>,
+    pub externs: Vec<Extern>,
+    /// The type of the value the program gives, if it gives one
+    pub result: Option<IrType>,
+    /// Where the code outside of functions is, from its first statement to its last
+    pub code_span: Span,
 }
 impl Builder {
     pub fn new() -> Self {
-        Builder { code: Vec::new() }
+        Builder {
+            code: Vec::new(),
+            functions: Vec::new(),
+            externs: Vec::new(),
+            result: None,
+            code_span: Span::from(0..0),
+        }
     }
     pub fn expr(&self, expr: Expr) -> Stmt{
         Stmt::ExprStmt(expr)
     }
-    pub fn stmt(&mut self, stmt: Stmt) {
+    pub fn stmt(&mut self, stmt: Stmt, span: Span) {
+        self.cover(span);
         self.code.push(stmt);
     }
+    /// Makes the span of the code reach `span`
+    fn cover(&mut self, span: Span) {
+        if self.code.is_empty() {
+            self.code_span = span;
+        } else {
+            self.code_span.end = span.end;
+        }
+    }
     pub fn int(&self, int: i64) -> Expr {
         Expr::Value(IrValue::Literal(Literal::Int(int)), IrType::Int)
     }
@@ -28,6 +57,18 @@ impl Builder {
     pub fn binary(&self, left: Expr, op: BinOp, right: Expr, ty: IrType) -> Expr {
         Expr::Value(IrValue::Binary(left.boxed(), op, right.boxed()), ty)
     }
+    pub fn int_div(&self, left: Expr, right: Expr, remainder: bool, span: Span) -> Expr {
+        Expr::IntDiv(left.boxed(), right.boxed(), remainder, span)
+    }
+    pub fn and(&self, left: Expr, right: Expr) -> Expr {
+        Expr::And(left.boxed(), right.boxed())
+    }
+    pub fn or(&self, left: Expr, right: Expr) -> Expr {
+        Expr::Or(left.boxed(), right.boxed())
+    }
+    pub fn unary(&self, op: UnOp, value: Expr, ty: IrType) -> Expr {
+        Expr::Value(IrValue::Unary(op, value.boxed()), ty)
+    }
     pub fn str_print(&self, s: Expr) -> Expr {
         Expr::PrintStr(s.boxed())
     }
@@ -40,24 +81,208 @@ impl Builder {
     pub fn new_var(&mut self, name: &str, value: Expr, ty: IrType)-> Stmt{
         Stmt::Var(name.to_owned(), value, ty)
     }
+    pub fn stack_var(&mut self, name: &str, value: Expr, ty: IrType)-> Stmt{
+        Stmt::StackVar(name.to_owned(), value, ty)
+    }
+    pub fn destructure(&mut self, names: Vec<String>, value: Expr, ty: IrType) -> Stmt {
+        Stmt::Destructure(names, value, ty)
+    }
+    pub fn address_of(&self, name: &str) -> Expr {
+        Expr::AddressOf(name.to_owned())
+    }
+    pub fn deref(&self, pointer: Expr, ty: IrType) -> Expr {
+        Expr::Deref(pointer.boxed(), ty)
+    }
+    pub fn set_deref(&self, pointer: Expr, value: Expr, ty: IrType) -> Stmt {
+        Stmt::SetDeref(pointer, value, ty)
+    }
+    pub fn wrap(&self, value: Expr, ty: IrType) -> Expr {
+        Expr::Wrap(value.boxed(), ty)
+    }
+    pub fn null(&self) -> Expr {
+        Expr::Null
+    }
+    pub fn unwrap(&self, optional: Expr, ty: IrType) -> Expr {
+        Expr::Unwrap(optional.boxed(), ty)
+    }
+    pub fn ok(&self, value: Expr, ty: IrType) -> Expr {
+        Expr::MakeResult(value.boxed(), false, ty)
+    }
+    pub fn err(&self, error: Expr, ty: IrType) -> Expr {
+        Expr::MakeResult(error.boxed(), true, ty)
+    }
+    pub fn is_err(&self, result: Expr) -> Expr {
+        Expr::IsErr(result.boxed())
+    }
+    pub fn result_value(&self, result: Expr, error: bool, ty: IrType) -> Expr {
+        Expr::ResultValue(result.boxed(), error, ty)
+    }
+    pub fn try_(&self, result: Expr, ty: IrType) -> Expr {
+        Expr::Try(result.boxed(), ty)
+    }
     pub fn get_var(&self, name: &str) -> Expr {
         Expr::GetVar(name.to_owned())
     }
-    pub fn set_var(&mut self, name: &str, value: Expr) {
-        self.code.push(Stmt::SetVar(name.to_owned(), value));
+    pub fn set_var(&self, name: &str, value: Expr) -> Stmt {
+        Stmt::SetVar(name.to_owned(), value)
+    }
+    pub fn array(&self, elements: Vec<Expr>, ty: IrType) -> Expr {
+        Expr::Array(elements, ty)
+    }
+    pub fn index(&self, array: Expr, index: Expr, ty: IrType, span: Span) -> Expr {
+        Expr::Index(array.boxed(), index.boxed(), ty, span)
+    }
+    pub fn set_index(&self, array: Expr, index: Expr, value: Expr, ty: IrType, span: Span) -> Stmt {
+        Stmt::SetIndex(array, index, value, ty, span)
+    }
+    pub fn len(&self, array: Expr) -> Expr {
+        Expr::Len(array.boxed())
+    }
+    pub fn tuple(&self, elements: Vec<Expr>, ty: IrType) -> Expr {
+        Expr::Tuple(elements, ty)
+    }
+    pub fn tuple_field(&self, tuple: Expr, index: usize, ty: IrType) -> Expr {
+        Expr::TupleField(tuple.boxed(), index, ty)
+    }
+    pub fn vector(&self, elements: Vec<Expr>, ty: IrType) -> Expr {
+        Expr::Vector(elements, ty)
+    }
+    pub fn push(&self, vector: Expr, value: Expr, ty: IrType) -> Expr {
+        Expr::Push(vector.boxed(), value.boxed(), ty)
+    }
+    pub fn pop(&self, vector: Expr, ty: IrType, span: Span) -> Expr {
+        Expr::Pop(vector.boxed(), ty, span)
+    }
+    pub fn slice(&self, s: Expr, start: Option<Expr>, end: Option<Expr>, span: Span) -> Expr {
+        Expr::Slice(s.boxed(), start.map(Expr::boxed), end.map(Expr::boxed), span)
+    }
+    pub fn convert(&self, value: Expr, from: IrType, to: IrType, span: Span) -> Expr {
+        Expr::Convert(value.boxed(), from, to, span)
+    }
+    pub fn map(&self, entries: Vec<(Expr, Expr)>, ty: IrType) -> Expr {
+        Expr::Map(entries, ty)
+    }
+    pub fn map_get(&self, map: Expr, key: Expr, ty: IrType, span: Span) -> Expr {
+        Expr::MapGet(map.boxed(), key.boxed(), ty, span)
+    }
+    pub fn map_set(&self, map: Expr, key: Expr, value: Expr, ty: IrType) -> Expr {
+        Expr::MapSet(map.boxed(), key.boxed(), value.boxed(), ty)
+    }
+    pub fn map_remove(&self, map: Expr, key: Expr, ty: IrType) -> Expr {
+        Expr::MapRemove(map.boxed(), key.boxed(), ty)
+    }
+    pub fn map_contains(&self, map: Expr, key: Expr, ty: IrType) -> Expr {
+        Expr::MapContains(map.boxed(), key.boxed(), ty)
+    }
+    pub fn map_key(&self, map: Expr, index: Expr, ty: IrType, span: Span) -> Expr {
+        Expr::MapKey(map.boxed(), index.boxed(), ty, span)
+    }
+    pub fn map_value(&self, map: Expr, index: Expr, ty: IrType, span: Span) -> Expr {
+        Expr::MapValue(map.boxed(), index.boxed(), ty, span)
+    }
+    pub fn block(&self, stmts: Vec<Stmt>) -> Stmt {
+        Stmt::Block(stmts)
+    }
+    pub fn scope(&self, stmts: Vec<Stmt>) -> Stmt {
+        Stmt::Scope(stmts)
+    }
+    pub fn while_(&self, condition: Expr, stmts: Vec<Stmt>) -> Stmt {
+        Stmt::While(condition, stmts)
     }
     pub fn if_(&self, condition: Expr, stmts: Vec<Stmt>, else_: Vec<Stmt>,) -> Stmt{
         Stmt::If(condition, stmts, else_)
     }
-    pub fn finish(&self) {
-        let mut compiler = Compiler::new();
-        let code = compiler.compile(self.code.clone());
-        match code {
-            Ok(ptr) => {
-                let run_code = unsafe { std::mem::transmute::<_, fn() -> i64>(ptr) };
-                run_code();
-            }
-            Err(e) => eprintln!("{e}"),
-        }
+    pub fn return_(&self, value: Option<Expr>) -> Stmt {
+        Stmt::Return(value)
+    }
+    pub fn call(&self, name: &str, args: Vec<Expr>, return_type: Option<IrType>) -> Expr {
+        Expr::Call(name.to_owned(), args, return_type)
+    }
+    pub fn closure(&self, name: &str, captures: Vec<(Expr, IrType)>) -> Expr {
+        Expr::Closure(name.to_owned(), captures)
+    }
+    pub fn call_indirect(&self, closure: Expr, args: Vec<Expr>, ty: IrType) -> Expr {
+        Expr::CallIndirect(closure.boxed(), args, ty)
+    }
+    pub fn call_extern(
+        &self,
+        name: &str,
+        args: Vec<(Expr, IrType)>,
+        return_type: Option<IrType>,
+    ) -> Expr {
+        Expr::CallExtern(name.to_owned(), args, return_type)
+    }
+    pub fn call_host(
+        &self,
+        name: &str,
+        args: Vec<(Expr, IrType)>,
+        return_type: Option<IrType>,
+        span: Span,
+    ) -> Expr {
+        Expr::CallHost(name.to_owned(), args, return_type, span)
+    }
+    pub fn extern_function(&mut self, function: Extern) {
+        self.externs.push(function);
+    }
+    pub fn function(
+        &mut self,
+        name: &str,
+        params: Vec<(String, IrType)>,
+        return_type: Option<IrType>,
+        body: Vec<Stmt>,
+        span: Span,
+    ) {
+        self.functions.push(Function {
+            name: name.to_owned(),
+            params,
+            return_type,
+            captures: None,
+            body,
+            span,
+        });
+    }
+    pub fn closure_function(
+        &mut self,
+        name: &str,
+        captures: Vec<(String, IrType)>,
+        params: Vec<(String, IrType)>,
+        return_type: Option<IrType>,
+        body: Vec<Stmt>,
+        span: Span,
+    ) {
+        self.functions.push(Function {
+            name: name.to_owned(),
+            params,
+            return_type,
+            captures: Some(captures),
+            body,
+            span,
+        });
+    }
+    /// Ends the program by giving the value of `expr`
+    pub fn result(&mut self, expr: Expr, ty: IrType, span: Span) {
+        self.cover(span);
+        self.code.push(Stmt::Return(Some(expr)));
+        self.result = Some(ty);
+    }
+    /// Compiles the program, host functions are called at the given addresses
+    pub fn compile(&self, hosts: HashMap<String, *const Host>) -> Result<Program, JitError> {
+        let compiler = Compiler::new(&self.externs, hosts).map_err(JitError::Link)?;
+        compiler.compile(
+            self.functions.clone(),
+            self.code.clone(),
+            self.code_span,
+            self.result.clone(),
+        )
+    }
+    /// The Cranelift IR of the program
+    pub fn emit(&self, hosts: HashMap<String, *const Host>) -> Result<String, JitError> {
+        let compiler = Compiler::new(&self.externs, hosts).map_err(JitError::Link)?;
+        compiler.emit(
+            self.functions.clone(),
+            self.code.clone(),
+            self.code_span,
+            self.result.clone(),
+        )
     }
 }
\ No newline at end of file
@@ -1,44 +1,255 @@
+use cranelift::codegen::ir::StackSlot;
+use cranelift::codegen::print_errors::pretty_verifier_error;
 use cranelift::prelude::{settings::Flags, *};
 use cranelift_jit::{JITBuilder, JITModule};
-use cranelift_module::{DataContext, FuncId, Linkage, Module};
+use cranelift_module::{DataContext, FuncId, FuncOrDataId, Linkage, Module};
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::panic;
 
-use super::ir::*;
+use super::ffi::{self, Kind, Passing};
+use super::runtime::{Arena, Host, RuntimeError};
+use super::unwind::Frames;
+use super::{ir::*, runtime};
+use crate::error::{bug, catch_bug};
+use crate::token::Span;
 pub struct Compiler {
     module: JITModule,
     builder_ctx: FunctionBuilderContext,
     ctx: codegen::Context,
+    /// The C functions the code can call, by their name
+    externs: HashMap<String, Extern>,
+    /// The shared libraries the C functions are in, they stay loaded while the code runs
+    _libraries: Vec<libloading::Library>,
+    /// The functions of the program embedding Illusio, by their name
+    hosts: HashMap<String, *const Host>,
+    /// Where the function being compiled is in the source
+    span: Span,
+}
+/// Why a program couldn't be compiled
+#[derive(Debug)]
+pub enum JitError {
+    /// A C function or library that can't be found
+    Link(String),
+    /// A bug in the compiler, found while compiling the code at the span
+    Bug(String, Span),
+}
+/// A compiled program, its code and the values it made stay in memory while it exists
+pub struct Program {
+    main: *const u8,
+    /// The type of the value the program gives, if it gives one
+    result: Option<IrType>,
+    /// What the runtime allocated for every run of the program
+    arena: RefCell<Arena>,
+    _frames: Frames,
+    _compiler: Compiler,
+}
+impl Program {
+    /// Runs the program, giving the 8 bytes of the value it gives.
+    /// Runtime errors unwind back to here.
+    pub fn run(&self) -> Result<u64, RuntimeError> {
+        let main = self.main;
+        let result = self.result.clone();
+        let run = move || unsafe {
+            match result {
+                Some(IrType::Float) => {
+                    std::mem::transmute::<*const u8, extern "C-unwind" fn() -> f64>(main)()
+                        .to_bits()
+                }
+                _ => std::mem::transmute::<*const u8, extern "C-unwind" fn() -> u64>(main)(),
+            }
+        };
+        // Allocations go to the arena of the program while it runs. Host functions can run
+        // other programs or this one again, which put the arena in use back when they end.
+        let previous = runtime::swap_arena(self.arena.take());
+        let outcome = panic::catch_unwind(run);
+        let arena = runtime::swap_arena(previous);
+        self.arena.borrow_mut().merge(arena);
+        match outcome {
+            // Booleans are only in the lowest bit
+            Ok(value) if matches!(self.result, Some(IrType::Bool)) => Ok(value & 1),
+            Ok(value) => Ok(value),
+            Err(payload) => match payload.downcast::<RuntimeError>() {
+                Ok(error) => Err(*error),
+                Err(payload) => panic::resume_unwind(payload),
+            },
+        }
+    }
 }
 impl Compiler {
-    pub fn new() -> Self {
-        let builder = JITBuilder::new(cranelift_module::default_libcall_names());
-        let module = JITModule::new(builder.unwrap());
-        Self {
+    pub fn new(externs: &[Extern], hosts: HashMap<String, *const Host>) -> Result<Self, String> {
+        let mut builder = JITBuilder::new(cranelift_module::default_libcall_names()).unwrap();
+        builder.symbols(runtime::symbols());
+        let (symbols, libraries) = ffi::resolve(externs)?;
+        builder.symbols(symbols);
+        let module = JITModule::new(builder);
+        Ok(Self {
             builder_ctx: FunctionBuilderContext::new(),
             ctx: module.make_context(),
             module,
+            externs: externs
+                .iter()
+                .map(|function| (function.name.clone(), function.clone()))
+                .collect(),
+            _libraries: libraries,
+            hosts,
+            span: Span::from(0..0),
+        })
+    }
+    /// Compiles the functions and the code of the program, which gives a value
+    /// of type `result` when it has one.
+    pub fn compile(
+        mut self,
+        functions: Vec<Function>,
+        stmts: Vec<Stmt>,
+        code_span: Span,
+        result: Option<IrType>,
+    ) -> Result<Program, JitError> {
+        let mut unwind = Vec::new();
+        let id = self.catching(|compiler| {
+            compiler.translate_all(
+                functions,
+                stmts,
+                code_span,
+                result.clone(),
+                &mut |compiler, _, id| compiler.define(id, &mut unwind),
+            )
+        })?;
+        self.module.finalize_definitions();
+        let main = self.module.get_finalized_function(id);
+        let unwind = unwind
+            .into_iter()
+            .map(|(id, info)| (self.module.get_finalized_function(id), info))
+            .collect();
+        Ok(Program {
+            main,
+            result,
+            arena: RefCell::default(),
+            _frames: Frames::register(self.module.isa(), unwind),
+            _compiler: self,
+        })
+    }
+    /// The Cranelift IR of the functions and the code of the program, without compiling it
+    pub fn emit(
+        mut self,
+        functions: Vec<Function>,
+        stmts: Vec<Stmt>,
+        code_span: Span,
+        result: Option<IrType>,
+    ) -> Result<String, JitError> {
+        let mut ir = String::new();
+        self.catching(|compiler| {
+            compiler.translate_all(
+                functions,
+                stmts,
+                code_span,
+                result,
+                &mut |compiler, name, _| {
+                    ir += &format!("; {}\n{}\n", name, compiler.ctx.func);
+                    compiler.module.clear_context(&mut compiler.ctx);
+                    Ok(())
+                },
+            )
+        })?;
+        Ok(ir)
+    }
+    /// Runs `compile`, its errors and panics are bugs in the function being compiled
+    fn catching<T>(
+        &mut self,
+        compile: impl FnOnce(&mut Self) -> Result<T, String>,
+    ) -> Result<T, JitError> {
+        match catch_bug(|| compile(self)) {
+            Ok(Ok(value)) => Ok(value),
+            Ok(Err(message)) | Err(message) => Err(JitError::Bug(message, self.span)),
         }
     }
-    pub fn compile(&mut self, stmts: Vec<Stmt>) -> Result<*const u8, String> {
-        self.translate(stmts)?;
+    /// Translates every function and then the code of the program as `main`,
+    /// `finish` is given each of them by its name after it is translated.
+    /// Gives the id of `main`.
+    fn translate_all(
+        &mut self,
+        functions: Vec<Function>,
+        mut stmts: Vec<Stmt>,
+        code_span: Span,
+        result: Option<IrType>,
+        finish: &mut dyn FnMut(&mut Self, &str, FuncId) -> Result<(), String>,
+    ) -> Result<FuncId, String> {
+        for function in self.externs.values() {
+            let (sig, ..) = ffi::signature(&self.module, &function.params, &function.return_type);
+            self.module
+                .declare_function(&function.name, Linkage::Import, &sig)
+                .map_err(|err| err.to_string())?;
+        }
+        // Declaring every function first, so they can call each other in any order
+        let mut ids = Vec::new();
+        for function in &functions {
+            let params: Vec<IrType> = function.params.iter().map(|(_, ty)| ty.clone()).collect();
+            let sig = signature(
+                &self.module,
+                function.captures.is_some(),
+                &params,
+                &function.return_type,
+            );
+            let id = self
+                .module
+                .declare_function(&function.name, Linkage::Local, &sig)
+                .map_err(|err| err.to_string())?;
+            ids.push(id);
+        }
+        for (function, id) in functions.into_iter().zip(ids) {
+            self.span = function.span;
+            self.translate(
+                function.params,
+                function.return_type,
+                function.captures,
+                function.body,
+            )?;
+            finish(self, &function.name, id)?;
+        }
+
+        // The value of the program is returned by the code, otherwise it returns 0
+        if result.is_none() {
+            stmts.push(Stmt::Return(Some(Expr::Value(
+                IrValue::Literal(Literal::Int(0)),
+                IrType::Int,
+            ))));
+        }
+        let return_type = result.or(Some(IrType::Int));
+        self.span = code_span;
+        self.translate(vec![], return_type, None, stmts)?;
         let id = self
             .module
             .declare_function("main", Linkage::Export, &self.ctx.func.signature)
             .map_err(|err| err.to_string())?;
+        finish(self, "main", id)?;
+        Ok(id)
+    }
+    /// Defines the function `id` with the function being compiled, and keeps how to unwind through it
+    fn define(
+        &mut self,
+        id: FuncId,
+        unwind: &mut Vec<(FuncId, codegen::isa::unwind::UnwindInfo)>,
+    ) -> Result<(), String> {
         self.module
             .define_function(id, &mut self.ctx)
             .map_err(|e| e.to_string())?;
+        let info = self
+            .ctx
+            .create_unwind_info(self.module.isa())
+            .map_err(|e| e.to_string())?;
+        unwind.extend(info.map(|info| (id, info)));
         self.module.clear_context(&mut self.ctx);
-        self.module.finalize_definitions();
-        let code = self.module.get_finalized_function(id);
-        Ok(code)
-    }
-    pub fn translate(&mut self, stmts: Vec<Stmt>) -> Result<(), String> {
-        self.ctx
-            .func
-            .signature
-            .returns
-            .push(AbiParam::new(types::I64));
+        Ok(())
+    }
+    pub fn translate(
+        &mut self,
+        params: Vec<(String, IrType)>,
+        return_type: Option<IrType>,
+        captures: Option<Vec<(String, IrType)>>,
+        stmts: Vec<Stmt>,
+    ) -> Result<(), String> {
+        let types: Vec<IrType> = params.iter().map(|(_, ty)| ty.clone()).collect();
+        self.ctx.func.signature = signature(&self.module, captures.is_some(), &types, &return_type);
         let mut builder = FunctionBuilder::new(&mut self.ctx.func, &mut self.builder_ctx);
         let entry_block = builder.create_block();
         builder.append_block_params_for_function_params(entry_block);
@@ -47,39 +258,65 @@ impl Compiler {
         let mut handler = Handler {
             builder,
             variables: HashMap::new(),
+            slots: HashMap::new(),
+            var_index: 0,
             data: DataContext::new(),
             module: &mut self.module,
+            externs: &self.externs,
+            hosts: &self.hosts,
+            return_type: return_type.clone(),
+            env: None,
+            captured: HashMap::new(),
         };
+        let mut values = handler.builder.block_params(entry_block).to_vec();
+        // Closures read the variables they capture from the closure itself
+        if let Some(captures) = captures {
+            handler.env = Some(values.remove(0));
+            for (i, (name, ty)) in captures.into_iter().enumerate() {
+                handler.captured.insert(name, (8 * (i as i32 + 1), ty));
+            }
+        }
+        for ((name, ty), val) in params.into_iter().zip(values) {
+            handler.declare_var(name, val, &ty);
+        }
         for stmt in stmts {
             handler.translate_stmt(stmt);
         }
-        let zero = handler.builder.ins().iconst(types::I64, 0);
-        handler.builder.ins().return_(&[zero]);
+        match return_type {
+            // Falling off the end of a function without returning a value,
+            // the type checker makes sure this is never reached.
+            Some(_) => handler.builder.ins().trap(TrapCode::UnreachableCodeReached),
+            None => handler.builder.ins().return_(&[]),
+        };
         handler.builder.finalize();
-        println!("{}", self.ctx.func);
-        if let Err(e) = codegen::verify_function(
+        codegen::verify_function(
             &self.ctx.func,
             &Flags::new(cranelift::codegen::settings::builder()),
-        ) {
-            println!("codegen error");
-
-            let mut s = String::new();
-
-            s.clear();
-
-            codegen::write_function(&mut s, &self.ctx.func).unwrap();
-            println!("CLIF:\n{}", s);
-
-            panic!("errors: {:#?}", e);
-        }
-        Ok(())
+        )
+        .map_err(|errors| {
+            format!(
+                "Cranelift rejected the code of the function:\n{}",
+                pretty_verifier_error(&self.ctx.func, None, errors)
+            )
+        })
     }
 }
 pub struct Handler<'a> {
     builder: FunctionBuilder<'a>,
     data: DataContext,
     module: &'a mut JITModule,
+    externs: &'a HashMap<String, Extern>,
+    hosts: &'a HashMap<String, *const Host>,
     variables: HashMap<String, Variable>,
+    /// Variables kept in stack slots, because their address is taken
+    slots: HashMap<String, (StackSlot, IrType)>,
+    var_index: usize,
+    /// The return type of the function being translated
+    return_type: Option<IrType>,
+    /// The closure, when a closure is being translated
+    env: Option<Value>,
+    /// The variables the closure captures, with their offset in it and their type
+    captured: HashMap<String, (i32, IrType)>,
 }
 impl<'a> Handler<'a> {
     fn translate_stmt(&mut self, stmt: Stmt) -> Value {
@@ -109,20 +346,14 @@ impl<'a> Handler<'a> {
 
                 self.builder.switch_to_block(then_block);
                 self.builder.seal_block(then_block);
-                let mut then_return = self.builder.ins().iconst(types::I64, 0);
-                for stmt in then_body {
-                    then_return = self.translate_stmt(stmt);
-                }
+                let then_return = self.scoped(then_body);
 
                 // Jump to the merge block, passing it the block return value.
                 self.builder.ins().jump(merge_block, &[then_return]);
 
                 self.builder.switch_to_block(else_block);
                 self.builder.seal_block(else_block);
-                let mut else_return = self.builder.ins().iconst(types::I64, 0);
-                for stmt in else_body {
-                    else_return = self.translate_stmt(stmt);
-                }
+                let else_return = self.scoped(else_body);
 
                 // Jump to the merge block, passing it the block return value.
                 self.builder.ins().jump(merge_block, &[else_return]);
@@ -137,22 +368,149 @@ impl<'a> Handler<'a> {
             }
             Stmt::SetVar(name, expr) => {
                 let val = self.translate_expr(expr);
-                let var = self.variables.get(&name).expect("Variable not found");
-                self.builder.def_var(*var, val);
+                if let Some((slot, ty)) = self.slots.get(&name).cloned() {
+                    let address = self.builder.ins().stack_addr(types::I64, slot, 0);
+                    self.store_element(&ty, val, address, 0);
+                    return self.null();
+                }
+                if let Some((offset, ty)) = self.captured.get(&name).cloned() {
+                    let env = self.env.unwrap();
+                    self.store_element(&ty, val, env, offset);
+                    return self.null();
+                }
+                let var = self.variable(&name);
+                self.builder.def_var(var, val);
+                self.null()
+            }
+            Stmt::SetIndex(array, index, value, ty, span) => {
+                let array = self.translate_expr(array);
+                let index = self.translate_expr(index);
+                let value = self.translate_expr(value);
+                let address = self.element_address(array, index, elements_offset(&ty), span);
+                self.store_element(element_type(&ty), value, address, 0);
+                self.null()
+            }
+            Stmt::Block(stmts) => {
+                for stmt in stmts {
+                    self.translate_stmt(stmt);
+                }
+                self.null()
+            }
+            Stmt::Scope(stmts) => {
+                self.scoped(stmts);
+                self.null()
+            }
+            Stmt::While(condition, body) => {
+                let header_block = self.builder.create_block();
+                let body_block = self.builder.create_block();
+                let exit_block = self.builder.create_block();
+                self.builder.ins().jump(header_block, &[]);
+
+                self.builder.switch_to_block(header_block);
+                let condition_value = self.translate_expr(condition);
+                self.builder.ins().brz(condition_value, exit_block, &[]);
+                self.builder.ins().jump(body_block, &[]);
+
+                self.builder.switch_to_block(body_block);
+                self.builder.seal_block(body_block);
+                self.scoped(body);
+                self.builder.ins().jump(header_block, &[]);
+
+                // The header can only be sealed once the jump back to it exists
+                self.builder.seal_block(header_block);
+                self.builder.switch_to_block(exit_block);
+                self.builder.seal_block(exit_block);
                 self.null()
             }
             Stmt::Var(name, expr, var_type) => {
-                let index: usize = self.variables.len();
-                let var: Variable = Variable::new(index);
                 let val: Value = self.translate_expr(expr);
-                let ty = self.to_cranelift_ty(&var_type);
-                self.variables.insert(name, var);
-                self.builder.declare_var(var, ty);
-                self.builder.def_var(var, val);
+                self.declare_var(name, val, &var_type);
+                self.null()
+            }
+            Stmt::StackVar(name, expr, var_type) => {
+                let val = self.translate_expr(expr);
+                let slot = self
+                    .builder
+                    .create_sized_stack_slot(StackSlotData::new(StackSlotKind::ExplicitSlot, 8));
+                let address = self.builder.ins().stack_addr(types::I64, slot, 0);
+                self.store_element(&var_type, val, address, 0);
+                self.captured.remove(&name);
+                self.variables.remove(&name);
+                self.slots.insert(name, (slot, var_type));
+                self.null()
+            }
+            Stmt::Destructure(names, value, ty) => {
+                let values = self.tuple_values(value, &ty);
+                let fields = match &ty {
+                    IrType::Tuple(fields) => fields,
+                    _ => unreachable!(),
+                };
+                for ((name, value), field) in names.into_iter().zip(values).zip(fields) {
+                    self.declare_var(name, value, field);
+                }
+                self.null()
+            }
+            Stmt::SetDeref(pointer, value, ty) => {
+                let pointer = self.translate_expr(pointer);
+                let value = self.translate_expr(value);
+                self.store_element(element_type(&ty), value, pointer, 0);
+                self.null()
+            }
+            Stmt::Return(value) => {
+                match value {
+                    Some(value) if matches!(self.return_type, Some(IrType::Tuple(_))) => {
+                        let ty = self.return_type.clone().unwrap();
+                        let values = self.tuple_values(value, &ty);
+                        self.builder.ins().return_(&values);
+                    }
+                    Some(value) => {
+                        let value = self.translate_expr(value);
+                        self.builder.ins().return_(&[value]);
+                    }
+                    None => {
+                        self.builder.ins().return_(&[]);
+                    }
+                }
+                // Anything after a return is unreachable,
+                // but it still needs a block to live in.
+                let block = self.builder.create_block();
+                self.builder.switch_to_block(block);
+                self.builder.seal_block(block);
                 self.null()
             }
         }
     }
+    /// Translates statements in their own scope, giving the value of the last one.
+    /// The variables they declare are gone after them, and the ones they shadow are back.
+    fn scoped(&mut self, stmts: Vec<Stmt>) -> Value {
+        let variables = self.variables.clone();
+        let slots = self.slots.clone();
+        let captured = self.captured.clone();
+        let mut last = self.null();
+        for stmt in stmts {
+            last = self.translate_stmt(stmt);
+        }
+        self.variables = variables;
+        self.slots = slots;
+        self.captured = captured;
+        last
+    }
+    fn variable(&self, name: &str) -> Variable {
+        match self.variables.get(name) {
+            Some(var) => *var,
+            None => bug(format!("Variable `{}` was never declared", name)),
+        }
+    }
+    fn declare_var(&mut self, name: String, val: Value, ty: &IrType) {
+        // Every declaration gets its own variable, even when shadowing
+        let var = Variable::new(self.var_index);
+        self.var_index += 1;
+        self.captured.remove(&name);
+        self.slots.remove(&name);
+        self.variables.insert(name, var);
+        self.builder.declare_var(var, to_cranelift_ty(ty));
+        self.builder.def_var(var, val);
+    }
     fn null(&mut self) -> Value {
         self.builder.ins().iconst(types::I64, 0)
     }
@@ -160,34 +518,93 @@ impl<'a> Handler<'a> {
         use Expr::*;
         match expr {
             GetVar(name) => {
-                let var = self.variables.get(&name).expect("Variable not found");
-                self.builder.use_var(*var)
+                if let Some((slot, ty)) = self.slots.get(&name).cloned() {
+                    let address = self.builder.ins().stack_addr(types::I64, slot, 0);
+                    return self.load_element(&ty, address);
+                }
+                if let Some((offset, ty)) = self.captured.get(&name).cloned() {
+                    let address = self
+                        .builder
+                        .ins()
+                        .iadd_imm(self.env.unwrap(), offset as i64);
+                    return self.load_element(&ty, address);
+                }
+                let var = self.variable(&name);
+                self.builder.use_var(var)
             }
             Value(val, ir_ty) => {
-                let ty = self.to_cranelift_ty(&ir_ty);
+                let ty = to_cranelift_ty(&ir_ty);
                 match val {
                     IrValue::Literal(lit) => match lit {
                         Literal::Int(i) => self.builder.ins().iconst(ty, i),
                         Literal::Float(f) => self.builder.ins().f64const(f),
                         Literal::Str(s) => {
-                            let mut string = s;
-                            string.push('\0');
-                            let boxed_s = string.into_bytes().into_boxed_slice();
-                            let id = self.module.declare_anonymous_data(false, false).unwrap();
+                            let len = s.len() as i64;
+                            let mut bytes = s.into_bytes();
+                            // Keeping a NUL at the end for C functions
+                            bytes.push(b'\0');
+                            let bytes_id =
+                                self.module.declare_anonymous_data(false, false).unwrap();
+                            self.data.define(bytes.into_boxed_slice());
+                            self.module.define_data(bytes_id, &self.data).unwrap();
+                            self.data.clear();
 
-                            self.data.define(boxed_s);
+                            // The string is its length followed by a pointer to its bytes
+                            let id = self.module.declare_anonymous_data(false, false).unwrap();
+                            let mut header = len.to_ne_bytes().to_vec();
+                            header.extend([0; 8]);
+                            self.data.define(header.into_boxed_slice());
+                            self.data.set_align(8);
+                            let bytes = self.module.declare_data_in_data(bytes_id, &mut self.data);
+                            self.data.write_data_addr(8, bytes, 0);
                             self.module.define_data(id, &self.data).unwrap();
                             self.data.clear();
-                            let value =
-                                self.module.declare_data_in_func(id, &mut self.builder.func);
+                            let value = self.module.declare_data_in_func(id, self.builder.func);
 
                             self.builder.ins().global_value(types::I64, value)
                         }
                         Literal::Bool(b) => self.builder.ins().bconst(ty, b),
                     },
-                    IrValue::Binary(left, op, right) => {
+                    IrValue::Unary(op, value) => {
+                        let value = self.translate_expr(*value);
+                        match op {
+                            UnOp::Neg if ty.is_float() => self.builder.ins().fneg(value),
+                            UnOp::Neg => self.builder.ins().ineg(value),
+                            UnOp::Not | UnOp::BitNot => self.builder.ins().bnot(value),
+                        }
+                    }
+                    IrValue::Binary(left, op, right) if matches!(ir_ty, IrType::Str) => {
                         let left = self.translate_expr(*left);
                         let right = self.translate_expr(*right);
+                        if let BinOp::Add = op {
+                            return self.call_runtime(
+                                "illusio_str_concat",
+                                &[left, right],
+                                Some(types::I64),
+                            );
+                        }
+                        // Strings are compared by their contents
+                        let cmp =
+                            self.call_runtime("illusio_str_cmp", &[left, right], Some(types::I64));
+                        let cc = match op {
+                            BinOp::Eq => IntCC::Equal,
+                            BinOp::Neq => IntCC::NotEqual,
+                            BinOp::Gt => IntCC::SignedGreaterThan,
+                            BinOp::Lt => IntCC::SignedLessThan,
+                            BinOp::Ge => IntCC::SignedGreaterThanOrEqual,
+                            BinOp::Le => IntCC::SignedLessThanOrEqual,
+                            _ => unreachable!(),
+                        };
+                        self.builder.ins().icmp_imm(cc, cmp, 0)
+                    }
+                    IrValue::Binary(left, op, right) => {
+                        let mut left = self.translate_expr(*left);
+                        let mut right = self.translate_expr(*right);
+                        if ty.is_bool() {
+                            // Booleans can't be compared directly, so comparing them as integers
+                            left = self.builder.ins().bint(types::I8, left);
+                            right = self.builder.ins().bint(types::I8, right);
+                        }
                         match op {
                             BinOp::Add => {
                                 if ty.is_float() {
@@ -214,7 +631,7 @@ impl<'a> Handler<'a> {
                                 if ty.is_float() {
                                     self.builder.ins().fdiv(left, right)
                                 } else {
-                                    self.builder.ins().udiv(left, right)
+                                    self.builder.ins().sdiv(left, right)
                                 }
                             }
                             BinOp::Eq => {
@@ -275,71 +692,825 @@ impl<'a> Handler<'a> {
                                     )
                                 }
                             }
+                            // Bitwise operators only work on ints
+                            BinOp::BitAnd => self.builder.ins().band(left, right),
+                            BinOp::BitOr => self.builder.ins().bor(left, right),
+                            BinOp::BitXor => self.builder.ins().bxor(left, right),
+                            BinOp::Shl => self.builder.ins().ishl(left, right),
+                            BinOp::Shr => self.builder.ins().sshr(left, right),
+                            BinOp::UShr => self.builder.ins().ushr(left, right),
                         }
                     }
                 }
             }
+            Call(name, args, return_type) => {
+                let results = self.call(&name, args);
+                self.returned(&results, return_type.as_ref())
+            }
+            Closure(name, captures) => {
+                let size = self
+                    .builder
+                    .ins()
+                    .iconst(types::I64, 8 * (captures.len() as i64 + 1));
+                let closure = self.call_runtime("illusio_alloc", &[size], Some(types::I64));
+                let id = match self.module.get_name(&name) {
+                    Some(FuncOrDataId::Func(id)) => id,
+                    _ => bug(format!("Function `{}` was never declared", name)),
+                };
+                let function = self.module.declare_func_in_func(id, self.builder.func);
+                let code = self.builder.ins().func_addr(types::I64, function);
+                self.builder
+                    .ins()
+                    .store(MemFlags::trusted(), code, closure, 0);
+                for (i, (value, ty)) in captures.into_iter().enumerate() {
+                    let value = self.translate_expr(value);
+                    self.store_element(&ty, value, closure, 8 * (i as i32 + 1));
+                }
+                closure
+            }
+            CallExtern(name, args, return_type) => {
+                if self.externs[&name].variadic {
+                    return self.call_variadic(&name, args, return_type);
+                }
+                let (results, passing) = self.call_extern(&name, args);
+                match (return_type, passing) {
+                    (Some(ty), Some(Passing::Fields)) => self.pack(&results, &ty),
+                    (Some(ty), Some(Passing::Value)) => self.c_result(&ty, results[0]),
+                    // The memory the tuple was returned in
+                    (Some(_), Some(Passing::Memory(_))) => results[0],
+                    _ => self.null(),
+                }
+            }
+            CallHost(name, args, return_type, span) => {
+                let result = self.call_host(&name, args, span);
+                match return_type {
+                    Some(IrType::Float) => self.builder.ins().bitcast(types::F64, result),
+                    Some(IrType::Bool) => self.builder.ins().icmp_imm(IntCC::NotEqual, result, 0),
+                    Some(_) => result,
+                    None => self.null(),
+                }
+            }
+            CallIndirect(closure, args, ty) => {
+                let results = self.call_indirect(*closure, args, &ty);
+                let return_type = match &ty {
+                    IrType::Function(_, return_type) => return_type.as_deref(),
+                    _ => unreachable!(),
+                };
+                self.returned(&results, return_type)
+            }
+            Tuple(elements, ty) => {
+                let values: Vec<cranelift::prelude::Value> = elements
+                    .into_iter()
+                    .map(|element| self.translate_expr(element))
+                    .collect();
+                self.pack(&values, &ty)
+            }
+            TupleField(tuple, index, ty) => {
+                let tuple = self.translate_expr(*tuple);
+                let address = self.builder.ins().iadd_imm(tuple, 8 * index as i64);
+                self.load_element(&field_types(&ty)[index], address)
+            }
+            Array(elements, ty) => {
+                let size = self
+                    .builder
+                    .ins()
+                    .iconst(types::I64, 8 * (elements.len() as i64 + 1));
+                let array = self.call_runtime("illusio_alloc", &[size], Some(types::I64));
+                let len = self.builder.ins().iconst(types::I64, elements.len() as i64);
+                self.builder.ins().store(MemFlags::trusted(), len, array, 0);
+                for (i, element) in elements.into_iter().enumerate() {
+                    let element = self.translate_expr(element);
+                    self.store_element(&ty, element, array, 8 * (i as i32 + 1));
+                }
+                array
+            }
+            Vector(elements, ty) => {
+                let vector = self.call_runtime("illusio_vec_new", &[], Some(types::I64));
+                for element in elements {
+                    let element = self.translate_expr(element);
+                    let address =
+                        self.call_runtime("illusio_vec_push", &[vector], Some(types::I64));
+                    self.store_element(&ty, element, address, 0);
+                }
+                vector
+            }
+            AddressOf(name) if self.captured.contains_key(&name) => {
+                let (offset, _) = self.captured[&name];
+                self.builder
+                    .ins()
+                    .iadd_imm(self.env.unwrap(), offset as i64)
+            }
+            AddressOf(name) => {
+                let (slot, _) = self.slots[&name];
+                self.builder.ins().stack_addr(types::I64, slot, 0)
+            }
+            Deref(pointer, ty) => {
+                let pointer = self.translate_expr(*pointer);
+                self.load_element(element_type(&ty), pointer)
+            }
+            Wrap(value, ty) => {
+                let value = self.translate_expr(*value);
+                let inner = element_type(&ty);
+                if is_pointer(inner) {
+                    return value;
+                }
+                let size = self.builder.ins().iconst(types::I64, 8);
+                let boxed = self.call_runtime("illusio_alloc", &[size], Some(types::I64));
+                self.store_element(inner, value, boxed, 0);
+                boxed
+            }
+            Null => self.null(),
+            MakeResult(value, error, ty) => {
+                let value = self.translate_expr(*value);
+                let size = self.builder.ins().iconst(types::I64, 16);
+                let result = self.call_runtime("illusio_alloc", &[size], Some(types::I64));
+                let flag = self.builder.ins().iconst(types::I64, error as i64);
+                self.builder
+                    .ins()
+                    .store(MemFlags::trusted(), flag, result, 0);
+                self.store_element(result_type(&ty, error), value, result, 8);
+                result
+            }
+            IsErr(result) => {
+                let result = self.translate_expr(*result);
+                let flag = self
+                    .builder
+                    .ins()
+                    .load(types::I64, MemFlags::trusted(), result, 0);
+                self.builder.ins().icmp_imm(IntCC::NotEqual, flag, 0)
+            }
+            ResultValue(result, error, ty) => {
+                let result = self.translate_expr(*result);
+                let address = self.builder.ins().iadd_imm(result, 8);
+                self.load_element(result_type(&ty, error), address)
+            }
+            Try(result, ty) => {
+                let result = self.translate_expr(*result);
+                let flag = self
+                    .builder
+                    .ins()
+                    .load(types::I64, MemFlags::trusted(), result, 0);
+                let error_block = self.builder.create_block();
+                let ok_block = self.builder.create_block();
+                self.builder.ins().brnz(flag, error_block, &[]);
+                self.builder.ins().jump(ok_block, &[]);
+
+                // The error has the type the function returns, so the result is returned as is
+                self.builder.switch_to_block(error_block);
+                self.builder.seal_block(error_block);
+                self.builder.ins().return_(&[result]);
+
+                self.builder.switch_to_block(ok_block);
+                self.builder.seal_block(ok_block);
+                let address = self.builder.ins().iadd_imm(result, 8);
+                self.load_element(result_type(&ty, false), address)
+            }
+            Unwrap(optional, ty) => {
+                let optional = self.translate_expr(*optional);
+                let inner = element_type(&ty);
+                if is_pointer(inner) {
+                    return optional;
+                }
+                self.load_element(inner, optional)
+            }
+            Index(array, index, ty, span) => {
+                let array = self.translate_expr(*array);
+                let index = self.translate_expr(*index);
+                let address = self.element_address(array, index, elements_offset(&ty), span);
+                self.load_element(element_type(&ty), address)
+            }
+            IntDiv(left, right, remainder, span) => {
+                let left = self.translate_expr(*left);
+                let right = self.translate_expr(*right);
+                self.int_div(left, right, remainder, span)
+            }
+            And(left, right) => self.short_circuit(*left, *right, false),
+            Or(left, right) => self.short_circuit(*left, *right, true),
+            Push(vector, value, ty) => {
+                let vector = self.translate_expr(*vector);
+                let value = self.translate_expr(*value);
+                let address = self.call_runtime("illusio_vec_push", &[vector], Some(types::I64));
+                self.store_element(&ty, value, address, 0);
+                self.null()
+            }
+            Pop(vector, ty, span) => {
+                let vector = self.translate_expr(*vector);
+                let start = self.builder.ins().iconst(types::I64, span.start as i64);
+                let end = self.builder.ins().iconst(types::I64, span.end as i64);
+                let address =
+                    self.call_runtime("illusio_vec_pop", &[vector, start, end], Some(types::I64));
+                self.load_element(&ty, address)
+            }
+            Slice(s, start, end, span) => {
+                let s = self.translate_expr(*s);
+                let start = match start {
+                    Some(start) => self.translate_expr(*start),
+                    None => self.builder.ins().iconst(types::I64, 0),
+                };
+                let end = match end {
+                    Some(end) => self.translate_expr(*end),
+                    None => self
+                        .builder
+                        .ins()
+                        .load(types::I64, MemFlags::trusted(), s, 0),
+                };
+                let span_start = self.builder.ins().iconst(types::I64, span.start as i64);
+                let span_end = self.builder.ins().iconst(types::I64, span.end as i64);
+                self.call_runtime(
+                    "illusio_str_slice",
+                    &[s, start, end, span_start, span_end],
+                    Some(types::I64),
+                )
+            }
+            Convert(value, from, to, span) => {
+                let value = self.translate_expr(*value);
+                let start = self.builder.ins().iconst(types::I64, span.start as i64);
+                let end = self.builder.ins().iconst(types::I64, span.end as i64);
+                match (from, to) {
+                    (IrType::Str, IrType::Int) => self.call_runtime(
+                        "illusio_str_to_int",
+                        &[value, start, end],
+                        Some(types::I64),
+                    ),
+                    (IrType::Str, IrType::Float) => self.call_runtime(
+                        "illusio_str_to_float",
+                        &[value, start, end],
+                        Some(types::F64),
+                    ),
+                    (IrType::Int, IrType::Str) => {
+                        self.call_runtime("illusio_int_to_str", &[value], Some(types::I64))
+                    }
+                    (IrType::Float, IrType::Str) => {
+                        self.call_runtime("illusio_float_to_str", &[value], Some(types::I64))
+                    }
+                    (IrType::Bool, IrType::Str) => {
+                        let value = self.builder.ins().bint(types::I64, value);
+                        self.call_runtime("illusio_bool_to_str", &[value], Some(types::I64))
+                    }
+                    (IrType::Float, IrType::Int) => {
+                        self.builder.ins().fcvt_to_sint_sat(types::I64, value)
+                    }
+                    (IrType::Int, IrType::Float) => {
+                        self.builder.ins().fcvt_from_sint(types::F64, value)
+                    }
+                    (IrType::Bool, IrType::Int) => self.builder.ins().bint(types::I64, value),
+                    // Converting to the same type
+                    _ => value,
+                }
+            }
+            Map(entries, ty) => {
+                let (key_ty, value_ty) = entry_types(&ty);
+                let string_keys = matches!(key_ty, IrType::Str) as i64;
+                let string_keys = self.builder.ins().iconst(types::I64, string_keys);
+                let map = self.call_runtime("illusio_map_new", &[string_keys], Some(types::I64));
+                for (key, value) in entries {
+                    let key = self.translate_expr(key);
+                    let key = self.slot_value(key_ty, key);
+                    let value = self.translate_expr(value);
+                    let address =
+                        self.call_runtime("illusio_map_set", &[map, key], Some(types::I64));
+                    self.store_element(value_ty, value, address, 0);
+                }
+                map
+            }
+            MapGet(map, key, ty, span) => {
+                let map = self.translate_expr(*map);
+                let key = self.translate_expr(*key);
+                let key = self.slot_value(entry_types(&ty).0, key);
+                let start = self.builder.ins().iconst(types::I64, span.start as i64);
+                let end = self.builder.ins().iconst(types::I64, span.end as i64);
+                let address =
+                    self.call_runtime("illusio_map_get", &[map, key, start, end], Some(types::I64));
+                self.load_element(entry_types(&ty).1, address)
+            }
+            MapSet(map, key, value, ty) => {
+                let map = self.translate_expr(*map);
+                let key = self.translate_expr(*key);
+                let key = self.slot_value(entry_types(&ty).0, key);
+                let value = self.translate_expr(*value);
+                let address = self.call_runtime("illusio_map_set", &[map, key], Some(types::I64));
+                self.store_element(entry_types(&ty).1, value, address, 0);
+                self.null()
+            }
+            MapRemove(map, key, ty) => {
+                let map = self.translate_expr(*map);
+                let key = self.translate_expr(*key);
+                let key = self.slot_value(entry_types(&ty).0, key);
+                self.call_runtime("illusio_map_remove", &[map, key], None)
+            }
+            MapContains(map, key, ty) => {
+                let map = self.translate_expr(*map);
+                let key = self.translate_expr(*key);
+                let key = self.slot_value(entry_types(&ty).0, key);
+                let contains =
+                    self.call_runtime("illusio_map_contains", &[map, key], Some(types::I64));
+                self.builder.ins().icmp_imm(IntCC::NotEqual, contains, 0)
+            }
+            MapKey(map, index, ty, span) => {
+                let map = self.translate_expr(*map);
+                let index = self.translate_expr(*index);
+                let address = self.element_address(map, index, Some(8), span);
+                self.load_element(entry_types(&ty).0, address)
+            }
+            MapValue(map, index, ty, span) => {
+                let map = self.translate_expr(*map);
+                let index = self.translate_expr(*index);
+                let address = self.element_address(map, index, Some(16), span);
+                self.load_element(entry_types(&ty).1, address)
+            }
+            Len(array) => {
+                let array = self.translate_expr(*array);
+                self.builder
+                    .ins()
+                    .load(types::I64, MemFlags::trusted(), array, 0)
+            }
             PrintStr(arg) => {
-                let args = &[self.translate_expr(*arg)];
-                let mut sig = self.module.make_signature();
-                sig.params.push(AbiParam::new(types::I64));
-                sig.returns.push(AbiParam::new(types::I64));
-                let callee = self
-                    .module
-                    .declare_function("puts", Linkage::Import, &sig)
-                    .unwrap();
-                let local_callee = self.module.declare_func_in_func(callee, self.builder.func);
-                let call = self.builder.ins().call(local_callee, args);
-                self.builder.inst_results(call)[0]
+                let arg = self.translate_expr(*arg);
+                self.call_runtime("illusio_print_str", &[arg], None)
             }
             PrintInt(arg) => {
-                let args = &[
-                    self.translate_expr(Expr::Value(
-                        IrValue::Literal(Literal::Str("%d\n".to_owned())),
-                        IrType::Str,
-                    )),
-                    self.translate_expr(*arg),
-                ];
-                let mut sig = self.module.make_signature();
-                sig.params.push(AbiParam::new(types::I64));
-                sig.params.push(AbiParam::new(types::I64));
-                sig.returns.push(AbiParam::new(types::I64));
-                let callee = self
-                    .module
-                    .declare_function("printf", Linkage::Import, &sig)
-                    .unwrap();
-                let local_callee = self.module.declare_func_in_func(callee, self.builder.func);
-                let call = self.builder.ins().call(local_callee, args);
-                self.builder.inst_results(call)[0]
+                let arg = self.translate_expr(*arg);
+                self.call_runtime("illusio_print_int", &[arg], None)
             }
             PrintFloat(arg) => {
-                let args = &[
-                    self.translate_expr(Expr::Value(
-                        IrValue::Literal(Literal::Str("%f\n".to_owned())),
-                        IrType::Str,
-                    )),
-                    self.translate_expr(*arg),
-                ];
-                let mut sig = self.module.make_signature();
-                sig.params.push(AbiParam::new(types::I64));
-                sig.params.push(AbiParam::new(types::F64));
-                sig.returns.push(AbiParam::new(types::I64));
-                let callee = self
-                    .module
-                    .declare_function("printf", Linkage::Import, &sig)
-                    .unwrap();
-                let local_callee = self.module.declare_func_in_func(callee, self.builder.func);
-                let call = self.builder.ins().call(local_callee, args);
-                self.builder.inst_results(call)[0]
+                let arg = self.translate_expr(*arg);
+                self.call_runtime("illusio_print_float", &[arg], None)
+            }
+        }
+    }
+    /// Evaluates `right` only when `left` isn't enough to know the result:
+    /// when it is false for `&&`, and when it is true for `||`.
+    fn short_circuit(&mut self, left: Expr, right: Expr, or: bool) -> Value {
+        let left = self.translate_expr(left);
+        let right_block = self.builder.create_block();
+        let merge_block = self.builder.create_block();
+        self.builder.append_block_param(merge_block, types::B1);
+        if or {
+            self.builder.ins().brnz(left, merge_block, &[left]);
+        } else {
+            self.builder.ins().brz(left, merge_block, &[left]);
+        }
+        self.builder.ins().jump(right_block, &[]);
+
+        self.builder.switch_to_block(right_block);
+        self.builder.seal_block(right_block);
+        let right = self.translate_expr(right);
+        self.builder.ins().jump(merge_block, &[right]);
+
+        self.builder.switch_to_block(merge_block);
+        self.builder.seal_block(merge_block);
+        self.builder.block_params(merge_block)[0]
+    }
+    /// Divides two integers or takes the remainder of their division, reporting `span`
+    /// and stopping the program when dividing by zero or when the quotient
+    /// of `i64::MIN / -1` overflows.
+    fn int_div(&mut self, left: Value, right: Value, remainder: bool, span: Span) -> Value {
+        let start = self.builder.ins().iconst(types::I64, span.start as i64);
+        let end = self.builder.ins().iconst(types::I64, span.end as i64);
+        let zero_block = self.builder.create_block();
+        let nonzero_block = self.builder.create_block();
+        self.builder.ins().brz(right, zero_block, &[]);
+        self.builder.ins().jump(nonzero_block, &[]);
+
+        self.builder.switch_to_block(zero_block);
+        self.builder.seal_block(zero_block);
+        self.call_runtime("illusio_divide_by_zero", &[start, end], None);
+        self.builder.ins().trap(TrapCode::IntegerDivisionByZero);
+
+        self.builder.switch_to_block(nonzero_block);
+        self.builder.seal_block(nonzero_block);
+        if remainder {
+            // `i64::MIN % -1` is 0, but `srem` can overflow computing it like the quotient
+            let minus_one = self.builder.ins().icmp_imm(IntCC::Equal, right, -1);
+            let one = self.builder.ins().iconst(types::I64, 1);
+            let right = self.builder.ins().select(minus_one, one, right);
+            return self.builder.ins().srem(left, right);
+        }
+        let min = self.builder.ins().icmp_imm(IntCC::Equal, left, i64::MIN);
+        let minus_one = self.builder.ins().icmp_imm(IntCC::Equal, right, -1);
+        let overflow = self.builder.ins().band(min, minus_one);
+        let overflow_block = self.builder.create_block();
+        let ok_block = self.builder.create_block();
+        self.builder.ins().brnz(overflow, overflow_block, &[]);
+        self.builder.ins().jump(ok_block, &[]);
+
+        self.builder.switch_to_block(overflow_block);
+        self.builder.seal_block(overflow_block);
+        self.call_runtime("illusio_divide_overflow", &[start, end], None);
+        self.builder.ins().trap(TrapCode::IntegerOverflow);
+
+        self.builder.switch_to_block(ok_block);
+        self.builder.seal_block(ok_block);
+        self.builder.ins().sdiv(left, right)
+    }
+    /// Gives the address of element `index` of `array`,
+    /// reporting `span` and stopping the program when the index is out of bounds.
+    /// `elements` is the offset of the pointer to the elements,
+    /// when they aren't right after the length.
+    fn element_address(
+        &mut self,
+        array: Value,
+        index: Value,
+        elements: Option<i32>,
+        span: Span,
+    ) -> Value {
+        let len = self
+            .builder
+            .ins()
+            .load(types::I64, MemFlags::trusted(), array, 0);
+        // Negative indexes are huge when unsigned, so one comparison checks both ends
+        let out_of_bounds = self
+            .builder
+            .ins()
+            .icmp(IntCC::UnsignedGreaterThanOrEqual, index, len);
+        let error_block = self.builder.create_block();
+        let ok_block = self.builder.create_block();
+        self.builder.ins().brnz(out_of_bounds, error_block, &[]);
+        self.builder.ins().jump(ok_block, &[]);
+
+        self.builder.switch_to_block(error_block);
+        self.builder.seal_block(error_block);
+        let start = self.builder.ins().iconst(types::I64, span.start as i64);
+        let end = self.builder.ins().iconst(types::I64, span.end as i64);
+        self.call_runtime("illusio_out_of_bounds", &[index, len, start, end], None);
+        self.builder.ins().trap(TrapCode::HeapOutOfBounds);
+
+        self.builder.switch_to_block(ok_block);
+        self.builder.seal_block(ok_block);
+        let offset = self.builder.ins().imul_imm(index, 8);
+        let elements = match elements {
+            Some(offset) => self
+                .builder
+                .ins()
+                .load(types::I64, MemFlags::trusted(), array, offset),
+            // Skipping the length
+            None => self.builder.ins().iadd_imm(array, 8),
+        };
+        self.builder.ins().iadd(elements, offset)
+    }
+    /// Calls the function `name`, giving the values it returns
+    fn call(&mut self, name: &str, args: Vec<Expr>) -> Vec<Value> {
+        let args: Vec<Value> = args
+            .into_iter()
+            .map(|arg| self.translate_expr(arg))
+            .collect();
+        let id = match self.module.get_name(name) {
+            Some(FuncOrDataId::Func(id)) => id,
+            _ => bug(format!("Function `{}` was never declared", name)),
+        };
+        let local_callee = self.module.declare_func_in_func(id, self.builder.func);
+        let call = self.builder.ins().call(local_callee, &args);
+        self.builder.inst_results(call).to_vec()
+    }
+    /// Calls the closure `closure` of type `ty`, giving the values it returns
+    fn call_indirect(&mut self, closure: Expr, args: Vec<Expr>, ty: &IrType) -> Vec<Value> {
+        let (params, return_type) = match ty {
+            IrType::Function(params, return_type) => (params, return_type.as_deref().cloned()),
+            _ => unreachable!(),
+        };
+        let closure = self.translate_expr(closure);
+        let mut values = vec![closure];
+        values.extend(args.into_iter().map(|arg| self.translate_expr(arg)));
+        let code = self
+            .builder
+            .ins()
+            .load(types::I64, MemFlags::trusted(), closure, 0);
+        let sig = signature(self.module, true, params, &return_type);
+        let sig = self.builder.import_signature(sig);
+        let call = self.builder.ins().call_indirect(sig, code, &values);
+        self.builder.inst_results(call).to_vec()
+    }
+    /// Calls the C function `name`, giving the values it returns and how its result is returned.
+    /// When it returns into memory, the address of the memory is given instead.
+    fn call_extern(
+        &mut self,
+        name: &str,
+        args: Vec<(Expr, IrType)>,
+    ) -> (Vec<Value>, Option<Passing>) {
+        let function = &self.externs[name];
+        let (_, passing, returned) =
+            ffi::signature(self.module, &function.params, &function.return_type);
+        let mut values = Vec::new();
+        if let Some(Passing::Memory(size)) = returned {
+            let size = self.builder.ins().iconst(types::I64, size as i64);
+            values.push(self.call_runtime("illusio_alloc", &[size], Some(types::I64)));
+        }
+        for ((arg, ty), passing) in args.into_iter().zip(passing) {
+            match passing {
+                Passing::Value => {
+                    let value = self.translate_expr(arg);
+                    values.push(self.c_value(&ty, value));
+                }
+                Passing::Fields => values.extend(self.tuple_values(arg, &ty)),
+                // Cranelift copies the tuple from its address
+                Passing::Memory(_) => values.push(self.translate_expr(arg)),
+            }
+        }
+        let id = match self.module.get_name(name) {
+            Some(FuncOrDataId::Func(id)) => id,
+            _ => bug(format!("C function `{}` was never declared", name)),
+        };
+        let callee = self.module.declare_func_in_func(id, self.builder.func);
+        let call = self.builder.ins().call(callee, &values);
+        let results = match returned {
+            Some(Passing::Memory(_)) => vec![values[0]],
+            _ => self.builder.inst_results(call).to_vec(),
+        };
+        (results, returned)
+    }
+    /// Calls the variadic C function `name` through the runtime, which uses the C variadic
+    /// convention. Every argument is stored in a stack slot with its kind.
+    fn call_variadic(
+        &mut self,
+        name: &str,
+        args: Vec<(Expr, IrType)>,
+        return_type: Option<IrType>,
+    ) -> Value {
+        let count = args.len();
+        let fixed = self.externs[name].params.len();
+        let size = 8 * count.max(1) as u32;
+        let values = self
+            .builder
+            .create_sized_stack_slot(StackSlotData::new(StackSlotKind::ExplicitSlot, size));
+        let kinds = self
+            .builder
+            .create_sized_stack_slot(StackSlotData::new(StackSlotKind::ExplicitSlot, size));
+        for (i, (arg, ty)) in args.into_iter().enumerate() {
+            let value = self.translate_expr(arg);
+            let value = match ty {
+                IrType::Str => self.c_value(&ty, value),
+                _ => self.slot_value(&ty, value),
+            };
+            self.builder.ins().stack_store(value, values, 8 * i as i32);
+            let kind = self
+                .builder
+                .ins()
+                .iconst(types::I64, Kind::of(Some(&ty)) as i64);
+            self.builder.ins().stack_store(kind, kinds, 8 * i as i32);
+        }
+        let id = match self.module.get_name(name) {
+            Some(FuncOrDataId::Func(id)) => id,
+            _ => bug(format!("C function `{}` was never declared", name)),
+        };
+        let function = self.module.declare_func_in_func(id, self.builder.func);
+        let code = self.builder.ins().func_addr(types::I64, function);
+        let values = self.builder.ins().stack_addr(types::I64, values, 0);
+        let kinds = self.builder.ins().stack_addr(types::I64, kinds, 0);
+        let count = self.builder.ins().iconst(types::I64, count as i64);
+        let fixed = self.builder.ins().iconst(types::I64, fixed as i64);
+        let returns = self
+            .builder
+            .ins()
+            .iconst(types::I64, Kind::of(return_type.as_ref()) as i64);
+        let result = self.call_runtime(
+            "illusio_call_variadic",
+            &[code, values, kinds, count, fixed, returns],
+            Some(types::I64),
+        );
+        match return_type {
+            Some(IrType::Float) => self.builder.ins().bitcast(types::F64, result),
+            Some(IrType::Bool) => self.builder.ins().icmp_imm(IntCC::NotEqual, result, 0),
+            Some(IrType::Str) => {
+                self.call_runtime("illusio_str_from_c", &[result], Some(types::I64))
+            }
+            Some(_) => result,
+            None => self.null(),
+        }
+    }
+    /// Calls the host function `name` through the runtime, with its arguments in a stack slot.
+    /// The span is reported when it panics.
+    fn call_host(&mut self, name: &str, args: Vec<(Expr, IrType)>, span: Span) -> Value {
+        let count = args.len();
+        let slot = self.builder.create_sized_stack_slot(StackSlotData::new(
+            StackSlotKind::ExplicitSlot,
+            8 * count.max(1) as u32,
+        ));
+        for (i, (arg, ty)) in args.into_iter().enumerate() {
+            let value = self.translate_expr(arg);
+            let value = self.slot_value(&ty, value);
+            self.builder.ins().stack_store(value, slot, 8 * i as i32);
+        }
+        let host = self
+            .builder
+            .ins()
+            .iconst(types::I64, self.hosts[name] as i64);
+        let args = self.builder.ins().stack_addr(types::I64, slot, 0);
+        let count = self.builder.ins().iconst(types::I64, count as i64);
+        let start = self.builder.ins().iconst(types::I64, span.start as i64);
+        let end = self.builder.ins().iconst(types::I64, span.end as i64);
+        self.call_runtime(
+            "illusio_call_host",
+            &[host, args, count, start, end],
+            Some(types::I64),
+        )
+    }
+    /// Converts a value to how C takes it, strings are given as their NUL terminated bytes
+    fn c_value(&mut self, ty: &IrType, value: Value) -> Value {
+        match ty {
+            IrType::Str => self
+                .builder
+                .ins()
+                .load(types::I64, MemFlags::trusted(), value, 8),
+            IrType::Bool => self.builder.ins().bint(types::I8, value),
+            _ => value,
+        }
+    }
+    /// Converts a value a C function returned
+    fn c_result(&mut self, ty: &IrType, value: Value) -> Value {
+        match ty {
+            IrType::Str => self.call_runtime("illusio_str_from_c", &[value], Some(types::I64)),
+            IrType::Bool => self.builder.ins().icmp_imm(IntCC::NotEqual, value, 0),
+            _ => value,
+        }
+    }
+    /// The value of a call from the values it returns
+    fn returned(&mut self, results: &[Value], return_type: Option<&IrType>) -> Value {
+        match return_type {
+            // The elements of a returned tuple are put back together
+            Some(ty @ IrType::Tuple(_)) => self.pack(results, ty),
+            Some(_) => results[0],
+            None => self.null(),
+        }
+    }
+    /// Gives the elements of the tuple `expr` of type `ty`. Tuple literals and calls
+    /// give their elements directly, other tuples are read from memory.
+    fn tuple_values(&mut self, expr: Expr, ty: &IrType) -> Vec<Value> {
+        match expr {
+            Expr::Tuple(elements, _) => elements
+                .into_iter()
+                .map(|element| self.translate_expr(element))
+                .collect(),
+            Expr::Call(name, args, Some(_)) => self.call(&name, args),
+            Expr::CallIndirect(closure, args, closure_ty) => {
+                self.call_indirect(*closure, args, &closure_ty)
+            }
+            expr => {
+                let tuple = self.translate_expr(expr);
+                field_types(ty)
+                    .iter()
+                    .enumerate()
+                    .map(|(i, field)| {
+                        let address = self.builder.ins().iadd_imm(tuple, 8 * i as i64);
+                        self.load_element(field, address)
+                    })
+                    .collect()
+            }
+        }
+    }
+    /// Stores the elements of a tuple of type `ty` on the heap
+    fn pack(&mut self, values: &[Value], ty: &IrType) -> Value {
+        let size = self
+            .builder
+            .ins()
+            .iconst(types::I64, 8 * values.len() as i64);
+        let tuple = self.call_runtime("illusio_alloc", &[size], Some(types::I64));
+        for (i, (value, field)) in values.iter().zip(field_types(ty)).enumerate() {
+            self.store_element(field, *value, tuple, 8 * i as i32);
+        }
+        tuple
+    }
+    fn load_element(&mut self, ty: &IrType, address: Value) -> Value {
+        match ty {
+            // Booleans are stored as integers
+            IrType::Bool => {
+                let int = self
+                    .builder
+                    .ins()
+                    .load(types::I64, MemFlags::trusted(), address, 0);
+                self.builder.ins().icmp_imm(IntCC::NotEqual, int, 0)
             }
+            _ => self
+                .builder
+                .ins()
+                .load(to_cranelift_ty(ty), MemFlags::trusted(), address, 0),
+        }
+    }
+    fn store_element(&mut self, ty: &IrType, value: Value, address: Value, offset: i32) {
+        let value = self.slot_value(ty, value);
+        self.builder
+            .ins()
+            .store(MemFlags::trusted(), value, address, offset);
+    }
+    /// Converts a value to how it is stored, booleans are stored as integers
+    fn slot_value(&mut self, ty: &IrType, value: Value) -> Value {
+        match ty {
+            IrType::Bool => self.builder.ins().bint(types::I64, value),
+            _ => value,
         }
     }
-    fn to_cranelift_ty(&self, ir_ty: &IrType) -> Type {
-        match ir_ty {
-            IrType::Int => types::I64,
-            IrType::Bool => types::B1,
-            IrType::Float => types::F64,
-            IrType::Str => types::I64,
+    /// Calls a function from the runtime, the parameter types are taken from `args`.
+    fn call_runtime(&mut self, name: &str, args: &[Value], returns: Option<Type>) -> Value {
+        let mut sig = self.module.make_signature();
+        for arg in args {
+            let ty = self.builder.func.dfg.value_type(*arg);
+            sig.params.push(AbiParam::new(ty));
+        }
+        if let Some(ty) = returns {
+            sig.returns.push(AbiParam::new(ty));
+        }
+        let callee = self
+            .module
+            .declare_function(name, Linkage::Import, &sig)
+            .unwrap();
+        let local_callee = self.module.declare_func_in_func(callee, self.builder.func);
+        let call = self.builder.ins().call(local_callee, args);
+        match returns {
+            Some(_) => self.builder.inst_results(call)[0],
+            None => self.null(),
+        }
+    }
+}
+/// The signature of a function, closures take themselves before their parameters
+fn signature(
+    module: &JITModule,
+    closure: bool,
+    params: &[IrType],
+    return_type: &Option<IrType>,
+) -> Signature {
+    let mut sig = module.make_signature();
+    if closure {
+        sig.params.push(AbiParam::new(types::I64));
+    }
+    for ty in params {
+        sig.params.push(AbiParam::new(to_cranelift_ty(ty)));
+    }
+    match return_type {
+        // Tuples are returned as multiple values, Cranelift puts the ones
+        // that don't fit in registers in a return area
+        Some(IrType::Tuple(fields)) => {
+            for field in fields {
+                sig.returns.push(AbiParam::new(to_cranelift_ty(field)));
+            }
         }
+        Some(ty) => sig.returns.push(AbiParam::new(to_cranelift_ty(ty))),
+        None => {}
+    }
+    sig
+}
+fn to_cranelift_ty(ir_ty: &IrType) -> Type {
+    match ir_ty {
+        IrType::Int => types::I64,
+        IrType::Bool => types::B1,
+        IrType::Float => types::F64,
+        IrType::Str
+        | IrType::Array(_)
+        | IrType::Vec(_)
+        | IrType::Map(..)
+        | IrType::Ptr(_)
+        | IrType::Optional(_)
+        | IrType::Result(..)
+        | IrType::Tuple(_)
+        | IrType::Function(..) => types::I64,
+    }
+}
+/// Whether values of a type are pointers that are never null,
+/// so an optional of them can use null for a missing value
+fn is_pointer(ty: &IrType) -> bool {
+    matches!(
+        ty,
+        IrType::Str
+            | IrType::Array(_)
+            | IrType::Vec(_)
+            | IrType::Map(..)
+            | IrType::Ptr(_)
+            | IrType::Tuple(_)
+            | IrType::Function(..)
+    )
+}
+/// The offset of the pointer to the elements of an array or a vector,
+/// `None` when the elements come right after the length
+fn elements_offset(ty: &IrType) -> Option<i32> {
+    match ty {
+        IrType::Vec(_) => Some(8),
+        _ => None,
+    }
+}
+/// The type of the value of a result, or of its error
+fn result_type(ty: &IrType, error: bool) -> &IrType {
+    match ty {
+        IrType::Result(_, error_ty) if error => error_ty,
+        IrType::Result(value, _) => value,
+        _ => unreachable!(),
+    }
+}
+/// The types of the elements of a tuple
+fn field_types(ty: &IrType) -> &[IrType] {
+    match ty {
+        IrType::Tuple(fields) => fields,
+        _ => unreachable!(),
+    }
+}
+/// The types of the keys and the values of a map
+fn entry_types(ty: &IrType) -> (&IrType, &IrType) {
+    match ty {
+        IrType::Map(key, value) => (key, value),
+        _ => unreachable!(),
+    }
+}
+/// The type of the elements of an array or a vector, or of the value of a pointer or an optional
+fn element_type(ty: &IrType) -> &IrType {
+    match ty {
+        IrType::Array(ty) | IrType::Vec(ty) | IrType::Ptr(ty) | IrType::Optional(ty) => ty,
+        _ => unreachable!(),
     }
 }
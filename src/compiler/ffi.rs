@@ -0,0 +1,190 @@
+//! Calling C functions declared with `extern fun`: finding them, and giving them
+//! values the way C expects. Tuples are C structs, passed by value with the rules of
+//! the System V ABI of x86-64.
+use cranelift::codegen::ir::ArgumentPurpose;
+use cranelift::prelude::*;
+use cranelift_jit::JITModule;
+use cranelift_module::Module;
+use libloading::Library;
+
+use super::ir::{Extern, IrType};
+
+/// C functions with their addresses
+pub type Symbols = Vec<(String, *const u8)>;
+/// Loads the shared libraries of `externs` and looks up every one of them,
+/// in its library or in the process. The libraries have to stay loaded while the code runs.
+pub fn resolve(externs: &[Extern]) -> Result<(Symbols, Vec<Library>), String> {
+    let mut libraries: Vec<(Option<&str>, Library)> = Vec::new();
+    let mut symbols = Vec::new();
+    for function in externs {
+        let library = function.library.as_deref();
+        let index = match libraries.iter().position(|(name, _)| *name == library) {
+            Some(index) => index,
+            None => {
+                libraries.push((library, load(library)?));
+                libraries.len() - 1
+            }
+        };
+        let symbol = unsafe {
+            libraries[index]
+                .1
+                .get::<*const u8>(function.name.as_bytes())
+        };
+        match symbol {
+            Ok(symbol) => symbols.push((function.name.clone(), *symbol)),
+            Err(_) => {
+                return Err(format!(
+                    "Cannot find C function `{}` in {}",
+                    function.name,
+                    library.map_or("the process".to_owned(), |name| format!("`{}`", name))
+                ))
+            }
+        }
+    }
+    let libraries = libraries.into_iter().map(|(_, library)| library).collect();
+    Ok((symbols, libraries))
+}
+/// Loads a shared library by its name, or the process itself
+fn load(library: Option<&str>) -> Result<Library, String> {
+    match library {
+        Some(name) => unsafe { Library::new(name) }
+            .map_err(|err| format!("Cannot load library `{}`: {}", name, err)),
+        #[cfg(unix)]
+        None => Ok(libloading::os::unix::Library::this().into()),
+        #[cfg(windows)]
+        None => libloading::os::windows::Library::this()
+            .map(Library::from)
+            .map_err(|err| err.to_string()),
+    }
+}
+/// How a value is given to a C function, or returned from it
+#[derive(Debug, Clone)]
+pub enum Passing {
+    /// As one value, strings are pointers to their bytes and booleans are bytes
+    Value,
+    /// A tuple given as its fields, in registers like separate values
+    Fields,
+    /// A tuple copied on the stack, or returned into memory given by the caller
+    Memory(u32),
+}
+/// The kind of the values given to variadic C functions and returned by them,
+/// the runtime calls those with libffi.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Int,
+    Float,
+    Bool,
+    Pointer,
+    None,
+}
+impl Kind {
+    pub fn of(ty: Option<&IrType>) -> Self {
+        match ty {
+            Some(IrType::Int) => Kind::Int,
+            Some(IrType::Float) => Kind::Float,
+            Some(IrType::Bool) => Kind::Bool,
+            Some(_) => Kind::Pointer,
+            None => Kind::None,
+        }
+    }
+    pub fn from_i64(kind: i64) -> Self {
+        [
+            Kind::Int,
+            Kind::Float,
+            Kind::Bool,
+            Kind::Pointer,
+            Kind::None,
+        ][kind as usize]
+    }
+}
+/// Registers for the arguments of a call that are still free
+struct Registers {
+    ints: usize,
+    floats: usize,
+}
+/// The signature of a C function, with how every parameter is given and how
+/// the returned value comes back
+pub fn signature(
+    module: &JITModule,
+    params: &[IrType],
+    return_type: &Option<IrType>,
+) -> (Signature, Vec<Passing>, Option<Passing>) {
+    let mut sig = module.make_signature();
+    let mut registers = Registers { ints: 6, floats: 8 };
+    let returned = return_type.as_ref().map(|ty| match ty {
+        IrType::Tuple(fields) if fields.len() <= 2 => {
+            sig.returns
+                .extend(field_types(fields).into_iter().map(AbiParam::new));
+            Passing::Fields
+        }
+        // The caller gives the memory for large tuples as a hidden first parameter
+        IrType::Tuple(fields) => {
+            sig.params
+                .push(AbiParam::special(types::I64, ArgumentPurpose::StructReturn));
+            registers.ints -= 1;
+            Passing::Memory(8 * fields.len() as u32)
+        }
+        ty => {
+            sig.returns.push(c_param(ty));
+            Passing::Value
+        }
+    });
+    let passing = params
+        .iter()
+        .map(|ty| match ty {
+            // Tuples up to 16 bytes go in registers, when there are enough left for all of it
+            IrType::Tuple(fields) if fields.len() <= 2 && registers.take(&field_types(fields)) => {
+                sig.params
+                    .extend(field_types(fields).into_iter().map(AbiParam::new));
+                Passing::Fields
+            }
+            IrType::Tuple(fields) => {
+                let size = 8 * fields.len() as u32;
+                sig.params.push(AbiParam::special(
+                    types::I64,
+                    ArgumentPurpose::StructArgument(size),
+                ));
+                Passing::Memory(size)
+            }
+            ty => {
+                let param = c_param(ty);
+                registers.take(&[param.value_type]);
+                sig.params.push(param);
+                Passing::Value
+            }
+        })
+        .collect();
+    (sig, passing, returned)
+}
+impl Registers {
+    /// Uses registers for values of `types`, when there are enough for all of them
+    fn take(&mut self, types: &[Type]) -> bool {
+        let floats = types.iter().filter(|ty| ty.is_float()).count();
+        let ints = types.len() - floats;
+        if ints > self.ints || floats > self.floats {
+            return false;
+        }
+        self.ints -= ints;
+        self.floats -= floats;
+        true
+    }
+}
+/// How C sees a value of type `ty`
+fn c_param(ty: &IrType) -> AbiParam {
+    match ty {
+        IrType::Float => AbiParam::new(types::F64),
+        IrType::Bool => AbiParam::new(types::I8).uext(),
+        _ => AbiParam::new(types::I64),
+    }
+}
+/// The types of the fields of a tuple, the type checker only lets ints, floats
+/// and pointers in tuples given to C
+fn field_types(fields: &[IrType]) -> Vec<Type> {
+    fields
+        .iter()
+        .map(|field| match field {
+            IrType::Float => types::F64,
+            _ => types::I64,
+        })
+        .collect()
+}
@@ -1,3 +1,6 @@
+use crate::error::bug;
+use crate::token::Span;
+
 #[derive(Debug, Clone)]
 
 pub enum BinOp {
@@ -11,15 +14,30 @@ pub enum BinOp {
     Lt, // <
     Ge, // >=
     Le, // <=
+    BitAnd, // &
+    BitOr, // |
+    BitXor, // ^
+    Shl, // <<
+    /// Arithmetic shift, which keeps the sign
+    Shr, // >>
+    /// Logical shift, which fills with zeros
+    UShr, // >>>
+}
+#[derive(Debug, Clone)]
+pub enum UnOp {
+    Neg, // -
+    Not, // !
+    BitNot, // ~
 }
 impl BinOp {
+    #[allow(dead_code)]
     pub fn inverse(self) -> Self {
         use BinOp::*;
         match self {
             Eq => Neq,
             Gt => Lt,
 
-            _ => panic!("Cant inverse")
+            _ => bug(format!("{:?} has no inverse", self))
         }
     }
 }
@@ -31,12 +49,85 @@ pub enum Expr {
     PrintStr(Box<Expr>),
     PrintInt(Box<Expr>),
     PrintFloat(Box<Expr>),
+    /// Call a function by its name, with its return type if it has one
+    Call(String, Vec<Expr>, Option<IrType>),
+    /// A new closure of the function with the given name, with the values
+    /// of the variables it captures and their types
+    Closure(String, Vec<(Expr, IrType)>),
+    /// Calls a closure with the arguments, with the type of the closure
+    CallIndirect(Box<Expr>, Vec<Expr>, IrType),
+    /// Calls a C function by its name, with the types of the arguments and its return type
+    CallExtern(String, Vec<(Expr, IrType)>, Option<IrType>),
+    /// Calls a function of the program embedding Illusio by its name, with the types of
+    /// the arguments and its return type. The span is reported when it panics.
+    CallHost(String, Vec<(Expr, IrType)>, Option<IrType>, Span),
+    /// A new array with the type of its elements
+    Array(Vec<Expr>, IrType),
+    /// A new vector with the type of its elements
+    Vector(Vec<Expr>, IrType),
+    /// An element of an array or a vector, with the type of the indexed value.
+    /// The span is reported when the index is out of bounds.
+    Index(Box<Expr>, Box<Expr>, IrType, Span),
+    /// The quotient of two integers, or their remainder when the flag is set.
+    /// The span is reported when dividing by zero or when the quotient overflows.
+    IntDiv(Box<Expr>, Box<Expr>, bool, Span),
+    /// Whether both booleans are true, the second one is only evaluated when the first one is
+    And(Box<Expr>, Box<Expr>),
+    /// Whether either boolean is true, the second one is only evaluated when the first one isn't
+    Or(Box<Expr>, Box<Expr>),
+    /// The number of elements of an array or a vector
+    Len(Box<Expr>),
+    /// A new tuple, with the type of the tuple
+    Tuple(Vec<Expr>, IrType),
+    /// An element of a tuple, with the type of the tuple
+    TupleField(Box<Expr>, usize, IrType),
+    /// The address of a variable kept in a stack slot
+    AddressOf(String),
+    /// Reads the value a pointer points to, with the type of the pointer
+    Deref(Box<Expr>, IrType),
+    /// An optional with a value, with the type of the optional
+    Wrap(Box<Expr>, IrType),
+    /// An optional without a value
+    Null,
+    /// The value of an optional that has one, with the type of the optional
+    Unwrap(Box<Expr>, IrType),
+    /// A new result with a value, or with an error when the flag is set,
+    /// with the type of the result
+    MakeResult(Box<Expr>, bool, IrType),
+    /// Whether a result has an error
+    IsErr(Box<Expr>),
+    /// The value of a result, or its error when the flag is set, with the type of the result
+    ResultValue(Box<Expr>, bool, IrType),
+    /// The value of a result, a result with an error is returned from the function instead
+    Try(Box<Expr>, IrType),
+    /// Adds an element with the given type at the end of a vector
+    Push(Box<Expr>, Box<Expr>, IrType),
+    /// Removes the last element of a vector, the span is reported when it is empty
+    Pop(Box<Expr>, IrType, Span),
+    /// The bytes of a string between two optional indexes, the span is reported when
+    /// they are out of bounds
+    Slice(Box<Expr>, Option<Box<Expr>>, Option<Box<Expr>>, Span),
+    /// Converts a value from a type to another, the span is reported when it fails
+    Convert(Box<Expr>, IrType, IrType, Span),
+    /// A new map with its entries, and the type of the map
+    Map(Vec<(Expr, Expr)>, IrType),
+    /// The value of a key in a map, the span is reported when the key is missing
+    MapGet(Box<Expr>, Box<Expr>, IrType, Span),
+    /// Sets the value of a key in a map
+    MapSet(Box<Expr>, Box<Expr>, Box<Expr>, IrType),
+    MapRemove(Box<Expr>, Box<Expr>, IrType),
+    MapContains(Box<Expr>, Box<Expr>, IrType),
+    /// The key of the entry at an index of a map, in the order the keys were added
+    MapKey(Box<Expr>, Box<Expr>, IrType, Span),
+    /// The value of the entry at an index of a map, in the order the keys were added
+    MapValue(Box<Expr>, Box<Expr>, IrType, Span),
 }
 #[derive(Debug, Clone)]
 
 pub enum IrValue {
     Literal(Literal),
     Binary(Box<Expr>, BinOp, Box<Expr>),
+    Unary(UnOp, Box<Expr>),
 }
 #[derive(Debug, Clone)]
 
@@ -51,18 +142,74 @@ pub enum Literal {
 pub enum IrType {
     Int,
     Float,
+    /// A pointer to the length of the string, followed by a pointer to its bytes
     Str,
     Bool,
+    /// A pointer to the length of the array, followed by its elements
+    Array(Box<IrType>),
+    /// A pointer to the length of the vector, followed by a pointer to its elements
+    Vec(Box<IrType>),
+    /// A pointer to the length of the map, followed by pointers to its keys and its values
+    Map(Box<IrType>, Box<IrType>),
+    /// A pointer to a value of the inner type
+    Ptr(Box<IrType>),
+    /// A pointer to its elements. Functions return the elements themselves when they can.
+    Tuple(Vec<IrType>),
+    /// A pointer to the code of the function, followed by the variables it captures.
+    /// The function takes that pointer as its first parameter.
+    Function(Vec<IrType>, Option<Box<IrType>>),
+    /// Null when the value is missing. Types that are pointers are used as is,
+    /// others are a pointer to their value.
+    Optional(Box<IrType>),
+    /// A pointer to a flag that is set for errors, followed by the value or the error
+    Result(Box<IrType>, Box<IrType>),
 }
 #[derive(Debug, Clone)]
+#[allow(clippy::enum_variant_names)]
 pub enum Stmt {
     ExprStmt(Expr),
     SetVar(String, Expr),
     Var(String, Expr, IrType),
+    /// A variable kept in a stack slot instead of a register, so it can have its address taken
+    StackVar(String, Expr, IrType),
+    /// Declares a variable for every element of a tuple, with the type of the tuple
+    Destructure(Vec<String>, Expr, IrType),
+    /// Writes to the value a pointer points to, with the type of the pointer
+    SetDeref(Expr, Expr, IrType),
+    /// Sets an element of an array or a vector, like `Index` but with the value to store
+    SetIndex(Expr, Expr, Expr, IrType, Span),
+    While(Expr, Vec<Stmt>),
+    Block(Vec<Stmt>),
+    /// Statements whose variables are only visible inside of them, like a `do ... end` block
+    Scope(Vec<Stmt>),
     If(Expr, Vec<Stmt>, Vec<Stmt>),
+    Return(Option<Expr>),
+}
+/// A C function declared with `extern fun`
+#[derive(Debug, Clone)]
+pub struct Extern {
+    pub name: String,
+    /// The shared library it is in, `None` when it is looked up in the process
+    pub library: Option<String>,
+    pub params: Vec<IrType>,
+    pub return_type: Option<IrType>,
+    /// Whether it takes more arguments after its parameters, like `printf`
+    pub variadic: bool,
+}
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub name: String,
+    pub params: Vec<(String, IrType)>,
+    pub return_type: Option<IrType>,
+    /// For closures, the variables they capture. They are read from the closure,
+    /// which is passed before the parameters.
+    pub captures: Option<Vec<(String, IrType)>>,
+    pub body: Vec<Stmt>,
+    /// Where the function is in the source, a bug in compiling it is reported there
+    pub span: Span,
 }
 impl Expr {
     pub fn boxed(self) -> Box<Self> {
-        return Box::new(self)
+        Box::new(self)
     }
 }
\ No newline at end of file
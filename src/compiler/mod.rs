@@ -1,8 +1,13 @@
 pub mod builder;
+#[allow(clippy::module_inception)]
 pub mod compiler;
+pub mod ffi;
 pub mod ir;
+pub mod runtime;
+pub mod unwind;
+#[allow(unused_imports)]
 pub mod prelude {
-    use super::builder::*;
-    use super::compiler::*;
-    use super::ir::*;
+    pub use super::builder::*;
+    pub use super::compiler::*;
+    pub use super::ir::*;
 }
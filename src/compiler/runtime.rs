@@ -0,0 +1,480 @@
+//! Functions the compiled code calls into, they are registered into the JIT by name.
+use std::alloc::{alloc_zeroed, dealloc, handle_alloc_error, Layout};
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::ffi::{c_void, CStr};
+use std::io::Write;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr::addr_of_mut;
+
+use libffi::low::{ffi_abi_FFI_DEFAULT_ABI, ffi_cif, ffi_type, prep_cif_var, types};
+
+use super::ffi::Kind;
+use crate::error::panic_message;
+use crate::token::Span;
+
+/// Every runtime function with the name compiled code uses for it
+pub fn symbols() -> Vec<(&'static str, *const u8)> {
+    vec![
+        ("illusio_print_str", print_str as *const u8),
+        ("illusio_print_int", print_int as *const u8),
+        ("illusio_print_float", print_float as *const u8),
+        ("illusio_alloc", alloc as *const u8),
+        ("illusio_out_of_bounds", out_of_bounds as *const u8),
+        ("illusio_divide_by_zero", divide_by_zero as *const u8),
+        ("illusio_divide_overflow", divide_overflow as *const u8),
+        ("illusio_vec_new", vec_new as *const u8),
+        ("illusio_vec_push", vec_push as *const u8),
+        ("illusio_vec_pop", vec_pop as *const u8),
+        ("illusio_map_new", map_new as *const u8),
+        ("illusio_map_set", map_set as *const u8),
+        ("illusio_map_get", map_get as *const u8),
+        ("illusio_map_remove", map_remove as *const u8),
+        ("illusio_map_contains", map_contains as *const u8),
+        ("illusio_str_concat", str_concat as *const u8),
+        ("illusio_str_cmp", str_cmp as *const u8),
+        ("illusio_str_slice", str_slice as *const u8),
+        ("illusio_str_to_int", str_to_int as *const u8),
+        ("illusio_str_to_float", str_to_float as *const u8),
+        ("illusio_int_to_str", int_to_str as *const u8),
+        ("illusio_float_to_str", float_to_str as *const u8),
+        ("illusio_bool_to_str", bool_to_str as *const u8),
+        ("illusio_str_from_c", str_from_c as *const u8),
+        ("illusio_call_variadic", call_variadic as *const u8),
+        ("illusio_call_host", call_host as *const u8),
+    ]
+}
+/// Memory the runtime gave to a running program
+enum Allocation {
+    Str(*mut Str),
+    Bytes(*mut [u8]),
+    Vector(*mut Vector),
+    Map(*mut Map),
+    Block(*mut u8, Layout),
+}
+impl Drop for Allocation {
+    fn drop(&mut self) {
+        unsafe {
+            match *self {
+                Allocation::Str(s) => drop(Box::from_raw(s)),
+                Allocation::Bytes(bytes) => drop(Box::from_raw(bytes)),
+                Allocation::Vector(vector) => drop(Box::from_raw(vector)),
+                Allocation::Map(map) => drop(Box::from_raw(map)),
+                Allocation::Block(block, layout) => dealloc(block, layout),
+            }
+        }
+    }
+}
+/// Everything the runtime allocated for a program, which is freed when the arena is dropped.
+/// Compiled code never frees values itself, since they can be shared freely.
+#[derive(Default)]
+pub struct Arena {
+    allocations: Vec<Allocation>,
+}
+impl Arena {
+    /// Takes the allocations of `other`, to be freed with this arena
+    pub fn merge(&mut self, mut other: Arena) {
+        self.allocations.append(&mut other.allocations);
+    }
+}
+thread_local! {
+    /// Where allocations go, the arena of the program running on this thread.
+    /// Values made outside of a program live as long as the thread.
+    static ARENA: RefCell<Arena> = RefCell::new(Arena::default());
+}
+/// Makes allocations go to `arena`, giving the arena they went to until now
+pub fn swap_arena(arena: Arena) -> Arena {
+    ARENA.with(|current| std::mem::replace(&mut *current.borrow_mut(), arena))
+}
+fn track(allocation: Allocation) {
+    ARENA.with(|current| current.borrow_mut().allocations.push(allocation));
+}
+/// A string, compiled code passes around pointers to it and reads `len` directly.
+/// The bytes are followed by a NUL so they can be given to C, and are never changed,
+/// so slices share the bytes of the string they come from.
+#[repr(C)]
+struct Str {
+    len: i64,
+    ptr: *const u8,
+}
+impl Str {
+    fn bytes(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len as usize) }
+    }
+}
+fn new_str(bytes: &[u8]) -> *mut Str {
+    let mut owned = bytes.to_vec();
+    owned.push(0);
+    let owned = Box::into_raw(owned.into_boxed_slice());
+    track(Allocation::Bytes(owned));
+    let s = Box::into_raw(Box::new(Str {
+        len: bytes.len() as i64,
+        ptr: owned.cast(),
+    }));
+    track(Allocation::Str(s));
+    s
+}
+extern "C" fn print_str(s: *const Str) {
+    let s = unsafe { &*s };
+    let mut stdout = std::io::stdout().lock();
+    stdout.write_all(s.bytes()).unwrap();
+    stdout.write_all(b"\n").unwrap();
+}
+extern "C" fn print_int(i: i64) {
+    println!("{}", i);
+}
+extern "C" fn print_float(f: f64) {
+    println!("{:.6}", f);
+}
+/// A growable vector, compiled code reads `len` and `elements` directly.
+/// Every element takes 8 bytes.
+#[repr(C)]
+struct Vector {
+    len: i64,
+    elements: *mut u64,
+    items: Vec<u64>,
+}
+extern "C" fn vec_new() -> *mut Vector {
+    let mut items = Vec::new();
+    let vector = Box::into_raw(Box::new(Vector {
+        len: 0,
+        elements: items.as_mut_ptr(),
+        items,
+    }));
+    track(Allocation::Vector(vector));
+    vector
+}
+/// Adds an element at the end of the vector, and gives its address for the value to be stored
+extern "C" fn vec_push(vector: *mut Vector) -> *mut u64 {
+    let vector = unsafe { &mut *vector };
+    vector.items.push(0);
+    vector.len += 1;
+    vector.elements = vector.items.as_mut_ptr();
+    vector.items.last_mut().unwrap()
+}
+/// Removes the last element of the vector, and gives its address for the value to be loaded.
+/// The address stays valid since popping never shrinks the vector's memory.
+extern "C-unwind" fn vec_pop(vector: *mut Vector, start: i64, end: i64) -> *mut u64 {
+    let vector = unsafe { &mut *vector };
+    if vector.items.is_empty() {
+        report(
+            "Cannot pop from an empty vector".to_owned(),
+            Span::from(start as usize..end as usize),
+        );
+    }
+    vector.items.pop();
+    vector.len -= 1;
+    unsafe { vector.elements.add(vector.items.len()) }
+}
+/// A hash map, compiled code reads `len`, `keys` and `values` directly to loop over it.
+/// Keys and values take 8 bytes, strings are hashed by their content.
+#[repr(C)]
+struct Map {
+    len: i64,
+    keys: *mut u64,
+    values: *mut u64,
+    string_keys: bool,
+    key_list: Vec<u64>,
+    value_list: Vec<u64>,
+    /// The index of every key in the lists
+    indexes: HashMap<Key, usize>,
+}
+#[derive(PartialEq, Eq, Hash)]
+enum Key {
+    Int(u64),
+    Str(Vec<u8>),
+}
+impl Map {
+    fn key(&self, key: u64) -> Key {
+        if self.string_keys {
+            let s = unsafe { &*(key as *const Str) };
+            Key::Str(s.bytes().to_vec())
+        } else {
+            Key::Int(key)
+        }
+    }
+    /// Updates the fields read by compiled code after the lists changed
+    fn update(&mut self) {
+        self.len = self.key_list.len() as i64;
+        self.keys = self.key_list.as_mut_ptr();
+        self.values = self.value_list.as_mut_ptr();
+    }
+}
+extern "C" fn map_new(string_keys: i64) -> *mut Map {
+    let mut map = Map {
+        len: 0,
+        keys: std::ptr::null_mut(),
+        values: std::ptr::null_mut(),
+        string_keys: string_keys != 0,
+        key_list: Vec::new(),
+        value_list: Vec::new(),
+        indexes: HashMap::new(),
+    };
+    map.update();
+    let map = Box::into_raw(Box::new(map));
+    track(Allocation::Map(map));
+    map
+}
+/// Gives the address of the value of `key` for the value to be stored, adding the key if it's missing
+extern "C" fn map_set(map: *mut Map, key: u64) -> *mut u64 {
+    let map = unsafe { &mut *map };
+    let index = match map.indexes.get(&map.key(key)) {
+        Some(index) => *index,
+        None => {
+            map.indexes.insert(map.key(key), map.key_list.len());
+            map.key_list.push(key);
+            map.value_list.push(0);
+            map.update();
+            map.key_list.len() - 1
+        }
+    };
+    &mut map.value_list[index]
+}
+/// Gives the address of the value of `key` for the value to be loaded
+extern "C-unwind" fn map_get(map: *mut Map, key: u64, start: i64, end: i64) -> *mut u64 {
+    let map = unsafe { &mut *map };
+    match map.indexes.get(&map.key(key)) {
+        Some(index) => &mut map.value_list[*index],
+        None => report(
+            "Key not found in map".to_owned(),
+            Span::from(start as usize..end as usize),
+        ),
+    }
+}
+extern "C" fn map_remove(map: *mut Map, key: u64) {
+    let map = unsafe { &mut *map };
+    if let Some(index) = map.indexes.remove(&map.key(key)) {
+        // The last entry takes the place of the removed one
+        map.key_list.swap_remove(index);
+        map.value_list.swap_remove(index);
+        if let Some(moved) = map.key_list.get(index) {
+            map.indexes.insert(map.key(*moved), index);
+        }
+        map.update();
+    }
+}
+extern "C" fn map_contains(map: *mut Map, key: u64) -> i64 {
+    let map = unsafe { &*map };
+    map.indexes.contains_key(&map.key(key)) as i64
+}
+extern "C" fn str_concat(a: *const Str, b: *const Str) -> *mut Str {
+    let (a, b) = unsafe { (&*a, &*b) };
+    new_str(&[a.bytes(), b.bytes()].concat())
+}
+/// Compares the contents of two strings, giving -1, 0 or 1
+extern "C" fn str_cmp(a: *const Str, b: *const Str) -> i64 {
+    let (a, b) = unsafe { (&*a, &*b) };
+    match a.bytes().cmp(b.bytes()) {
+        Ordering::Less => -1,
+        Ordering::Equal => 0,
+        Ordering::Greater => 1,
+    }
+}
+/// The bytes of `s` from `start` to `end`, which have to be on character boundaries
+extern "C-unwind" fn str_slice(
+    s: *const Str,
+    start: i64,
+    end: i64,
+    span_start: i64,
+    span_end: i64,
+) -> *mut Str {
+    let s = unsafe { &*s };
+    let span = Span::from(span_start as usize..span_end as usize);
+    if start < 0 || end < start || end > s.len {
+        report(
+            format!(
+                "Slice out of bounds: the length is {} but the slice is {}..{}",
+                s.len, start, end
+            ),
+            span,
+        );
+    }
+    let text = String::from_utf8_lossy(s.bytes());
+    if !text.is_char_boundary(start as usize) || !text.is_char_boundary(end as usize) {
+        report(
+            format!("Slice {}..{} is not on character boundaries", start, end),
+            span,
+        );
+    }
+    let slice = Box::into_raw(Box::new(Str {
+        len: end - start,
+        ptr: unsafe { s.ptr.add(start as usize) },
+    }));
+    track(Allocation::Str(slice));
+    slice
+}
+extern "C-unwind" fn str_to_int(s: *const Str, start: i64, end: i64) -> i64 {
+    let s = unsafe { &*s };
+    let text = String::from_utf8_lossy(s.bytes());
+    match text.trim().parse() {
+        Ok(i) => i,
+        Err(_) => report(
+            format!("Cannot convert \"{}\" to an int", text),
+            Span::from(start as usize..end as usize),
+        ),
+    }
+}
+extern "C-unwind" fn str_to_float(s: *const Str, start: i64, end: i64) -> f64 {
+    let s = unsafe { &*s };
+    let text = String::from_utf8_lossy(s.bytes());
+    match text.trim().parse() {
+        Ok(f) => f,
+        Err(_) => report(
+            format!("Cannot convert \"{}\" to a float", text),
+            Span::from(start as usize..end as usize),
+        ),
+    }
+}
+extern "C" fn int_to_str(i: i64) -> *mut Str {
+    new_str(i.to_string().as_bytes())
+}
+extern "C" fn float_to_str(f: f64) -> *mut Str {
+    new_str(f.to_string().as_bytes())
+}
+extern "C" fn bool_to_str(b: i64) -> *mut Str {
+    new_str(if b != 0 { b"true" } else { b"false" })
+}
+/// A string with the text a C function gave, `NULL` gives an empty string.
+/// Bytes that aren't UTF-8 are replaced with U+FFFD, so every string stays UTF-8.
+extern "C" fn str_from_c(s: *const u8) -> *mut Str {
+    if s.is_null() {
+        return new_str(b"");
+    }
+    let text = String::from_utf8_lossy(unsafe { CStr::from_ptr(s.cast()) }.to_bytes());
+    new_str(text.as_bytes())
+}
+/// Calls a variadic C function with `count` arguments, of which the first `fixed` are its parameters.
+/// Every argument takes 8 bytes in `args`, and has its kind in `kinds`. Compiled code can't
+/// follow the C variadic convention itself, so libffi does the call. The result is given as 8 bytes.
+unsafe extern "C" fn call_variadic(
+    function: *const u8,
+    args: *mut u64,
+    kinds: *const i64,
+    count: i64,
+    fixed: i64,
+    returns: i64,
+) -> u64 {
+    let count = count as usize;
+    let fixed = fixed as usize;
+    let c_type = |kind: i64, variadic: bool| -> *mut ffi_type {
+        match Kind::from_i64(kind) {
+            Kind::Int => addr_of_mut!(types::sint64),
+            Kind::Float => addr_of_mut!(types::double),
+            // Booleans given as variadic arguments are promoted to ints
+            Kind::Bool if variadic => addr_of_mut!(types::sint32),
+            Kind::Bool => addr_of_mut!(types::uint8),
+            Kind::Pointer => addr_of_mut!(types::pointer),
+            Kind::None => addr_of_mut!(types::void),
+        }
+    };
+    let kinds = std::slice::from_raw_parts(kinds, count);
+    let mut arg_types: Vec<*mut ffi_type> = kinds
+        .iter()
+        .enumerate()
+        .map(|(i, kind)| c_type(*kind, i >= fixed))
+        .collect();
+    let mut values: Vec<*mut c_void> = (0..count).map(|i| args.add(i).cast()).collect();
+    let mut cif = ffi_cif::default();
+    prep_cif_var(
+        &mut cif,
+        ffi_abi_FFI_DEFAULT_ABI,
+        fixed,
+        count,
+        c_type(returns, false),
+        arg_types.as_mut_ptr(),
+    )
+    .unwrap();
+    let mut result = 0u64;
+    libffi::raw::ffi_call(
+        &mut cif,
+        Some(std::mem::transmute::<*const u8, unsafe extern "C" fn()>(
+            function,
+        )),
+        addr_of_mut!(result).cast(),
+        values.as_mut_ptr(),
+    );
+    result
+}
+/// Calls a host function with its arguments as 8 bytes each, giving the returned value the same way
+pub type HostCall = Box<dyn Fn(&[u64]) -> u64>;
+/// A function of the program embedding Illusio, it takes and returns values as their 8 bytes
+pub struct Host {
+    pub name: String,
+    pub call: HostCall,
+}
+/// Calls the host function `host` with `count` arguments from `args`.
+/// A panic in it stops the program with an error at the call.
+extern "C-unwind" fn call_host(
+    host: *const Host,
+    args: *const u64,
+    count: i64,
+    start: i64,
+    end: i64,
+) -> u64 {
+    let host = unsafe { &*host };
+    let args = unsafe { std::slice::from_raw_parts(args, count as usize) };
+    match panic::catch_unwind(AssertUnwindSafe(|| (host.call)(args))) {
+        Ok(result) => result,
+        Err(payload) => report(
+            format!(
+                "Host function `{}` panicked: {}",
+                host.name,
+                panic_message(&*payload)
+            ),
+            Span::from(start as usize..end as usize),
+        ),
+    }
+}
+/// The text of a string given by compiled code. It lives as long as the arena of the
+/// program that made it, so `'a` can't be longer than that program.
+pub fn str_text<'a>(s: u64) -> &'a str {
+    let s = unsafe { &*(s as *const Str) };
+    // Literals are UTF-8, slices are on character boundaries and C strings are decoded
+    std::str::from_utf8(s.bytes()).expect("Strings are always UTF-8")
+}
+/// A string for compiled code with the text `text`
+pub fn str_from_text(text: &str) -> u64 {
+    new_str(text.as_bytes()) as u64
+}
+/// Allocates zeroed memory, freed with the arena
+extern "C" fn alloc(size: i64) -> *mut u8 {
+    let layout = Layout::from_size_align(size.max(1) as usize, 8).unwrap();
+    let block = unsafe { alloc_zeroed(layout) };
+    if block.is_null() {
+        handle_alloc_error(layout);
+    }
+    track(Allocation::Block(block, layout));
+    block
+}
+extern "C-unwind" fn out_of_bounds(index: i64, len: i64, start: i64, end: i64) {
+    report(
+        format!(
+            "Index out of bounds: the length is {} but the index is {}",
+            len, index
+        ),
+        Span::from(start as usize..end as usize),
+    );
+}
+extern "C-unwind" fn divide_by_zero(start: i64, end: i64) {
+    report(
+        "Attempt to divide by zero".to_string(),
+        Span::from(start as usize..end as usize),
+    );
+}
+extern "C-unwind" fn divide_overflow(start: i64, end: i64) {
+    report(
+        "Attempt to divide with overflow".to_string(),
+        Span::from(start as usize..end as usize),
+    );
+}
+/// An error that stops the program, compiled code unwinds to where the program was run from
+#[derive(Debug, Clone)]
+pub struct RuntimeError {
+    pub message: String,
+    pub span: Span,
+}
+/// Stops the program with an error at `span` of the running file
+fn report(message: String, span: Span) -> ! {
+    panic::resume_unwind(Box::new(RuntimeError { message, span }))
+}
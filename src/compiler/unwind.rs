@@ -0,0 +1,83 @@
+//! Registers how to unwind through compiled functions, so runtime errors can go back
+//! through them to where the program was run from.
+use cranelift::codegen::gimli::write::{Address, EhFrame, EndianVec, FrameTable};
+use cranelift::codegen::gimli::RunTimeEndian;
+use cranelift::codegen::isa::{unwind::UnwindInfo, TargetIsa};
+
+#[cfg(unix)]
+extern "C" {
+    fn __register_frame(entry: *const u8);
+    fn __deregister_frame(entry: *const u8);
+}
+
+/// The unwind information of compiled functions, it stays registered until it is dropped
+#[derive(Default)]
+pub struct Frames {
+    /// The `.eh_frame` section the registered entries are in
+    _eh_frame: Vec<u8>,
+    registered: Vec<*const u8>,
+}
+impl Frames {
+    /// Registers the unwind information of the functions compiled at the given addresses
+    pub fn register(isa: &dyn TargetIsa, functions: Vec<(*const u8, UnwindInfo)>) -> Self {
+        let mut table = FrameTable::default();
+        let cie = match isa.create_systemv_cie() {
+            Some(cie) => table.add_cie(cie),
+            None => return Self::default(),
+        };
+        for (address, info) in functions {
+            if let UnwindInfo::SystemV(info) = info {
+                table.add_fde(cie, info.to_fde(Address::Constant(address as u64)));
+            }
+        }
+        let mut eh_frame = EhFrame(EndianVec::new(RunTimeEndian::default()));
+        table.write_eh_frame(&mut eh_frame).unwrap();
+        let mut eh_frame = eh_frame.0.into_vec();
+        // The section ends with an empty entry
+        eh_frame.extend([0; 4]);
+        let registered = register(&eh_frame);
+        Self {
+            _eh_frame: eh_frame,
+            registered,
+        }
+    }
+}
+impl Drop for Frames {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        for entry in self.registered.iter().rev() {
+            unsafe { __deregister_frame(*entry) };
+        }
+    }
+}
+/// Registers the entries of `eh_frame`, giving what has to be deregistered
+#[cfg(unix)]
+fn register(eh_frame: &[u8]) -> Vec<*const u8> {
+    // libgcc takes the whole section
+    if cfg!(all(target_os = "linux", target_env = "gnu")) {
+        unsafe { __register_frame(eh_frame.as_ptr()) };
+        return vec![eh_frame.as_ptr()];
+    }
+    // Other unwinders take every entry after the CIE, one by one.
+    // Every entry starts with its length, which doesn't count itself.
+    let mut registered = Vec::new();
+    let mut offset = 0;
+    loop {
+        let len = u32::from_ne_bytes(eh_frame[offset..offset + 4].try_into().unwrap()) as usize;
+        if len == 0 {
+            break;
+        }
+        if offset != 0 {
+            let entry = eh_frame[offset..].as_ptr();
+            unsafe { __register_frame(entry) };
+            registered.push(entry);
+        }
+        offset += len + 4;
+    }
+    registered
+}
+/// Runtime errors can't unwind through compiled code here, they abort
+#[cfg(not(unix))]
+fn register(_: &[u8]) -> Vec<*const u8> {
+    Vec::new()
+}
@@ -0,0 +1,514 @@
+//! Embedding Illusio in a Rust program. Functions of the program can be registered
+//! on an [`Engine`] and called from Illusio, and code is compiled and run from a string.
+//!
+//! Every stage of compiling can also be used on its own: [`Engine::lex`], [`Engine::parse`],
+//! [`Engine::analyze`], [`Engine::check`], [`Engine::lower`], then [`Engine::emit`] for
+//! the Cranelift IR or [`Engine::jit`] for machine code. Each one gives what the next takes,
+//! or the errors that stop it.
+//!
+//! ```no_run
+//! use illusio_2::Engine;
+//!
+//! let mut engine = Engine::new();
+//! engine.register_fn("log", |s: String| println!("[script] {}", s));
+//! engine.register_fn("twice", |n: i64| n * 2);
+//! let n: i64 = engine.eval("log(\"hi\")\ntwice(21)").unwrap();
+//! assert_eq!(n, 42);
+//! ```
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::analyzer::Analyzer;
+use crate::ast::{Expr, Type};
+use crate::compiler::compiler::{JitError, Program};
+use crate::compiler::runtime::{self, Host, HostCall};
+use crate::error::{catch_bug, Error};
+use crate::escape::EscapeChecker;
+use crate::lexer::Lexer;
+use crate::lower::Lower;
+use crate::parser::Parser;
+use crate::token::{Span, Token, TokenKind};
+use crate::typechecker::TypeChecker;
+
+/// A Rust value that can be given to Illusio, as an argument or as what a host function returns
+pub trait IntoIllusio {
+    /// The Illusio type of the value
+    fn ty() -> Type;
+    /// The 8 bytes compiled code uses for the value
+    fn into_raw(self) -> u64;
+}
+/// A Rust value that can be taken from Illusio, as a parameter of a host function
+/// or as the value of a program. Values borrowed from the program live for `'a`.
+pub trait FromIllusio<'a> {
+    /// The Illusio type of the value
+    fn ty() -> Type;
+    /// The value from the 8 bytes compiled code uses for it
+    fn from_raw(raw: u64) -> Self;
+}
+impl IntoIllusio for i64 {
+    fn ty() -> Type {
+        Type::Int
+    }
+    fn into_raw(self) -> u64 {
+        self as u64
+    }
+}
+impl FromIllusio<'_> for i64 {
+    fn ty() -> Type {
+        Type::Int
+    }
+    fn from_raw(raw: u64) -> Self {
+        raw as i64
+    }
+}
+impl IntoIllusio for f64 {
+    fn ty() -> Type {
+        Type::Float
+    }
+    fn into_raw(self) -> u64 {
+        self.to_bits()
+    }
+}
+impl FromIllusio<'_> for f64 {
+    fn ty() -> Type {
+        Type::Float
+    }
+    fn from_raw(raw: u64) -> Self {
+        f64::from_bits(raw)
+    }
+}
+impl IntoIllusio for bool {
+    fn ty() -> Type {
+        Type::Bool
+    }
+    fn into_raw(self) -> u64 {
+        self as u64
+    }
+}
+impl FromIllusio<'_> for bool {
+    fn ty() -> Type {
+        Type::Bool
+    }
+    fn from_raw(raw: u64) -> Self {
+        raw != 0
+    }
+}
+impl IntoIllusio for String {
+    fn ty() -> Type {
+        Type::String
+    }
+    fn into_raw(self) -> u64 {
+        runtime::str_from_text(&self)
+    }
+}
+impl FromIllusio<'_> for String {
+    fn ty() -> Type {
+        Type::String
+    }
+    fn from_raw(raw: u64) -> Self {
+        runtime::str_text(raw).to_owned()
+    }
+}
+impl IntoIllusio for &str {
+    fn ty() -> Type {
+        Type::String
+    }
+    fn into_raw(self) -> u64 {
+        runtime::str_from_text(self)
+    }
+}
+/// Strings of Illusio are freed with the program, so they can be borrowed while it exists
+impl<'a> FromIllusio<'a> for &'a str {
+    fn ty() -> Type {
+        Type::String
+    }
+    fn from_raw(raw: u64) -> Self {
+        runtime::str_text(raw)
+    }
+}
+/// Host functions that return nothing
+impl IntoIllusio for () {
+    fn ty() -> Type {
+        Type::None
+    }
+    fn into_raw(self) -> u64 {
+        0
+    }
+}
+/// A Rust function or closure that can be called from Illusio,
+/// `Args` are the types of its parameters
+pub trait HostFunction<Args> {
+    fn params() -> Vec<Type>;
+    fn return_type() -> Type;
+    /// A function taking the arguments and returning the value as their 8 bytes
+    fn into_call(self) -> HostCall;
+}
+macro_rules! host_function {
+    ($($arg:ident),*) => {
+        impl<F, R, $($arg,)*> HostFunction<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> R + 'static,
+            R: IntoIllusio,
+            // Host functions outlive the programs calling them, so arguments can't be borrowed
+            $($arg: for<'a> FromIllusio<'a>,)*
+        {
+            fn params() -> Vec<Type> {
+                vec![$($arg::ty()),*]
+            }
+            fn return_type() -> Type {
+                R::ty()
+            }
+            #[allow(non_snake_case, unused_variables, unused_mut)]
+            fn into_call(self) -> HostCall {
+                Box::new(move |args| {
+                    let mut args = args.iter();
+                    $(let $arg = $arg::from_raw(*args.next().unwrap());)*
+                    self($($arg),*).into_raw()
+                })
+            }
+        }
+    };
+}
+host_function!();
+host_function!(A);
+host_function!(A, B);
+host_function!(A, B, C);
+host_function!(A, B, C, D);
+host_function!(A, B, C, D, E);
+host_function!(A, B, C, D, E, G);
+
+/// A host function with its signature
+struct Registered {
+    params: Vec<Type>,
+    return_type: Type,
+    /// Boxed so compiled code can keep its address
+    host: Box<Host>,
+}
+/// Compiles and runs Illusio code, which can call the host functions registered on it
+pub struct Engine {
+    file_name: String,
+    hosts: Vec<Registered>,
+}
+/// Why code couldn't be compiled or run
+#[derive(Debug)]
+pub enum EngineError {
+    /// Errors found in the code before running it
+    Compile(Vec<Error>),
+    /// A C function or library that can't be found
+    Link(String),
+    /// The error that stopped the program
+    Runtime(Box<Error>),
+    /// A bug in the compiler, at the code it was compiling
+    Internal(Box<Error>),
+}
+impl EngineError {
+    /// Shows the errors with the code they are about
+    pub fn show(&self) {
+        match self {
+            EngineError::Compile(errors) => errors.iter().for_each(Error::show),
+            EngineError::Link(message) => eprintln!("{}", message),
+            EngineError::Runtime(error) | EngineError::Internal(error) => error.show(),
+        }
+    }
+}
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EngineError::Compile(errors) => {
+                let messages: Vec<&str> = errors.iter().map(|err| err.message.as_str()).collect();
+                write!(f, "{}", messages.join("\n"))
+            }
+            EngineError::Link(message) => write!(f, "{}", message),
+            EngineError::Runtime(error) | EngineError::Internal(error) => {
+                write!(f, "{}", error.message)
+            }
+        }
+    }
+}
+impl std::error::Error for EngineError {}
+
+impl Engine {
+    pub fn new() -> Self {
+        Self {
+            file_name: "<script>".to_owned(),
+            hosts: Vec::new(),
+        }
+    }
+    /// Sets the name errors show for the code
+    pub fn file_name(&mut self, name: &str) -> &mut Self {
+        self.file_name = name.to_owned();
+        self
+    }
+    /// Registers a Rust function or closure, which Illusio code calls by `name`.
+    /// Its parameters can be `i64`, `f64`, `bool` or `String`, it can return one of those
+    /// or a `&str`, or nothing. A panic in it stops the program with an error.
+    pub fn register_fn<Args, F: HostFunction<Args>>(
+        &mut self,
+        name: &str,
+        function: F,
+    ) -> &mut Self {
+        self.hosts.retain(|registered| registered.host.name != name);
+        self.hosts.push(Registered {
+            params: F::params(),
+            return_type: F::return_type(),
+            host: Box::new(Host {
+                name: name.to_owned(),
+                call: function.into_call(),
+            }),
+        });
+        self
+    }
+    /// The tokens of `source`
+    pub fn lex(&self, source: &str) -> Result<Vec<Token>, EngineError> {
+        let mut lexer = Lexer::new(source, &self.file_name);
+        let tokens = self.stage(
+            source,
+            &mut lexer,
+            |lexer| {
+                let mut tokens = Vec::new();
+                loop {
+                    let token = lexer.next();
+                    if token.kind == TokenKind::Eof {
+                        break tokens;
+                    }
+                    tokens.push(token);
+                }
+            },
+            |lexer| Span::from(lexer.position..lexer.position),
+        )?;
+        if !lexer.errors.is_empty() {
+            return Err(EngineError::Compile(lexer.errors));
+        }
+        Ok(tokens)
+    }
+    /// The syntax tree of `source`
+    pub fn parse(&self, source: &str) -> Result<Vec<Expr>, EngineError> {
+        let mut parser = Parser::new(source, &self.file_name);
+        let ast = self.stage(source, &mut parser, Parser::parse, Parser::span)?;
+        if !parser.errors().is_empty() {
+            return Err(EngineError::Compile(parser.errors().to_vec()));
+        }
+        Ok(ast)
+    }
+    /// Resolves the names used in `ast`, and makes sure no reference outlives what it points to
+    pub fn analyze(&self, source: &str, ast: &Vec<Expr>) -> Result<Analysis, EngineError> {
+        let file_name = &self.file_name;
+        let mut analyzer = Analyzer::new(ast, source.to_owned(), file_name.clone());
+        for registered in &self.hosts {
+            analyzer.declare_function(&registered.host.name);
+        }
+        if self.stage(source, &mut analyzer, Analyzer::analyze, Analyzer::span)? {
+            return Err(EngineError::Compile(analyzer.errors().to_vec()));
+        }
+        let mut escape = EscapeChecker::new(ast, source.to_owned(), file_name.clone());
+        if self.stage(
+            source,
+            &mut escape,
+            EscapeChecker::check,
+            EscapeChecker::span,
+        )? {
+            return Err(EngineError::Compile(escape.errors().to_vec()));
+        }
+        Ok(Analysis {
+            captures: analyzer.captures(),
+        })
+    }
+    /// Type checks `ast`
+    pub fn check<'a>(&self, source: &str, ast: &'a Vec<Expr>) -> Result<Checked<'a>, EngineError> {
+        let mut checker = TypeChecker::new(ast, source, &self.file_name);
+        for registered in &self.hosts {
+            checker.declare_host(
+                &registered.host.name,
+                registered.params.clone(),
+                registered.return_type.clone(),
+            );
+        }
+        if self.stage(source, &mut checker, TypeChecker::check, TypeChecker::span)? {
+            return Err(EngineError::Compile(checker.errors().to_vec()));
+        }
+        Ok(Checked { ast, checker })
+    }
+    /// Translates a checked program to the representation the compiler takes,
+    /// or reports what it uses that can't be translated yet
+    pub fn lower<'a>(
+        &self,
+        source: &str,
+        analysis: Analysis,
+        checked: Checked<'a>,
+    ) -> Result<Lowered<'a>, EngineError> {
+        let mut lower = Lower::new(checked.ast, checked.checker, analysis.captures);
+        self.stage(source, &mut lower, Lower::translate, Lower::span)?;
+        if !lower.errors().is_empty() {
+            return Err(EngineError::Compile(lower.errors().to_vec()));
+        }
+        Ok(Lowered {
+            source: source.to_owned(),
+            lower,
+        })
+    }
+    /// The Cranelift IR of a lowered program
+    pub fn emit(&self, lowered: &Lowered) -> Result<String, EngineError> {
+        lowered
+            .lower
+            .emit(self.addresses())
+            .map_err(|err| self.jit_error(&lowered.source, err))
+    }
+    /// Compiles a lowered program to machine code
+    pub fn jit(&self, lowered: &Lowered) -> Result<Compiled<'_>, EngineError> {
+        let program = lowered
+            .lower
+            .compile(self.addresses())
+            .map_err(|err| self.jit_error(&lowered.source, err))?;
+        Ok(Compiled {
+            engine: self,
+            source: lowered.source.clone(),
+            program,
+            result: lowered.result_type().cloned().unwrap_or(Type::None),
+        })
+    }
+    /// Goes through every stage to compile `source`
+    pub fn compile(&self, source: &str) -> Result<Compiled<'_>, EngineError> {
+        let ast = self.parse(source)?;
+        let analysis = self.analyze(source, &ast)?;
+        let checked = self.check(source, &ast)?;
+        let lowered = self.lower(source, analysis, checked)?;
+        self.jit(&lowered)
+    }
+    /// Compiles and runs `source`, what it allocates is freed when it ends
+    pub fn run(&self, source: &str) -> Result<(), EngineError> {
+        self.compile(source)?.run()
+    }
+    /// Compiles and runs `source`, giving the value of its last expression
+    pub fn eval<T: for<'a> FromIllusio<'a>>(&self, source: &str) -> Result<T, EngineError> {
+        self.compile(source)?.eval()
+    }
+    /// Where compiled code finds every host function
+    fn addresses(&self) -> HashMap<String, *const Host> {
+        self.hosts
+            .iter()
+            .map(|registered| {
+                (
+                    registered.host.name.clone(),
+                    &*registered.host as *const Host,
+                )
+            })
+            .collect()
+    }
+    /// Runs a stage of the compiler on `source`, a bug in it is reported at the span `at` gives
+    fn stage<S, T>(
+        &self,
+        source: &str,
+        state: &mut S,
+        run: impl FnOnce(&mut S) -> T,
+        at: impl FnOnce(&S) -> Span,
+    ) -> Result<T, EngineError> {
+        catch_bug(|| run(state)).map_err(|message| self.internal(source, message, at(state)))
+    }
+    fn jit_error(&self, source: &str, err: JitError) -> EngineError {
+        match err {
+            JitError::Link(message) => EngineError::Link(message),
+            JitError::Bug(message, span) => self.internal(source, message, span),
+        }
+    }
+    /// An internal compiler error, for a bug found at `span`
+    fn internal(&self, source: &str, message: String, span: Span) -> EngineError {
+        let mut error = self.error(
+            source,
+            format!("Internal compiler error: {}", message),
+            span,
+        );
+        error.help = "This is a bug in the compiler, please report it".to_owned();
+        EngineError::Internal(Box::new(error))
+    }
+    fn error(&self, source: &str, message: String, span: Span) -> Error {
+        Error {
+            source: source.to_owned(),
+            file_name: self.file_name.clone(),
+            message,
+            span,
+            help: "".to_owned(),
+            labels: Vec::new(),
+        }
+    }
+}
+/// What lowering a program needs from its analysis
+pub struct Analysis {
+    /// The variables every closure captures, by the span of the closure
+    captures: HashMap<Span, Vec<String>>,
+}
+impl Analysis {
+    /// The variables every closure captures, by the span of the closure
+    pub fn captures(&self) -> &HashMap<Span, Vec<String>> {
+        &self.captures
+    }
+}
+/// A type checked program
+pub struct Checked<'a> {
+    ast: &'a Vec<Expr>,
+    checker: TypeChecker<'a>,
+}
+/// A program translated to the representation the compiler takes
+pub struct Lowered<'a> {
+    source: String,
+    lower: Lower<'a>,
+}
+impl Lowered<'_> {
+    /// The type of the value the program gives with its last expression, if it gives one
+    pub fn result_type(&self) -> Option<&Type> {
+        self.lower.result_type()
+    }
+}
+/// A program compiled to machine code, it can run as long as the engine
+/// that compiled it, which has its host functions.
+///
+/// The strings, vectors and maps a run allocates are only freed when the program is dropped,
+/// not when the run ends, so every run keeps adding to the memory it uses.
+/// A program run over and over should be compiled again from time to time.
+pub struct Compiled<'e> {
+    engine: &'e Engine,
+    source: String,
+    program: Program,
+    /// The type of the value the program gives, `None` if it doesn't give one
+    result: Type,
+}
+impl Compiled<'_> {
+    pub fn result_type(&self) -> &Type {
+        &self.result
+    }
+    pub fn run(&self) -> Result<(), EngineError> {
+        self.execute()?;
+        Ok(())
+    }
+    /// Runs the program, giving the value of its last expression
+    pub fn eval<'p, T: FromIllusio<'p>>(&'p self) -> Result<T, EngineError> {
+        if self.result != T::ty() {
+            let source = &self.source;
+            let span = match source.trim_end().len() {
+                0 => Span::from(0..0),
+                len => Span::from(source[..len].rfind('\n').map_or(0, |i| i + 1)..len),
+            };
+            let message = match &self.result {
+                Type::None => format!("The code doesn't end with a value of type {}", T::ty()),
+                ty => format!("The code ends with a value of type {}, not {}", ty, T::ty()),
+            };
+            return Err(EngineError::Compile(vec![self
+                .engine
+                .error(source, message, span)]));
+        }
+        self.execute().map(T::from_raw)
+    }
+    fn execute(&self) -> Result<u64, EngineError> {
+        self.program.run().map_err(|err| {
+            EngineError::Runtime(Box::new(self.engine.error(
+                &self.source,
+                err.message,
+                err.span,
+            )))
+        })
+    }
+}
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -3,28 +3,38 @@ use codespan_reporting::diagnostic::{Diagnostic, Label};
 use codespan_reporting::files::SimpleFiles;
 use codespan_reporting::term::termcolor::{ColorChoice, StandardStream};
 use codespan_reporting::term::Config;
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
 
 /// Error {source, filename, message, span
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct Error {
     pub source: String,
     pub file_name: String,
     pub message: String,
     pub span: Span,
     pub help: String,
+    /// Other places the error is about, with a message for each
+    pub labels: Vec<(Span, String)>,
 }
 impl Error {
     pub fn show(&self) {
         let mut files = SimpleFiles::new();
         let file_id = files.add(&self.file_name, &self.source);
-        let help = if self.help == "" {
+        let help = if self.help.is_empty() {
             vec![]
         } else {
             vec![self.help.clone()]
         };
         let diagnostic_err = Diagnostic::error()
             .with_message(&self.message)
-            .with_labels(vec![Label::primary(file_id, self.span)])
+            .with_labels(
+                std::iter::once(Label::primary(file_id, self.span))
+                    .chain(self.labels.iter().map(|(span, message)| {
+                        Label::secondary(file_id, *span).with_message(message)
+                    }))
+                    .collect(),
+            )
             .with_notes(help);
         let writer = StandardStream::stderr(ColorChoice::Always);
         let config = Config::default();
@@ -32,3 +42,28 @@ impl Error {
             .unwrap();
     }
 }
+/// A bug in the compiler, it is reported as an internal compiler error
+struct Bug(String);
+/// Stops the stage of the compiler that found a bug, which is reported
+/// at what the stage was working on
+pub fn bug(message: String) -> ! {
+    panic::resume_unwind(Box::new(Bug(message)))
+}
+/// Runs a stage of the compiler, giving the message of a bug in it.
+/// Any panic is a bug.
+pub fn catch_bug<T>(stage: impl FnOnce() -> T) -> Result<T, String> {
+    panic::catch_unwind(AssertUnwindSafe(stage)).map_err(|payload| {
+        match payload.downcast::<Bug>() {
+            Ok(bug) => bug.0,
+            Err(payload) => panic_message(&*payload),
+        }
+    })
+}
+/// The message a panic was started with
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_default()
+}
@@ -0,0 +1,346 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{ast::*, error::Error, token::Span};
+
+/// A reference to a local variable, like `&x`
+#[derive(Clone)]
+struct Borrow {
+    name: String,
+    /// Depth of the scope that owns the variable
+    depth: usize,
+    span: Span,
+}
+
+/// Checks that references to local variables don't outlive them.
+/// A reference can't be returned, stored on the heap, or stored in a variable
+/// or through a pointer that lives longer than the variable it points to.
+pub struct EscapeChecker<'a> {
+    ast: &'a Vec<Expr>,
+    source: String,
+    filename: String,
+    /// The variables of every scope, with the references they can hold
+    variables: Vec<HashMap<String, Vec<Borrow>>>,
+    /// Whether a function is being checked, outside of one the first scope has the globals
+    in_function: bool,
+    /// The expression being checked
+    span: Span,
+    errors: Vec<Error>,
+}
+impl<'a> EscapeChecker<'a> {
+    pub fn new(ast: &'a Vec<Expr>, source: String, filename: String) -> Self {
+        Self {
+            ast,
+            source,
+            filename,
+            variables: vec![HashMap::new()],
+            in_function: false,
+            span: Span::from(0..0),
+            errors: Vec::new(),
+        }
+    }
+    pub fn check(&mut self) -> bool {
+        for expr in self.ast {
+            self.handle(expr);
+        }
+        self.has_errors()
+    }
+    /// Gives the references to local variables that the value of `expr` can hold
+    fn handle(&mut self, expr: &Expr) -> Vec<Borrow> {
+        self.span = expr.span;
+        match &expr.inner {
+            ExprKind::Ref(value) => match &value.inner {
+                ExprKind::Ident(name) => match self.depth(name) {
+                    // Globals live as long as the program
+                    Some(0) if !self.in_function => vec![],
+                    Some(depth) => vec![Borrow {
+                        name: name.clone(),
+                        depth,
+                        span: expr.span,
+                    }],
+                    None => vec![],
+                },
+                _ => self.handle(value),
+            },
+            ExprKind::Ident(name) => self.get(name).cloned().unwrap_or_default(),
+            ExprKind::Try(value)
+            | ExprKind::TupleIndex(value, _)
+            | ExprKind::NamedArg(_, value) => self.handle(value),
+            // Tuples are values, they hold what their elements hold
+            ExprKind::Tuple(elements) => elements
+                .iter()
+                .flat_map(|element| self.handle(element))
+                .collect(),
+            // Reading through a reference to a variable gives what that variable holds
+            ExprKind::Deref(pointer) => self
+                .handle(pointer)
+                .iter()
+                .flat_map(|borrow| self.held(borrow))
+                .collect(),
+            ExprKind::Var(name, value, _) => {
+                let borrows = self.handle(value);
+                self.variables
+                    .last_mut()
+                    .unwrap()
+                    .insert(name.clone(), borrows);
+                vec![]
+            }
+            ExprKind::Destructure(names, value) => {
+                let borrows = self.handle(value);
+                let scope = self.variables.last_mut().unwrap();
+                for name in names {
+                    scope.insert(name.clone(), borrows.clone());
+                }
+                vec![]
+            }
+            ExprKind::Assign(target, value) => {
+                let borrows = self.handle(value);
+                match &target.inner {
+                    ExprKind::Ident(name) => {
+                        if let Some(depth) = self.depth(name) {
+                            self.store(name, depth, borrows, value.span);
+                        }
+                    }
+                    ExprKind::Deref(pointer) => {
+                        let targets = self.handle(pointer);
+                        // The pointer can point outside of this function
+                        if targets.is_empty() {
+                            for borrow in &borrows {
+                                self.escape(
+                                    borrow,
+                                    format!(
+                                        "Cannot store a reference to `{}` through this pointer",
+                                        borrow.name
+                                    ),
+                                    value.span,
+                                    format!("The pointer can outlive `{}`", borrow.name),
+                                );
+                            }
+                        }
+                        for target in targets {
+                            self.store(&target.name, target.depth, borrows.clone(), value.span);
+                        }
+                    }
+                    _ => {
+                        self.handle(target);
+                        self.heap(borrows, value.span);
+                    }
+                }
+                vec![]
+            }
+            ExprKind::Return(Some(value)) => {
+                for borrow in self.handle(value) {
+                    self.escape(
+                        &borrow,
+                        format!(
+                            "Cannot return a reference to the local variable `{}`",
+                            borrow.name
+                        ),
+                        value.span,
+                        format!("`{}` is freed when the function returns", borrow.name),
+                    );
+                }
+                vec![]
+            }
+            ExprKind::Array(elements) | ExprKind::Vector(elements) => {
+                for element in elements {
+                    let borrows = self.handle(element);
+                    self.heap(borrows, element.span);
+                }
+                vec![]
+            }
+            ExprKind::Map(entries) => {
+                for (key, value) in entries {
+                    for entry in [key, value] {
+                        let borrows = self.handle(entry);
+                        self.heap(borrows, entry.span);
+                    }
+                }
+                vec![]
+            }
+            // Methods like `push` keep their arguments in their receiver
+            ExprKind::MethodCall(receiver, _, args) => {
+                let mut borrows = self.handle(receiver);
+                for arg in args {
+                    let arg_borrows = self.handle(arg);
+                    self.heap(arg_borrows.clone(), arg.span);
+                    borrows.extend(arg_borrows);
+                }
+                borrows
+            }
+            // A function can give back any of the references it is called with
+            ExprKind::FunctionCall(_, args) => {
+                args.iter().flat_map(|arg| self.handle(arg)).collect()
+            }
+            ExprKind::Block(exprs) => {
+                self.block(vec![], exprs);
+                vec![]
+            }
+            ExprKind::If(condition, exprs) => {
+                self.handle(condition);
+                self.block(vec![], exprs);
+                vec![]
+            }
+            // The value of the optional or the result holds what they hold
+            ExprKind::IfLet(pattern, value, exprs) => {
+                let borrows = self.handle(value);
+                self.block(vec![(pattern.name().clone(), borrows)], exprs);
+                vec![]
+            }
+            ExprKind::For(name, second, iterable, exprs) => {
+                self.handle(iterable);
+                let names = std::iter::once(name)
+                    .chain(second)
+                    .map(|name| (name.clone(), vec![]))
+                    .collect();
+                self.block(names, exprs);
+                vec![]
+            }
+            ExprKind::FunctionDeclaration(_, _, params, _, body) => {
+                self.function(params, body);
+                vec![]
+            }
+            // Closures keep copies of the variables they use, with the references they hold
+            ExprKind::Closure(params, _, body) => {
+                let mut names = HashSet::new();
+                for expr in body {
+                    used_names(expr, &mut names);
+                }
+                let borrows = names
+                    .iter()
+                    .filter_map(|name| self.get(name))
+                    .flatten()
+                    .cloned()
+                    .collect();
+                self.function(params, body);
+                borrows
+            }
+            inner => {
+                for child in inner.children() {
+                    self.handle(child);
+                }
+                vec![]
+            }
+        }
+    }
+    /// Handles the body of a function or a closure. The variables around it
+    /// are copied into closures, so references to them can't escape.
+    fn function(&mut self, params: &[Param], body: &[Expr]) {
+        let scope = params
+            .iter()
+            .map(|param| (param.name.clone(), vec![]))
+            .collect();
+        let outer = std::mem::replace(&mut self.variables, vec![scope]);
+        let in_function = std::mem::replace(&mut self.in_function, true);
+        for expr in body {
+            self.handle(expr);
+        }
+        self.variables = outer;
+        self.in_function = in_function;
+    }
+    /// Handles `exprs` in a new scope, which owns the variables `names`
+    /// with the references they hold
+    fn block(&mut self, names: Vec<(String, Vec<Borrow>)>, exprs: &[Expr]) {
+        self.start_scope();
+        self.variables.last_mut().unwrap().extend(names);
+        for expr in exprs {
+            self.handle(expr);
+        }
+        self.end_scope();
+    }
+    /// Stores `borrows` in the variable `name` owned by the scope at `depth`
+    fn store(&mut self, name: &str, depth: usize, borrows: Vec<Borrow>, span: Span) {
+        for borrow in &borrows {
+            if borrow.depth > depth {
+                self.escape(
+                    borrow,
+                    format!("`{}` doesn't live as long as `{}`", borrow.name, name),
+                    span,
+                    format!(
+                        "`{}` is freed at the end of its block, but `{}` lives longer",
+                        borrow.name, name
+                    ),
+                );
+            }
+        }
+        self.variables[depth].get_mut(name).unwrap().extend(borrows);
+    }
+    /// Reports `borrows` being stored in an array, a vector or a map
+    fn heap(&mut self, borrows: Vec<Borrow>, span: Span) {
+        for borrow in borrows {
+            self.escape(
+                &borrow,
+                format!(
+                    "Cannot store a reference to the local variable `{}` on the heap",
+                    borrow.name
+                ),
+                span,
+                "Arrays, vectors and maps can outlive the variables they point to".to_owned(),
+            );
+        }
+    }
+    /// Reports the reference `borrow` escaping at `span`
+    fn escape(&mut self, borrow: &Borrow, message: String, span: Span, help: String) {
+        self.errors.push(Error {
+            source: self.source.clone(),
+            file_name: self.filename.clone(),
+            message,
+            span,
+            help,
+            labels: if borrow.span == span {
+                vec![]
+            } else {
+                vec![(borrow.span, format!("`{}` is borrowed here", borrow.name))]
+            },
+        })
+    }
+    /// The references held by the variable that `borrow` points to
+    fn held(&self, borrow: &Borrow) -> Vec<Borrow> {
+        self.variables
+            .get(borrow.depth)
+            .and_then(|scope| scope.get(&borrow.name))
+            .cloned()
+            .unwrap_or_default()
+    }
+    fn get(&self, name: &str) -> Option<&Vec<Borrow>> {
+        self.variables
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+    }
+    /// Depth of the scope that owns the variable `name`
+    fn depth(&self, name: &str) -> Option<usize> {
+        self.variables
+            .iter()
+            .rposition(|scope| scope.contains_key(name))
+    }
+    fn start_scope(&mut self) {
+        self.variables.push(HashMap::new());
+    }
+    fn end_scope(&mut self) {
+        self.variables.pop();
+    }
+    pub fn has_errors(&self) -> bool {
+        !self.errors.is_empty()
+    }
+    /// The expression being checked, a bug in the checker is reported there
+    pub fn span(&self) -> Span {
+        self.span
+    }
+    pub fn errors(&self) -> &[Error] {
+        &self.errors
+    }
+}
+/// Collects the names of the variables used in `expr`, and of the closures it calls
+fn used_names(expr: &Expr, names: &mut HashSet<String>) {
+    match &expr.inner {
+        ExprKind::Ident(name) | ExprKind::FunctionCall(name, _) => {
+            names.insert(name.clone());
+        }
+        // Nested functions can't see the variables around them
+        ExprKind::FunctionDeclaration(..) => return,
+        _ => {}
+    }
+    for child in expr.inner.children() {
+        used_names(child, names);
+    }
+}
@@ -1,25 +1,37 @@
 use crate::{error::Error, string_spliter, token::*};
 use colored::Colorize;
+use unicode_xid::UnicodeXID;
 /// # **Illusio Lexer**
-
 pub struct Lexer {
-    input: Vec<u8>,
+    input: String,
+    /// Byte position of the current character, which may take up to 4 bytes
     pub position: usize,
     pub errors: Vec<Error>,
-    ch: u8,
+    ch: char,
     file_name: String,
     pub read_position: usize,
+    /// Brace depth of every string interpolation we are currently inside of
+    interpolations: Vec<usize>,
+}
+/// Where the lexer was, to go back to after looking at the tokens ahead
+pub struct Checkpoint {
+    position: usize,
+    read_position: usize,
+    ch: char,
+    interpolations: Vec<usize>,
+    errors: usize,
 }
 impl Lexer {
     /// Create a new Lexer, takes input of string.
     pub fn new(input: &str, file_name: &str) -> Self {
         let mut lexer = Self {
-            input: input.as_bytes().to_vec(),
+            input: input.to_owned(),
             position: 0,
             file_name: file_name.to_owned(),
-            ch: b'\0',
+            ch: '\0',
             errors: Vec::new(),
             read_position: 0,
+            interpolations: Vec::new(),
         };
         lexer.read();
         lexer
@@ -28,31 +40,31 @@ impl Lexer {
     fn read(&mut self) {
         // Checking If out current read position passed the input length.
         if self.read_position >= self.input.len() {
-            self.ch = b'\0'
+            self.ch = '\0'
         } else {
-            self.ch = self.input[self.read_position]
+            self.ch = self.input[self.read_position..].chars().next().unwrap()
         }
         self.position = self.read_position;
         // NOTE: Read position can also represent the peek token,
         // since we assign position before we increase read_position
-        self.read_position += 1;
+        self.read_position += self.ch.len_utf8();
     }
     /// Used for skiping whitespace
     fn whitespace(&mut self) {
         loop {
             match self.ch {
-                b' ' | b'\t' | b'\r' => self.read(),
-                b'\n' => self.read(),
-                b'#' => {
-                    if self.next_match(b'*') {
+                ' ' | '\t' | '\r' => self.read(),
+                '\n' => self.read(),
+                '#' => {
+                    if self.next_match('*') {
                         self.read();
                         loop {
-                            if self.ch == b'\0' {
+                            if self.ch == '\0' {
                                 break;
                             }
-                            if self.ch == b'*' && self.peek_next() == Some(b'#') {
+                            if self.ch == '*' && self.peek_next() == Some('#') {
                                 self.next();
-                                break
+                                break;
                             }
                             self.read();
                         }
@@ -60,7 +72,7 @@ impl Lexer {
                         loop {
                             // Reading until the end of the line
                             self.read();
-                            if self.ch == b'\n' || self.ch == b'\0' {
+                            if self.ch == '\n' || self.ch == '\0' {
                                 break;
                             }
                         }
@@ -70,38 +82,79 @@ impl Lexer {
             }
         }
     }
-    /// Peeking for the next byte
-    pub fn peek_next(&self) -> Option<u8> {
-        return self.input.get(self.read_position).copied();
+    /// Peeking for the next character
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            position: self.position,
+            read_position: self.read_position,
+            ch: self.ch,
+            interpolations: self.interpolations.clone(),
+            errors: self.errors.len(),
+        }
+    }
+    /// Goes back to `checkpoint`, forgetting the errors found after it
+    pub fn restore(&mut self, checkpoint: Checkpoint) {
+        self.position = checkpoint.position;
+        self.read_position = checkpoint.read_position;
+        self.ch = checkpoint.ch;
+        self.interpolations = checkpoint.interpolations;
+        self.errors.truncate(checkpoint.errors);
+    }
+    pub fn peek_next(&self) -> Option<char> {
+        self.input.get(self.read_position..)?.chars().next()
     }
     pub fn next(&mut self) -> Token {
         self.whitespace();
         let mut token = Token::new(
             TokenKind::Illegal,
-            String::from_utf8(vec![self.ch]).unwrap_or("".to_owned()),
-            Span::from(self.position..self.position + 1),
+            self.ch.to_string(),
+            Span::from(self.position..self.read_position),
         );
         match self.ch {
-            b'+' => {
+            '+' => {
                 token.kind = TokenKind::Plus;
             }
-            b'-' => {
+            '-' => {
                 token.kind = TokenKind::Minus;
             }
-            b'*' => {
+            '*' => {
                 token.kind = TokenKind::Asterisk;
             }
-            b'/' => {
+            '/' => {
                 token.kind = TokenKind::Slash;
             }
-            b'%' => {
+            '%' => {
                 token.kind = TokenKind::Mod;
             }
-            b'|' => {
-                token.kind = TokenKind::Or;
+            '|' => {
+                if self.next_match('|') {
+                    token.kind = TokenKind::Or;
+                    token.literal = String::from("||");
+                    token.span = Span::from(token.span.start..self.position + 1);
+                } else {
+                    token.kind = TokenKind::Pipe;
+                }
+            }
+            '^' => {
+                token.kind = TokenKind::Caret;
+            }
+            '~' => {
+                token.kind = TokenKind::Tilde;
             }
-            b'>' => {
-                if self.next_match(b'=') {
+            '?' => {
+                token.kind = TokenKind::Question;
+            }
+            '>' => {
+                if self.next_match('>') {
+                    if self.next_match('>') {
+                        token.kind = TokenKind::UnsignedShiftRight;
+                        token.literal = String::from(">>>");
+                    } else {
+                        token.kind = TokenKind::ShiftRight;
+                        token.literal = String::from(">>");
+                    }
+                    token.span = Span::from(token.span.start..self.position + 1);
+                } else if self.next_match('=') {
                     token.kind = TokenKind::GreaterThanEqual;
                     token.literal = String::from(">=");
                     token.span = Span::from(token.span.start..self.position + 1);
@@ -109,8 +162,12 @@ impl Lexer {
                     token.kind = TokenKind::GreaterThan;
                 }
             }
-            b'<' => {
-                if self.next_match(b'=') {
+            '<' => {
+                if self.next_match('<') {
+                    token.kind = TokenKind::ShiftLeft;
+                    token.literal = String::from("<<");
+                    token.span = Span::from(token.span.start..self.position + 1);
+                } else if self.next_match('=') {
                     token.kind = TokenKind::LessThanEqual;
                     token.literal = String::from("<=");
                     token.span = Span::from(token.span.start..self.position + 1);
@@ -118,26 +175,49 @@ impl Lexer {
                     token.kind = TokenKind::LessThan;
                 }
             }
-            b'(' => {
+            '(' => {
                 token.kind = TokenKind::OpeningParen;
             }
-            b')' => {
+            ')' => {
                 token.kind = TokenKind::ClosingParen;
             }
-            b'{' => {
+            '{' => {
+                if let Some(depth) = self.interpolations.last_mut() {
+                    *depth += 1;
+                }
                 token.kind = TokenKind::OpeningBracket;
             }
-            b':' => {
+            ':' => {
                 token.kind = TokenKind::Colon;
             }
-            b'}' => {
-                token.kind = TokenKind::ClosingBracket;
+            // `t.0` gets an element of a tuple, `.5` is a float
+            '.' if !self.peek_next().unwrap_or('\0').is_ascii_digit() || self.after_value() => {
+                token.kind = TokenKind::Dot;
+            }
+            '}' => match self.interpolations.last_mut() {
+                // The brace closes an interpolation, so the string continues after it
+                Some(0) => {
+                    self.interpolations.pop();
+                    return self
+                        .string(TokenKind::InterpolationMiddle, TokenKind::InterpolationEnd);
+                }
+                Some(depth) => {
+                    *depth -= 1;
+                    token.kind = TokenKind::ClosingBracket;
+                }
+                None => token.kind = TokenKind::ClosingBracket,
+            },
+            '[' => {
+                token.kind = TokenKind::OpeningSquare;
+            }
+            ']' => {
+                token.kind = TokenKind::ClosingSquare;
             }
-            b',' => {
+            ',' => {
                 token.kind = TokenKind::Comma;
             }
-            b'=' => {
-                if self.next_match(b'=') {
+            '=' => {
+                if self.next_match('=') {
                     token.kind = TokenKind::EqualTo;
                     token.literal = String::from("==");
                     token.span = Span::from(token.span.start..self.position + 1);
@@ -145,8 +225,8 @@ impl Lexer {
                     token.kind = TokenKind::Equal;
                 }
             }
-            b'!' => {
-                if self.next_match(b'=') {
+            '!' => {
+                if self.next_match('=') {
                     token.kind = TokenKind::NotEqual;
                     token.literal = String::from("!=");
                     token.span = Span::from(token.span.start..self.position + 1);
@@ -155,156 +235,287 @@ impl Lexer {
                 }
             }
 
-            b'\0' => {
+            '\0' => {
                 token.kind = TokenKind::Eof;
                 token.literal = "end of file".to_owned()
             }
-            b';' => {
+            ';' => {
                 token.kind = TokenKind::SemiColon;
             }
-            b'&' => {
-                token.kind = TokenKind::And;
+            '&' => {
+                if self.next_match('&') {
+                    token.kind = TokenKind::And;
+                    token.literal = String::from("&&");
+                    token.span = Span::from(token.span.start..self.position + 1);
+                } else {
+                    token.kind = TokenKind::Ampersand;
+                }
             }
-            b'\'' => {
+            '\'' => {
                 let start = self.position;
                 self.read();
-                let mut buf: Vec<u8> = Vec::new();
-                while self.ch != b'\'' {
-                    if self.ch == b'\\' {
-                        self.read();
-                        if self.ch == b'\'' {
-                            buf.push(b'\'');
-                            self.read();
-                            continue;
-                        }
-                    }
-                    if self.ch == b'\0' {
+                while self.ch != '\'' {
+                    if self.ch == '\0' {
+                        let text = &self.input[start + 1..self.position];
                         self.errors.push(Error {
-                            source: String::from_utf8(self.input.clone()).unwrap(),
+                            source: self.input.clone(),
                             file_name: self.file_name.clone(),
                             message: "Unterminated String".to_string(),
                             span: Span::from(self.position..self.position + 1),
                             help: format!(
                                 "Try adding \"'\" here like this: {}",
-                                format!(
-                                    "'{}'",
-                                    String::from_utf8(buf.clone()).unwrap().green().bold()
-                                )
-                                .green()
+                                format!("'{}'", text.green().bold()).green()
                             ),
+                            labels: Vec::new(),
                         });
                         break;
                     }
-                    buf.push(self.ch);
+                    if self.ch == '\\' && self.peek_next().is_some() {
+                        self.read();
+                    }
                     self.read();
                 }
                 token.span = Span::from(start..self.position + 1);
-                let str_buf =
-                    String::from_utf8(string_spliter::split_string(buf)).unwrap_or("".to_owned());
+                let text = self.input[start + 1..self.position].to_owned();
+                let str_buf = String::from_utf8(self.unescape(text.as_bytes(), start + 1))
+                    .unwrap_or("".to_owned());
                 token.kind = TokenKind::String;
                 token.literal = str_buf;
             }
 
-            b'"' => {
+            '"' if self.input[self.position..].starts_with("\"\"\"") => {
+                return self.literal(self.position, 0, false)
+            }
+            '"' => return self.string(TokenKind::InterpolationStart, TokenKind::String),
+            'r' if self.raw_string_ahead() => {
                 let start = self.position;
                 self.read();
-                let mut buf: Vec<u8> = Vec::new();
-                while self.ch != b'"' {
-                    if self.ch == b'\\' {
-                        if self.peek_next() == Some(b'"') {
-                            self.read();
-                            buf.push(b'"');
-                            self.read();
-                            continue;
-                        }
-                    }
-                    if self.ch == b'\0' {
-                        self.errors.push(Error {
-                            source: String::from_utf8(self.input.clone()).unwrap(),
-                            file_name: self.file_name.clone(),
-                            message: "Unterminated String".to_string(),
-                            span: Span::from(self.position..self.position + 1),
-                            help: format!(
-                                "Try adding '\"' here like this: {}",
-                                format!("\"{}\"", String::from_utf8(buf).unwrap().green().bold())
-                                    .green()
-                            ),
-                        });
-                        return token;
-                    }
-                    buf.push(self.ch);
+                let mut hashes = 0;
+                while self.ch == '#' {
+                    hashes += 1;
                     self.read();
                 }
-                token.span = Span::from(start..self.position + 1);
-                let str_buf =
-                    String::from_utf8(string_spliter::split_string(buf)).unwrap_or("".to_owned());
-                token.kind = TokenKind::String;
-                token.literal = str_buf;
+                return self.literal(start, hashes, true);
             }
 
             _ => {
                 if self.ch.is_ascii_digit()
-                    || (self.ch == b'.' && self.peek_next().unwrap_or(0).is_ascii_digit())
+                    || (self.ch == '.' && self.peek_next().unwrap_or('\0').is_ascii_digit())
                 {
-                    let mut buf: Vec<u8> = Vec::new();
-                    let mut token_type = TokenKind::Int;
+                    return self.number();
+                }
+                if self.ch.is_xid_start() || self.ch == '_' {
+                    let mut buf = String::new();
                     let start = self.position;
-                    let mut accept = "0123456789_";
-                    if self.ch == b'0' && self.peek_next().unwrap_or(b'\0') == b'x' {
-                        accept = "0x123456789ABCDEFabcdef_"
-                    }
-                    if self.ch == b'0' && self.peek_next().unwrap_or(b'\0') == b'b' {
-                        accept = "0b10_"
-                    }
-                    while accept.contains(std::str::from_utf8(&[self.ch]).unwrap()) {
-                        if self.ch != b'_' {
-                            buf.push(self.ch);
-                            self.read();
-                        } else {
-                            self.read()
-                        }
-                    }
-                    if self.ch == b'.' && self.peek_next().unwrap_or(0).is_ascii_digit() {
-                        // Pushing the dot
-                        buf.push(self.ch);
-                        self.read();
-                        // Pushing the Number
-                        while self.ch.is_ascii_digit() {
-                            buf.push(self.ch);
-                            self.read();
-                        }
-                        token_type = TokenKind::Float;
-                    } else if self.ch == b'.' && !self.peek_next().unwrap_or(b'\0').is_ascii_digit()
-                    {
+                    while self.ch.is_xid_continue() {
                         buf.push(self.ch);
                         self.read();
-                        token_type = TokenKind::Float;
                     }
-                    let str_buf = String::from_utf8(buf).unwrap();
-                    token.kind = token_type;
-                    token.literal = str_buf;
+                    token.kind = self.keyword(&buf);
+                    token.literal = buf;
                     token.span = Span::from(start..self.position);
                     return token;
                 }
-                if self.ch.is_ascii_alphabetic() || self.ch == b'_' {
-                    let mut buf: Vec<u8> = Vec::new();
-                    let start = self.position;
-                    while self.ch.is_ascii_alphanumeric() || self.ch == b'_' {
-                        buf.push(self.ch);
+            }
+        }
+        self.read();
+        token
+    }
+    /// Lexes an int or float literal, its digits are checked by the parser
+    fn number(&mut self) -> Token {
+        let start = self.position;
+        let mut kind = TokenKind::Int;
+        if self.ch == '0' && matches!(self.peek_next(), Some('x' | 'o' | 'b')) {
+            self.read();
+            self.read();
+        } else if self.ch != '.' && self.input[..start].ends_with('.') {
+            // The index of a tuple element, so `t.0.1` is two indexes and not a float
+            self.digits();
+        } else {
+            self.digits();
+            if self.ch == '.' && self.peek_next().unwrap_or('\0').is_ascii_digit() {
+                self.read();
+                self.digits();
+                kind = TokenKind::Float;
+            } else if self.ch == '.'
+                // `1.show()` is a method call on an int, not a float
+                && !self.peek_next().unwrap_or('\0').is_xid_continue()
+            {
+                self.read();
+                kind = TokenKind::Float;
+            }
+            if matches!(self.ch, 'e' | 'E')
+                && matches!(self.peek_next(), Some('0'..='9' | '+' | '-' | '_'))
+            {
+                self.read();
+                if matches!(self.ch, '+' | '-') {
+                    self.read();
+                }
+                self.digits();
+                kind = TokenKind::Float;
+            }
+        }
+        // Letters right after a number are part of it, so `0b102` or `5px` are reported as a whole
+        while self.ch.is_xid_continue() {
+            self.read();
+        }
+        Token::new(
+            kind,
+            self.input[start..self.position].to_owned(),
+            Span::from(start..self.position),
+        )
+    }
+    /// Whether the current character comes right after a name, a `)` or a `]`
+    fn after_value(&self) -> bool {
+        self.input[..self.position]
+            .chars()
+            .next_back()
+            .is_some_and(|ch| ch.is_xid_continue() || ch == ')' || ch == ']')
+    }
+    fn digits(&mut self) {
+        while self.ch.is_ascii_digit() || self.ch == '_' {
+            self.read();
+        }
+    }
+    /// Lexes the text of a double quoted string, starting at its opening `"`
+    /// or at the `}` closing an interpolation. A `{` starts an interpolation,
+    /// and the expression inside of it is lexed by the following `next` calls.
+    fn string(&mut self, interpolation: TokenKind, end: TokenKind) -> Token {
+        let start = self.position;
+        self.read();
+        while self.ch != '"' && self.ch != '{' {
+            if self.ch == '\\' && self.peek_next() == Some('u') {
+                // The braces of `\u{..}` don't start an interpolation
+                self.read();
+                self.read();
+                if self.ch == '{' {
+                    while !matches!(self.ch, '}' | '"' | '\0') {
                         self.read();
                     }
-                    let str_buf = String::from_utf8(buf).unwrap();
-                    token.kind = self.keyword(&str_buf);
-                    token.literal = str_buf;
-                    token.span = Span::from(start..self.position);
-                    return token;
                 }
+                continue;
+            }
+            if self.ch == '\\' && self.peek_next().is_some() {
+                // Escapes are decoded by the string spliter, we only have to
+                // make sure that `\"` and `\{` don't end the text
+                self.read();
+            } else if self.ch == '\0' {
+                let text = &self.input[start + 1..self.position];
+                let quoted = format!("\"{}\"", text.green().bold());
+                self.errors.push(Error {
+                    source: self.input.clone(),
+                    file_name: self.file_name.clone(),
+                    message: "Unterminated String".to_string(),
+                    span: Span::from(self.position..self.position + 1),
+                    help: format!("Try adding '\"' here like this: {}", quoted.green()),
+                    labels: Vec::new(),
+                });
+                return Token::new(
+                    TokenKind::Illegal,
+                    "".to_owned(),
+                    Span::from(start..self.position),
+                );
             }
+            self.read();
         }
+        let text = self.input[start + 1..self.position].to_owned();
+        let kind = if self.ch == '{' {
+            self.interpolations.push(0);
+            interpolation
+        } else {
+            end
+        };
+        let span = Span::from(start..self.position + 1);
         self.read();
-        return token;
+        let literal =
+            String::from_utf8(self.unescape(text.as_bytes(), start + 1)).unwrap_or("".to_owned());
+        Token::new(kind, literal, span)
+    }
+    /// Whether the lexer is at the `r` of a raw string like `r"..."` or `r#"..."#`
+    fn raw_string_ahead(&self) -> bool {
+        let rest = &self.input.as_bytes()[self.read_position..];
+        let hashes = rest.iter().take_while(|ch| **ch == b'#').count();
+        rest.get(hashes) == Some(&b'"')
+    }
+    /// Lexes a raw or triple quoted string starting at its first `"`, which ends
+    /// at the same quotes followed by `hashes` hashes. Raw strings don't process escapes,
+    /// and triple quoted strings have their common indentation stripped.
+    /// Neither of them are interpolated.
+    fn literal(&mut self, start: usize, hashes: usize, raw: bool) -> Token {
+        let quotes = if self.input[self.position..].starts_with("\"\"\"") {
+            3
+        } else {
+            1
+        };
+        let delimiter = "\"".repeat(quotes) + &"#".repeat(hashes);
+        for _ in 0..quotes {
+            self.read();
+        }
+        let text_start = self.position;
+        while !self.input[self.position..].starts_with(&delimiter) {
+            if self.ch == '\0' && self.position >= self.input.len() {
+                self.errors.push(Error {
+                    source: self.input.clone(),
+                    file_name: self.file_name.clone(),
+                    message: "Unterminated String".to_string(),
+                    span: Span::from(self.position..self.position + 1),
+                    help: format!("Try adding '{}' here", delimiter.green().bold()),
+                    labels: Vec::new(),
+                });
+                return Token::new(
+                    TokenKind::Illegal,
+                    "".to_owned(),
+                    Span::from(start..self.position),
+                );
+            }
+            if self.ch == '\\' && !raw && self.peek_next().is_some() {
+                self.read();
+            }
+            self.read();
+        }
+        let text = self.input.as_bytes()[text_start..self.position].to_vec();
+        for _ in 0..delimiter.len() {
+            self.read();
+        }
+        let lines = if quotes == 3 {
+            string_spliter::dedent(&text)
+        } else {
+            std::iter::once(0..text.len()).collect()
+        };
+        let mut buf: Vec<u8> = Vec::new();
+        for (i, line) in lines.into_iter().enumerate() {
+            if i > 0 {
+                buf.push(b'\n');
+            }
+            if raw {
+                buf.extend(&text[line]);
+            } else {
+                let offset = text_start + line.start;
+                buf.extend(self.unescape(&text[line], offset));
+            }
+        }
+        Token::new(
+            TokenKind::String,
+            String::from_utf8(buf).unwrap_or("".to_owned()),
+            Span::from(start..self.position),
+        )
+    }
+    /// Decodes the escapes of a string's text starting at `offset`, reporting the invalid ones
+    fn unescape(&mut self, text: &[u8], offset: usize) -> Vec<u8> {
+        let (text, errors) = string_spliter::split_string(text, offset);
+        for error in errors {
+            self.errors.push(Error {
+                source: self.input.clone(),
+                file_name: self.file_name.clone(),
+                message: error.message,
+                span: error.span,
+                help: error.help,
+                labels: Vec::new(),
+            });
+        }
+        text
     }
-    fn next_match(&mut self, expected: u8) -> bool {
+    fn next_match(&mut self, expected: char) -> bool {
         if self.peek_next() == Some(expected) {
             self.read();
             return true;
@@ -322,6 +533,17 @@ impl Lexer {
             "enum" => TokenKind::Enum,
             "end" => TokenKind::End,
             "do" => TokenKind::Do,
+            "return" => TokenKind::Return,
+            "interface" => TokenKind::Interface,
+            "type" => TokenKind::TypeKw,
+            "newtype" => TokenKind::Newtype,
+            "extern" => TokenKind::Extern,
+            "impl" => TokenKind::Impl,
+            "for" => TokenKind::For,
+            "in" => TokenKind::In,
+            "let" => TokenKind::Let,
+            "none" => TokenKind::NoneKw,
+            "vec" => TokenKind::VecTy,
             "true" => TokenKind::Bool(true),
             "false" => TokenKind::Bool(false),
             _ => TokenKind::Identifier,
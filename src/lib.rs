@@ -0,0 +1,20 @@
+//! Illusio, a compiled language. [`Engine`] compiles and runs code from a string,
+//! stage by stage or all at once, and lets it call functions of the program embedding it.
+pub mod ast;
+pub mod engine;
+pub mod error;
+mod lexer;
+mod parser;
+pub mod token;
+mod traits;
+mod typechecker;
+mod string_spliter;
+mod number;
+mod analyzer;
+mod escape;
+mod compiler;
+mod lower;
+pub use engine::{
+    Analysis, Checked, Compiled, Engine, EngineError, FromIllusio, HostFunction, IntoIllusio,
+    Lowered,
+};
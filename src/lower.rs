@@ -1,61 +1,594 @@
+use std::collections::{HashMap, HashSet};
+
 use crate::ast::*;
 use crate::compiler::builder::Builder;
+use crate::compiler::compiler::{JitError, Program};
 use crate::compiler::ir::{self, IrType};
-use crate::token::TokenKind;
-use crate::typechecker::TypeChecker;
+use crate::compiler::runtime::Host;
+use crate::error::{bug, Error};
+use crate::token::{Span, TokenKind};
+use crate::typechecker::{loop_types, substitute, type_map, FuncSig, TypeChecker};
 pub struct Lower<'a> {
     ast: &'a Vec<Expr>,
     builder: Builder,
     checker: TypeChecker<'a>,
+    /// Instances of generic functions that still need to be translated,
+    /// each one is a function name with the types of its type parameters.
+    pending: Vec<(String, Vec<Type>)>,
+    instances: HashSet<String>,
+    /// Number of `for` loops and `if let`s translated, to give their hidden variables unique names
+    hidden: usize,
+    /// Variables of the current function that have their address taken,
+    /// they live in stack slots instead of registers
+    addressed: HashSet<String>,
+    /// The variables every closure captures, by the span of the closure
+    captures: HashMap<Span, Vec<String>>,
+    /// Functions used as values, which have a closure calling them
+    function_values: HashSet<String>,
+    /// The type of the value the program gives with its last expression, if it gives one
+    result: Option<Type>,
+    /// The expression being translated, a bug in the translation is reported there
+    span: Span,
 }
 impl<'a> Lower<'a> {
-    pub fn new(ast: &'a Vec<Expr>, checker: TypeChecker<'a>) -> Self {
+    pub fn new(
+        ast: &'a Vec<Expr>,
+        checker: TypeChecker<'a>,
+        captures: HashMap<Span, Vec<String>>,
+    ) -> Self {
         Self {
             ast,
             builder: Builder::new(),
             checker,
+            pending: Vec::new(),
+            instances: HashSet::new(),
+            hidden: 0,
+            addressed: HashSet::new(),
+            captures,
+            function_values: HashSet::new(),
+            result: None,
+            span: Span::from(0..0),
         }
     }
-    pub fn finish(&self) {
-        self.builder.finish()
+    /// Compiles the translated program, host functions are called at the given addresses
+    pub fn compile(&self, hosts: HashMap<String, *const Host>) -> Result<Program, JitError> {
+        self.builder.compile(hosts)
+    }
+    /// The Cranelift IR of the translated program
+    pub fn emit(&self, hosts: HashMap<String, *const Host>) -> Result<String, JitError> {
+        self.builder.emit(hosts)
+    }
+    pub fn result_type(&self) -> Option<&Type> {
+        self.result.as_ref()
+    }
+    /// Uses of what can't be translated yet
+    pub fn errors(&self) -> &[Error] {
+        self.checker.errors()
+    }
+    pub fn span(&self) -> Span {
+        self.span
     }
     pub fn translate(&mut self) {
         for stmt in self.ast {
-            let stmt =self.translate_stmt(stmt.clone());
-            self.builder.stmt(stmt);
+            match &stmt.inner {
+                ExprKind::FunctionDeclaration(..) | ExprKind::Impl(..) => {}
+                _ => addressed(stmt, &mut self.addressed),
+            }
+        }
+        for stmt in self.ast {
+            match &stmt.inner {
+                ExprKind::FunctionDeclaration(name, _, params, _, body) => {
+                    let sig = self.checker.sig(name).unwrap().clone();
+                    // Generic functions are translated once for every instance of them
+                    if sig.type_params.is_empty() {
+                        self.function(name, params, &sig, &[], body, stmt.span);
+                    }
+                }
+                ExprKind::Impl(_, ty, methods) => {
+                    let ty = &self.checker.resolve(ty);
+                    for method in methods {
+                        if let ExprKind::FunctionDeclaration(name, _, params, _, body) =
+                            &method.inner
+                        {
+                            let sig = self.checker.method(ty, name).unwrap();
+                            self.function(
+                                &method_name(ty, name),
+                                params,
+                                &sig,
+                                &[],
+                                body,
+                                method.span,
+                            );
+                        }
+                    }
+                }
+                ExprKind::Extern(library, sig, variadic) => {
+                    let func_sig = self.checker.sig(&sig.name).unwrap().clone();
+                    let params = func_sig
+                        .params_type
+                        .into_iter()
+                        .map(|ty| self.to_ir_type(ty))
+                        .collect();
+                    let return_type = self.to_ir_return_type(func_sig.return_type);
+                    self.builder.extern_function(ir::Extern {
+                        name: sig.name.clone(),
+                        library: library.clone(),
+                        params,
+                        return_type,
+                        variadic: *variadic,
+                    });
+                }
+                ExprKind::Interface(..)
+                | ExprKind::Enum(..)
+                | ExprKind::TypeAlias(..)
+                | ExprKind::Newtype(..) => {}
+                // The last expression gives the value of the program, when the host can take it
+                _ if std::ptr::eq(stmt, self.ast.last().unwrap()) && self.gives_value(stmt) => {
+                    let ty = self.checker.handle(stmt);
+                    let ir_ty = self.to_ir_type(ty.clone());
+                    let value = self.translate_expr(stmt.clone());
+                    self.builder.result(value, ir_ty, stmt.span);
+                    self.result = Some(ty);
+                }
+                _ => {
+                    let span = stmt.span;
+                    let stmt = self.translate_stmt(stmt.clone());
+                    self.builder.stmt(stmt, span);
+                }
+            }
+        }
+        while let Some((name, type_args)) = self.pending.pop() {
+            self.instance(&name, &type_args);
+        }
+    }
+    /// Whether `expr` gives a value the host can take
+    fn gives_value(&mut self, expr: &Expr) -> bool {
+        if matches!(expr.inner, ExprKind::Var(..) | ExprKind::Destructure(..)) {
+            return false;
+        }
+        let ty = self.checker.handle(expr);
+        matches!(
+            self.checker.underlying(&self.checker.resolve(&ty)),
+            Type::Int | Type::Float | Type::Bool | Type::String
+        )
+    }
+    /// Translates an instance of a generic function, with concrete types for its type parameters.
+    fn instance(&mut self, name: &str, type_args: &[Type]) {
+        let ast = self.ast;
+        let (params, body, span) = ast
+            .iter()
+            .find_map(|stmt| match &stmt.inner {
+                ExprKind::FunctionDeclaration(n, _, params, _, body) if n == name => {
+                    Some((params, body, stmt.span))
+                }
+                _ => None,
+            })
+            .unwrap();
+        let sig = self.checker.sig(name).unwrap().clone();
+        self.function(
+            &instance_name(name, type_args),
+            params,
+            &sig,
+            type_args,
+            body,
+            span,
+        );
+    }
+    fn function(
+        &mut self,
+        name: &str,
+        params: &[Param],
+        sig: &FuncSig,
+        type_args: &[Type],
+        body: &[Expr],
+        span: Span,
+    ) {
+        self.span = span;
+        let frame = self.checker.enter_function(params, sig, type_args);
+        let (ir_params, return_type, stmts) = self.body(params, sig, body);
+        self.checker.leave_function(frame);
+        self.builder
+            .function(name, ir_params, return_type, stmts, span);
+    }
+    /// Translates an anonymous function to a function taking the closure,
+    /// and gives the closure
+    fn closure(
+        &mut self,
+        params: &[Param],
+        return_type: &Type,
+        body: &[Expr],
+        span: Span,
+    ) -> ir::Expr {
+        let name = format!("closure.{}", self.hidden);
+        self.hidden += 1;
+        let captured: Vec<(String, IrType)> = self.captures[&span]
+            .iter()
+            .map(|name| {
+                let ty = self.checker.get(name).unwrap();
+                (name.clone(), self.to_ir_type(ty))
+            })
+            .collect();
+        let values = captured
+            .iter()
+            .map(|(name, ty)| (self.builder.get_var(name), ty.clone()))
+            .collect();
+        let sig = FuncSig {
+            type_params: Vec::new(),
+            return_type: self.checker.resolve(return_type),
+            params_type: params
+                .iter()
+                .map(|param| self.checker.resolve(&param.param_type))
+                .collect(),
+        };
+        let frame = self.checker.enter_closure(params, &sig);
+        let (ir_params, ir_return_type, stmts) = self.body(params, &sig, body);
+        self.checker.leave_function(frame);
+        self.builder
+            .closure_function(&name, captured, ir_params, ir_return_type, stmts, span);
+        self.builder.closure(&name, values)
+    }
+    /// Gives a closure calling the function `name`, which is translated the first time.
+    /// A panic of a host function called by it is reported at `span`, where it was first used.
+    fn function_value(&mut self, name: &str, span: Span) -> ir::Expr {
+        let closure_name = format!("{}.value", name);
+        if self.function_values.insert(name.to_owned()) {
+            let sig = self.checker.sig(name).unwrap().clone();
+            let params: Vec<(String, IrType)> = sig
+                .params_type
+                .iter()
+                .enumerate()
+                .map(|(i, ty)| (format!("arg.{}", i), self.to_ir_type(ty.clone())))
+                .collect();
+            let args: Vec<ir::Expr> = params
+                .iter()
+                .map(|(param, _)| self.builder.get_var(param))
+                .collect();
+            let return_type = self.to_ir_return_type(sig.return_type);
+            let typed_args = || {
+                args.clone()
+                    .into_iter()
+                    .zip(params.iter().map(|(_, ty)| ty.clone()))
+                    .collect()
+            };
+            let call = if self.checker.is_host(name) {
+                self.builder
+                    .call_host(name, typed_args(), return_type.clone(), span)
+            } else if self.checker.is_extern(name) {
+                self.builder
+                    .call_extern(name, typed_args(), return_type.clone())
+            } else {
+                self.builder.call(name, args, return_type.clone())
+            };
+            let body = match return_type {
+                Some(_) => self.builder.return_(Some(call)),
+                None => self.builder.expr(call),
+            };
+            self.builder.closure_function(
+                &closure_name,
+                vec![],
+                params,
+                return_type,
+                vec![body],
+                span,
+            );
         }
+        self.builder.closure(&closure_name, vec![])
+    }
+    /// Translates the body of a function, the checker should already be in it
+    fn body(
+        &mut self,
+        params: &[Param],
+        sig: &FuncSig,
+        body: &[Expr],
+    ) -> (Vec<(String, IrType)>, Option<IrType>, Vec<ir::Stmt>) {
+        let ir_params = params
+            .iter()
+            .zip(&sig.params_type)
+            .map(|(param, ty)| (param.name.clone(), self.to_ir_type(ty.clone())))
+            .collect();
+        let return_type = self.to_ir_return_type(sig.return_type.clone());
+        let mut inner = HashSet::new();
+        for stmt in body {
+            addressed(stmt, &mut inner);
+        }
+        let outer = std::mem::replace(&mut self.addressed, inner);
+        let mut stmts = Vec::new();
+        // Parameters that have their address taken are moved to stack slots
+        for (param, ty) in params.iter().zip(&sig.params_type) {
+            if self.addressed.contains(&param.name) {
+                let value = self.builder.get_var(&param.name);
+                let ty = self.to_ir_type(ty.clone());
+                stmts.push(self.builder.stack_var(&param.name, value, ty));
+            }
+        }
+        for stmt in body {
+            let stmt = self.translate_stmt(stmt.clone());
+            stmts.push(stmt);
+        }
+        self.addressed = outer;
+        (ir_params, return_type, stmts)
     }
     pub fn translate_stmt(&mut self, stmt: Expr) -> ir::Stmt {
+        self.span = stmt.span;
         match stmt.inner {
-            ExprKind::Var(name, value, ty) => {
+            ExprKind::Var(ref name, ref value, ref ty) => {
+                let ir_ty = self.to_ir_type(ty.clone());
+                let value = self.translate_expecting(*value.clone(), ty);
+                // Declaring the variable for the type checker
+                self.checker.handle(&stmt);
+                self.new_var(name, value, ir_ty)
+            }
+            ExprKind::Destructure(ref names, ref value) => {
+                let ty = self.checker.handle(value);
+                let fields = match self.checker.resolve(&ty) {
+                    Type::Tuple(fields) => fields,
+                    _ => unreachable!(),
+                };
                 let ir_ty = self.to_ir_type(ty);
-                let value = self.translate_expr(*value);
-                self.builder.new_var(&name, value, ir_ty)
+                let value = self.translate_expr(*value.clone());
+                // Declaring the variables for the type checker
+                self.checker.handle(&stmt);
+                // Elements that have their address taken are moved to stack slots
+                let mut stack_vars = Vec::new();
+                let names = names
+                    .iter()
+                    .zip(fields)
+                    .map(|(name, field)| {
+                        if !self.addressed.contains(name) {
+                            return name.clone();
+                        }
+                        let hidden = format!("destructure.{}.{}", self.hidden, name);
+                        self.hidden += 1;
+                        let field = self.to_ir_type(field);
+                        let value = self.builder.get_var(&hidden);
+                        stack_vars.push(self.builder.stack_var(name, value, field));
+                        hidden
+                    })
+                    .collect();
+                let destructure = self.builder.destructure(names, value, ir_ty);
+                if stack_vars.is_empty() {
+                    return destructure;
+                }
+                stack_vars.insert(0, destructure);
+                self.builder.block(stack_vars)
             }
-            ExprKind::If(if_,then) => {
+            ExprKind::If(if_, then) => {
                 let condition = self.translate_expr(*if_);
                 let mut block = Vec::new();
+                self.checker.start_scope();
                 for s in then {
                     let s = self.translate_stmt(s);
                     block.push(s);
                 }
+                self.checker.end_scope();
                 self.builder.if_(condition, block, vec![])
             }
+            ExprKind::IfLet(pattern, value, then) => {
+                // The value is kept in a hidden variable, so it is only evaluated once
+                let ty = self.checker.handle(&value);
+                let value_name = format!("if_let.{}.value", self.hidden);
+                self.hidden += 1;
+                let ir_ty = self.to_ir_type(ty.clone());
+                let value = self.translate_expr(*value);
+                let matched = self.builder.get_var(&value_name);
+                let (condition, inner, unwrapped) = match (&pattern, self.checker.resolve(&ty)) {
+                    (Pattern::Value(_), Type::Optional(inner)) => (
+                        self.builder.binary(
+                            matched.clone(),
+                            ir::BinOp::Neq,
+                            self.builder.null(),
+                            ir_ty.clone(),
+                        ),
+                        *inner,
+                        self.builder.unwrap(matched, ir_ty.clone()),
+                    ),
+                    (Pattern::Ok(_), Type::Result(inner, _)) => (
+                        self.builder.unary(
+                            ir::UnOp::Not,
+                            self.builder.is_err(matched.clone()),
+                            IrType::Bool,
+                        ),
+                        *inner,
+                        self.builder.result_value(matched, false, ir_ty.clone()),
+                    ),
+                    (Pattern::Err(_), Type::Result(_, inner)) => (
+                        self.builder.is_err(matched.clone()),
+                        *inner,
+                        self.builder.result_value(matched, true, ir_ty.clone()),
+                    ),
+                    _ => unreachable!(),
+                };
+                let name = pattern.name();
+                let inner_ir_ty = self.to_ir_type(inner.clone());
+                let mut block = vec![self.new_var(name, unwrapped, inner_ir_ty)];
+                self.checker.start_scope();
+                self.checker.declare(name, inner);
+                for s in then {
+                    let s = self.translate_stmt(s);
+                    block.push(s);
+                }
+                self.checker.end_scope();
+                let stmts = vec![
+                    self.builder.new_var(&value_name, value, ir_ty),
+                    self.builder.if_(condition, block, vec![]),
+                ];
+                self.builder.block(stmts)
+            }
+            ExprKind::Block(exprs) => {
+                let mut block = Vec::new();
+                self.checker.start_scope();
+                for s in exprs {
+                    let s = self.translate_stmt(s);
+                    block.push(s);
+                }
+                self.checker.end_scope();
+                self.builder.scope(block)
+            }
+            ExprKind::Return(value) => {
+                let return_type = self.checker.return_type();
+                let value = value.map(|value| self.translate_expecting(*value, &return_type));
+                self.builder.return_(value)
+            }
+            ExprKind::For(name, second, iterable, body) => {
+                // Looping with a hidden index, the iterable is kept in
+                // a hidden variable so it is only evaluated once
+                let ty = self.checker.handle(&iterable);
+                let ty = self.checker.resolve(&ty);
+                let (first_ty, second_ty) = loop_types(&ty, second.is_some()).unwrap();
+                let ir_ty = self.to_ir_type(ty.clone());
+                let iterable_name = format!("for.{}.iterable", self.hidden);
+                let index_name = format!("for.{}.index", self.hidden);
+                self.hidden += 1;
+
+                let iterable = self.translate_expr(*iterable);
+                let zero = self.builder.int(0);
+                let mut stmts = vec![
+                    self.builder
+                        .new_var(&iterable_name, iterable, ir_ty.clone()),
+                    self.builder.new_var(&index_name, zero, IrType::Int),
+                ];
+                let condition = self.builder.binary(
+                    self.builder.get_var(&index_name),
+                    ir::BinOp::Lt,
+                    self.builder.len(self.builder.get_var(&iterable_name)),
+                    IrType::Int,
+                );
+                let iterable = self.builder.get_var(&iterable_name);
+                let index = self.builder.get_var(&index_name);
+                // The values of the loop variables at the current index
+                let (first, second_value) = match ty {
+                    Type::Map(..) => (
+                        self.builder.map_key(
+                            iterable.clone(),
+                            index.clone(),
+                            ir_ty.clone(),
+                            stmt.span,
+                        ),
+                        self.builder.map_value(iterable, index, ir_ty, stmt.span),
+                    ),
+                    _ => {
+                        let element = self
+                            .builder
+                            .index(iterable, index.clone(), ir_ty, stmt.span);
+                        match second {
+                            Some(_) => (index, element),
+                            None => (element, self.builder.int(0)),
+                        }
+                    }
+                };
+                let first_ir_ty = self.to_ir_type(first_ty.clone());
+                let mut block = vec![self.new_var(&name, first, first_ir_ty)];
+                self.checker.start_scope();
+                self.checker.declare(&name, first_ty);
+                if let Some(second) = &second {
+                    let second_ir_ty = self.to_ir_type(second_ty.clone());
+                    block.push(self.new_var(second, second_value, second_ir_ty));
+                    self.checker.declare(second, second_ty);
+                }
+                for s in body {
+                    let s = self.translate_stmt(s);
+                    block.push(s);
+                }
+                self.checker.end_scope();
+                let next = self.builder.binary(
+                    self.builder.get_var(&index_name),
+                    ir::BinOp::Add,
+                    self.builder.int(1),
+                    IrType::Int,
+                );
+                block.push(self.builder.set_var(&index_name, next));
+                stmts.push(self.builder.while_(condition, block));
+                self.builder.block(stmts)
+            }
+            ExprKind::Assign(target, value) => match target.inner {
+                ExprKind::Ident(ref name) => {
+                    let ty = self.checker.handle(&target);
+                    let value = self.translate_expecting(*value, &ty);
+                    self.builder.set_var(name, value)
+                }
+                ExprKind::Index(array, index) => {
+                    let ty = self.checker.handle(&array);
+                    let element = match self.checker.resolve(&ty) {
+                        Type::Array(element, _) | Type::Vec(element) => *element,
+                        _ => unreachable!(),
+                    };
+                    let ty = self.to_ir_type(ty);
+                    let array = self.translate_expr(*array);
+                    let index = self.translate_expr(*index);
+                    let value = self.translate_expecting(*value, &element);
+                    self.builder.set_index(array, index, value, ty, target.span)
+                }
+                ExprKind::Deref(pointer) => {
+                    let ty = self.checker.handle(&pointer);
+                    let pointee = match self.checker.resolve(&ty) {
+                        Type::Ptr(pointee) => *pointee,
+                        _ => unreachable!(),
+                    };
+                    let ty = self.to_ir_type(ty);
+                    let pointer = self.translate_expr(*pointer);
+                    let value = self.translate_expecting(*value, &pointee);
+                    self.builder.set_deref(pointer, value, ty)
+                }
+                _ => unreachable!(),
+            },
             _ => {
                 let expr = self.translate_expr(stmt);
-                let stmt = self.builder.expr(expr);
-                stmt
+                self.builder.expr(expr)
             }
         }
     }
     pub fn translate_expr(&mut self, expr: Expr) -> ir::Expr {
+        self.span = expr.span;
         match expr.inner {
             ExprKind::Float(f) => self.builder.float(f),
+            ExprKind::Ident(ident) if self.checker.get(&ident).is_none() => {
+                self.function_value(&ident, expr.span)
+            }
             ExprKind::Ident(ident) => self.builder.get_var(&ident),
+            ExprKind::Closure(params, return_type, body) => {
+                self.closure(&params, &return_type, &body, expr.span)
+            }
             ExprKind::Str(s) => self.builder.string(&s),
-            ExprKind::Int(i) => self.builder.int(i) ,
-            ExprKind::Binary(left, op, right) => {
+            ExprKind::Interpolation(parts) => {
+                let parts: Vec<_> = parts
+                    .into_iter()
+                    .map(|part| {
+                        let ty = self.checker.handle(&part);
+                        let span = part.span;
+                        let value = self.translate_expr(part);
+                        self.to_str(value, ty, span)
+                    })
+                    .collect();
+                // The parser only keeps non-empty text, so there is always an expression
+                parts
+                    .into_iter()
+                    .reduce(|string, part| {
+                        self.builder
+                            .binary(string, ir::BinOp::Add, part, IrType::Str)
+                    })
+                    .unwrap()
+            }
+            ExprKind::Int(i) => self.builder.int(i),
+            ExprKind::Binary(left, op @ (TokenKind::EqualTo | TokenKind::NotEqual), right)
+                if matches!(left.inner, ExprKind::None)
+                    || matches!(right.inner, ExprKind::None) =>
+            {
+                let value = if matches!(left.inner, ExprKind::None) {
+                    right
+                } else {
+                    left
+                };
+                let ty = self.checker.handle(&value);
+                let ty = self.to_ir_type(ty);
+                let value = self.translate_expr(*value);
                 let op = match op {
+                    TokenKind::EqualTo => ir::BinOp::Eq,
+                    _ => ir::BinOp::Neq,
+                };
+                self.builder.binary(value, op, self.builder.null(), ty)
+            }
+            ExprKind::Binary(left, kind, right) => {
+                let op = match kind {
                     TokenKind::Plus => ir::BinOp::Add,
                     TokenKind::Minus => ir::BinOp::Sub,
                     TokenKind::Asterisk => ir::BinOp::Mul,
@@ -66,29 +599,439 @@ impl<'a> Lower<'a> {
                     TokenKind::LessThan => ir::BinOp::Lt,
                     TokenKind::GreaterThanEqual => ir::BinOp::Ge,
                     TokenKind::LessThanEqual => ir::BinOp::Le,
-                    _ => todo!(),
+                    TokenKind::Ampersand => ir::BinOp::BitAnd,
+                    TokenKind::Pipe => ir::BinOp::BitOr,
+                    TokenKind::Caret => ir::BinOp::BitXor,
+                    TokenKind::ShiftLeft => ir::BinOp::Shl,
+                    TokenKind::ShiftRight => ir::BinOp::Shr,
+                    TokenKind::UnsignedShiftRight => ir::BinOp::UShr,
+                    TokenKind::And => {
+                        let left = self.translate_expr(*left);
+                        let right = self.translate_expr(*right);
+                        return self.builder.and(left, right);
+                    }
+                    TokenKind::Or => {
+                        let left = self.translate_expr(*left);
+                        let right = self.translate_expr(*right);
+                        return self.builder.or(left, right);
+                    }
+                    // Only integers support `%`, it is lowered like a division
+                    TokenKind::Mod => ir::BinOp::Div,
+                    op => bug(format!("Operator `{}` passed type checking", op)),
                 };
                 let ty = self.checker.handle(&left);
+                let int = matches!(self.checker.underlying(&ty), Type::Int);
+                let remainder = matches!(kind, TokenKind::Mod);
                 let left = self.translate_expr(*left);
                 let right = self.translate_expr(*right);
+                if int && matches!(op, ir::BinOp::Div) {
+                    return self.builder.int_div(left, right, remainder, expr.span);
+                }
                 let ir_type = self.to_ir_type(ty);
                 self.builder.binary(left, op, right, ir_type)
             }
+            ExprKind::Unary(TokenKind::Plus, value) => self.translate_expr(*value),
+            ExprKind::Unary(op, value) => {
+                let op = match op {
+                    TokenKind::Minus => ir::UnOp::Neg,
+                    TokenKind::Not => ir::UnOp::Not,
+                    TokenKind::Tilde => ir::UnOp::BitNot,
+                    _ => unreachable!(),
+                };
+                let ty = self.checker.handle(&value);
+                let value = self.translate_expr(*value);
+                let ir_type = self.to_ir_type(ty);
+                self.builder.unary(op, value, ir_type)
+            }
             ExprKind::Bool(b) => self.builder.boolean(b),
-            ExprKind::Puts(s) =>{
-                let s = self.translate_expr(s.get(0).unwrap().clone());
-                self.builder.str_print(s)
+            ExprKind::Puts(s) => {
+                let arg = s.first().unwrap().clone();
+                let ty = self.checker.handle(&arg);
+                let arg = self.translate_expr(arg);
+                match self.checker.underlying(&ty) {
+                    Type::Int => self.builder.int_print(arg),
+                    Type::Float => self.builder.float_print(arg),
+                    Type::Bool => {
+                        let arg = self.to_str(arg, Type::Bool, expr.span);
+                        self.builder.str_print(arg)
+                    }
+                    _ => self.builder.str_print(arg),
+                }
+            }
+            ExprKind::FunctionCall(name, args)
+                if matches!(self.checker.get(&name), Some(Type::Function(..))) =>
+            {
+                let ty = self.checker.get(&name).unwrap();
+                let params_type = match self.checker.resolve(&ty) {
+                    Type::Function(params_type, _) => params_type,
+                    _ => unreachable!(),
+                };
+                let args = args
+                    .into_iter()
+                    .zip(&params_type)
+                    .map(|(arg, param)| self.translate_expecting(arg, param))
+                    .collect();
+                let closure = self.builder.get_var(&name);
+                let ty = self.to_ir_type(ty);
+                self.builder.call_indirect(closure, args, ty)
+            }
+            // A newtype has the same representation as its underlying type
+            ExprKind::FunctionCall(name, mut args)
+                if self.checker.is_newtype(&Type::Named(name.clone()))
+                    && self.checker.sig(&name).is_none() =>
+            {
+                let underlying = self.checker.underlying(&Type::Named(name));
+                self.translate_expecting(args.remove(0), &underlying)
+            }
+            ExprKind::FunctionCall(name, mut args)
+                if name == "len" && self.checker.sig(&name).is_none() =>
+            {
+                let array = self.translate_expr(args.remove(0));
+                self.builder.len(array)
+            }
+            ExprKind::FunctionCall(name, args) => {
+                let sig = self.checker.sig(&name).unwrap().clone();
+                // Named arguments are put in order, and missing ones get their default values
+                let args = self.checker.arguments(&name, &args, expr.span).unwrap();
+                if self.checker.is_host(&name) {
+                    let args = args
+                        .into_iter()
+                        .zip(&sig.params_type)
+                        .map(|(arg, param)| {
+                            let arg = self.translate_expecting(arg, param);
+                            (arg, self.to_ir_type(param.clone()))
+                        })
+                        .collect();
+                    let return_type = self.to_ir_return_type(sig.return_type);
+                    return self.builder.call_host(&name, args, return_type, expr.span);
+                }
+                if self.checker.is_extern(&name) {
+                    // The arguments after the parameters of variadic C functions keep their type
+                    let args = args
+                        .into_iter()
+                        .enumerate()
+                        .map(|(i, arg)| {
+                            let ty = match sig.params_type.get(i) {
+                                Some(param) => param.clone(),
+                                None => self.checker.handle(&arg),
+                            };
+                            let arg = self.translate_expecting(arg, &ty);
+                            (arg, self.to_ir_type(ty))
+                        })
+                        .collect();
+                    let return_type = self.to_ir_return_type(sig.return_type);
+                    return self.builder.call_extern(&name, args, return_type);
+                }
+                let (name, params_type, return_type) = if sig.type_params.is_empty() {
+                    (name, sig.params_type, sig.return_type)
+                } else {
+                    let type_args = self.checker.infer(&name, &sig, &args, expr.span).unwrap();
+                    let instance = instance_name(&name, &type_args);
+                    if self.instances.insert(instance.clone()) {
+                        self.pending.push((name, type_args.clone()));
+                    }
+                    let map = type_map(&sig.type_params, &type_args);
+                    let params_type = sig
+                        .params_type
+                        .iter()
+                        .map(|param| substitute(param, &map))
+                        .collect();
+                    (instance, params_type, substitute(&sig.return_type, &map))
+                };
+                let args = args
+                    .into_iter()
+                    .zip(&params_type)
+                    .map(|(arg, param)| self.translate_expecting(arg, param))
+                    .collect();
+                let return_type = self.to_ir_return_type(return_type);
+                self.builder.call(&name, args, return_type)
+            }
+            ExprKind::MethodCall(receiver, name, args) => {
+                let ty = self.checker.handle(&receiver);
+                let sig = self.checker.method(&ty, &name).unwrap();
+                let receiver = self.translate_expr(*receiver);
+                let mut ir_args = Vec::new();
+                // The first parameter is `self`, which is the receiver
+                for (arg, param) in args.into_iter().zip(sig.params_type.iter().skip(1)) {
+                    ir_args.push(self.translate_expecting(arg, param));
+                }
+                match self.checker.resolve(&ty) {
+                    Type::Vec(element) => {
+                        let element = self.to_ir_type(*element);
+                        match name.as_str() {
+                            "push" => self.builder.push(receiver, ir_args.remove(0), element),
+                            "pop" => self.builder.pop(receiver, element, expr.span),
+                            _ => unreachable!(),
+                        }
+                    }
+                    map_ty @ Type::Map(..) => {
+                        let map_ty = self.to_ir_type(map_ty);
+                        let key = ir_args.remove(0);
+                        match name.as_str() {
+                            "get" => self.builder.map_get(receiver, key, map_ty, expr.span),
+                            "set" => self
+                                .builder
+                                .map_set(receiver, key, ir_args.remove(0), map_ty),
+                            "remove" => self.builder.map_remove(receiver, key, map_ty),
+                            "contains" => self.builder.map_contains(receiver, key, map_ty),
+                            _ => unreachable!(),
+                        }
+                    }
+                    _ => {
+                        ir_args.insert(0, receiver);
+                        let return_type = self.to_ir_return_type(sig.return_type);
+                        self.builder
+                            .call(&method_name(&ty, &name), ir_args, return_type)
+                    }
+                }
+            }
+            ExprKind::Array(elements) => {
+                let ty = self.checker.handle(&elements[0]);
+                let elements = elements
+                    .into_iter()
+                    .map(|element| self.translate_expr(element))
+                    .collect();
+                let ty = self.to_ir_type(ty);
+                self.builder.array(elements, ty)
+            }
+            ExprKind::Tuple(elements) => {
+                let ty = Type::Tuple(elements.iter().map(|e| self.checker.handle(e)).collect());
+                let elements = elements
+                    .into_iter()
+                    .map(|element| self.translate_expr(element))
+                    .collect();
+                let ty = self.to_ir_type(ty);
+                self.builder.tuple(elements, ty)
+            }
+            ExprKind::TupleIndex(tuple, index) => {
+                let ty = self.checker.handle(&tuple);
+                let ty = self.to_ir_type(ty);
+                let tuple = self.translate_expr(*tuple);
+                self.builder.tuple_field(tuple, index, ty)
+            }
+            ExprKind::Vector(elements) => {
+                let ty = self.checker.handle(&elements[0]);
+                let ty = self.to_ir_type(ty);
+                let elements = elements
+                    .into_iter()
+                    .map(|element| self.translate_expr(element))
+                    .collect();
+                self.builder.vector(elements, ty)
+            }
+            ExprKind::Index(array, index) => {
+                let ty = self.checker.handle(&array);
+                let ty = self.to_ir_type(ty);
+                let array = self.translate_expr(*array);
+                let index = self.translate_expr(*index);
+                self.builder.index(array, index, ty, expr.span)
+            }
+            ExprKind::Slice(s, start, end) => {
+                let s = self.translate_expr(*s);
+                let start = start.map(|start| self.translate_expr(*start));
+                let end = end.map(|end| self.translate_expr(*end));
+                self.builder.slice(s, start, end, expr.span)
+            }
+            ExprKind::Convert(ty, value) => {
+                let from = self.checker.handle(&value);
+                let from = self.to_ir_type(from);
+                let value = self.translate_expr(*value);
+                let to = self.to_ir_type(ty);
+                self.builder.convert(value, from, to, expr.span)
+            }
+            ExprKind::Map(entries) => {
+                let key = self.checker.handle(&entries[0].0);
+                let value = self.checker.handle(&entries[0].1);
+                let ty = Type::Map(Box::new(key), Box::new(value));
+                let entries = entries
+                    .into_iter()
+                    .map(|(key, value)| (self.translate_expr(key), self.translate_expr(value)))
+                    .collect();
+                let ty = self.to_ir_type(ty);
+                self.builder.map(entries, ty)
+            }
+            ExprKind::Try(result) => {
+                let ty = self.checker.handle(&result);
+                let ty = self.to_ir_type(ty);
+                let result = self.translate_expr(*result);
+                self.builder.try_(result, ty)
+            }
+            ExprKind::Ref(value) => match value.inner {
+                ExprKind::Ident(name) => self.builder.address_of(&name),
+                _ => unreachable!(),
+            },
+            ExprKind::Deref(pointer) => {
+                let ty = self.checker.handle(&pointer);
+                let ty = self.to_ir_type(ty);
+                let pointer = self.translate_expr(*pointer);
+                self.builder.deref(pointer, ty)
             }
-            _ => todo!(),
+            // Statements and declarations are translated before getting here
+            _ => bug("Expected an expression".to_owned()),
         }
     }
-    fn to_ir_type(&self, ty: Type) -> IrType {
+    /// Like `translate_expr`, but `expected` gives the type of an empty `vec()`, `{}` or `none`,
+    /// and the values that are wrapped in an optional
+    fn translate_expecting(&mut self, expr: Expr, expected: &Type) -> ir::Expr {
+        match (&expr.inner, self.checker.resolve(expected)) {
+            (ExprKind::Vector(elements), Type::Vec(element)) => {
+                let elements = elements
+                    .iter()
+                    .map(|e| self.translate_expecting(e.clone(), &element))
+                    .collect();
+                let element = self.to_ir_type(*element);
+                self.builder.vector(elements, element)
+            }
+            (ExprKind::Array(elements), Type::Array(element, size)) if elements.len() == size => {
+                let elements = elements
+                    .iter()
+                    .map(|e| self.translate_expecting(e.clone(), &element))
+                    .collect();
+                let element = self.to_ir_type(*element);
+                self.builder.array(elements, element)
+            }
+            (ExprKind::Map(entries), ty @ Type::Map(..)) => {
+                let (key, value) = match &ty {
+                    Type::Map(key, value) => (key, value),
+                    _ => unreachable!(),
+                };
+                let entries = entries
+                    .iter()
+                    .map(|(k, v)| {
+                        (
+                            self.translate_expecting(k.clone(), key),
+                            self.translate_expecting(v.clone(), value),
+                        )
+                    })
+                    .collect();
+                let ty = self.to_ir_type(ty);
+                self.builder.map(entries, ty)
+            }
+            (ExprKind::Tuple(elements), ty @ Type::Tuple(_)) => {
+                let fields = match &ty {
+                    Type::Tuple(fields) if fields.len() == elements.len() => fields,
+                    _ => return self.translate_expr(expr),
+                };
+                let elements = elements
+                    .iter()
+                    .zip(fields)
+                    .map(|(e, field)| self.translate_expecting(e.clone(), field))
+                    .collect();
+                let ty = self.to_ir_type(ty.clone());
+                self.builder.tuple(elements, ty)
+            }
+            (ExprKind::None, Type::Optional(_)) => self.builder.null(),
+            (ExprKind::FunctionCall(name, args), ty @ Type::Result(..))
+                if (name == "ok" || name == "err") && self.checker.sig(name).is_none() =>
+            {
+                let (value, error) = match &ty {
+                    Type::Result(value, error) => (value, error),
+                    _ => unreachable!(),
+                };
+                let ir_ty = self.to_ir_type(ty.clone());
+                if name == "ok" {
+                    let value = self.translate_expecting(args[0].clone(), value);
+                    self.builder.ok(value, ir_ty)
+                } else {
+                    let error = self.translate_expecting(args[0].clone(), error);
+                    self.builder.err(error, ir_ty)
+                }
+            }
+            (_, Type::Optional(inner))
+                if self.checker.handle(&expr) != Type::Optional(inner.clone()) =>
+            {
+                let ty = self.to_ir_type(Type::Optional(inner.clone()));
+                let value = self.translate_expecting(expr, &inner);
+                self.builder.wrap(value, ty)
+            }
+            _ => self.translate_expr(expr),
+        }
+    }
+    /// Converts a printable value to a string, strings are left as is
+    fn to_str(&self, value: ir::Expr, ty: Type, span: Span) -> ir::Expr {
         match ty {
+            Type::String => value,
+            ty => {
+                let from = self.to_ir_type(ty);
+                self.builder.convert(value, from, IrType::Str, span)
+            }
+        }
+    }
+    fn to_ir_type(&self, ty: Type) -> IrType {
+        match self.checker.resolve(&ty) {
             Type::String => IrType::Str,
             Type::Int => IrType::Int,
             Type::Float => IrType::Float,
             Type::Bool => IrType::Bool,
-            _ => todo!(),
+            Type::Array(ty, _) => IrType::Array(Box::new(self.to_ir_type(*ty))),
+            Type::Vec(ty) => IrType::Vec(Box::new(self.to_ir_type(*ty))),
+            Type::Map(key, value) => IrType::Map(
+                Box::new(self.to_ir_type(*key)),
+                Box::new(self.to_ir_type(*value)),
+            ),
+            // Enums are represented by the index of their variant
+            ty @ Type::Named(_) if self.checker.is_enum(&ty) => IrType::Int,
+            ty @ Type::Named(_) if self.checker.is_newtype(&ty) => {
+                self.to_ir_type(self.checker.underlying(&ty))
+            }
+            Type::Ptr(ty) => IrType::Ptr(Box::new(self.to_ir_type(*ty))),
+            Type::Tuple(fields) => IrType::Tuple(
+                fields
+                    .into_iter()
+                    .map(|field| self.to_ir_type(field))
+                    .collect(),
+            ),
+            Type::Function(params, return_type) => IrType::Function(
+                params
+                    .into_iter()
+                    .map(|param| self.to_ir_type(param))
+                    .collect(),
+                self.to_ir_return_type(*return_type).map(Box::new),
+            ),
+            Type::Optional(ty) => IrType::Optional(Box::new(self.to_ir_type(*ty))),
+            Type::Result(value, error) => IrType::Result(
+                Box::new(self.to_ir_type(*value)),
+                Box::new(self.to_ir_type(*error)),
+            ),
+            ty => bug(format!("Type {} can't be given to the compiler", ty)),
+        }
+    }
+    /// Declares a variable, in a stack slot if its address is taken
+    fn new_var(&mut self, name: &str, value: ir::Expr, ty: IrType) -> ir::Stmt {
+        if self.addressed.contains(name) {
+            self.builder.stack_var(name, value, ty)
+        } else {
+            self.builder.new_var(name, value, ty)
+        }
+    }
+    fn to_ir_return_type(&self, ty: Type) -> Option<IrType> {
+        match ty {
+            Type::None => None,
+            _ => Some(self.to_ir_type(ty)),
         }
     }
 }
+/// Collects the variables that have their address taken in `expr`
+fn addressed(expr: &Expr, names: &mut HashSet<String>) {
+    match &expr.inner {
+        ExprKind::Ref(value) => {
+            if let ExprKind::Ident(name) = &value.inner {
+                names.insert(name.clone());
+            }
+        }
+        // Nested functions and closures have their own variables
+        ExprKind::FunctionDeclaration(..) | ExprKind::Closure(..) => return,
+        _ => {}
+    }
+    for child in expr.inner.children() {
+        addressed(child, names);
+    }
+}
+/// Name of the function implementing method `name` for type `ty`, like `int.show`
+fn method_name(ty: &Type, name: &str) -> String {
+    format!("{}.{}", ty, name)
+}
+/// Name of an instance of a generic function, like `max[int]`
+fn instance_name(name: &str, types: &[Type]) -> String {
+    let types: Vec<String> = types.iter().map(Type::to_string).collect();
+    format!("{}[{}]", name, types.join(", "))
+}
@@ -1,49 +1,70 @@
-pub mod ast;
-mod error;
-mod lexer;
-mod parser;
 mod read;
-mod token;
-mod traits;
-mod typechecker;
-mod string_spliter;
-mod analyzer;
-mod compiler;
-mod lower;
-use analyzer::Analyzer;
-use lower::Lower;
-use parser::Parser;
+use illusio_2::{Engine, EngineError};
 use read::read_file;
-use traits::Item;
-use typechecker::TypeChecker;
 use std::env;
+use std::process::ExitCode;
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() > 1 {
-        let path = args.get(1).unwrap();
-        let input = read_file(&path);
-        let mut parser = Parser::new(&input, &path.clone());
-        let parse = parser.parse();
-        parser.finish();
-        let mut analyzer =  Analyzer::new(&parse, input.clone(), path.to_string());
-        let analyze = analyzer.analyze();
+const USAGE: &str = "USAGE: illusio [--emit tokens|ast|ir] <file>";
 
-        if  analyze{
-            analyzer.finish();
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (emit, path) = match args.as_slice() {
+        [path] => (None, path),
+        [flag, emit, path]
+            if flag == "--emit" && matches!(emit.as_str(), "tokens" | "ast" | "ir") =>
+        {
+            (Some(emit.as_str()), path)
         }
-        else {
-            let mut typechecker = TypeChecker::new(&parse, &input, &path);
-            if typechecker.check(){
-                typechecker.finish();
+        _ => {
+            eprintln!("{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let input = match read_file(path) {
+        Ok(input) => input,
+        Err(err) => {
+            eprintln!("ERROR: {}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut engine = Engine::new();
+    engine.file_name(path);
+    match run(&engine, &input, emit) {
+        Err(err @ (EngineError::Runtime(_) | EngineError::Internal(_))) => {
+            err.show();
+            ExitCode::from(101)
+        }
+        Err(err) => {
+            err.show();
+            ExitCode::FAILURE
+        }
+        Ok(()) => ExitCode::SUCCESS,
+    }
+}
+/// Runs the program, or prints what the stage `emit` gives for it
+fn run(engine: &Engine, input: &str, emit: Option<&str>) -> Result<(), EngineError> {
+    match emit {
+        Some("tokens") => {
+            for token in engine.lex(input)? {
+                println!("{token}");
             }
-            else {
-                let mut lower = Lower::new(&parse, typechecker);
-                lower.translate();
-                lower.finish();
+            return Ok(());
+        }
+        Some("ast") => {
+            for expr in engine.parse(input)? {
+                println!("{expr:#?}");
             }
+            return Ok(());
         }
-    } else {
-        eprintln!("USAGE: illusio <file>")
+        _ => {}
+    }
+    let ast = engine.parse(input)?;
+    let analysis = engine.analyze(input, &ast)?;
+    let checked = engine.check(input, &ast)?;
+    let lowered = engine.lower(input, analysis, checked)?;
+    if emit == Some("ir") {
+        print!("{}", engine.emit(&lowered)?);
+        return Ok(());
     }
+    engine.jit(&lowered)?.run()
 }
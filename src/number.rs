@@ -0,0 +1,158 @@
+use crate::token::Span;
+
+/// A numeric literal that couldn't be parsed
+pub struct NumberError {
+    pub message: String,
+    pub help: String,
+    pub span: Span,
+}
+
+/// Parses an int literal like `1_000`, `0xff`, `0o17` or `0b101`, `offset` is its position
+/// in the source. Prefixed literals can use all 64 bits, so `0xffffffffffffffff` is -1.
+pub fn parse_int(literal: &str, offset: usize) -> Result<i64, NumberError> {
+    int(literal, offset, false)
+}
+
+/// Parses an int literal right after a `-`, so it can be as small as the smallest int,
+/// like `-9223372036854775808`
+pub fn parse_negative_int(literal: &str, offset: usize) -> Result<i64, NumberError> {
+    int(literal, offset, true)
+}
+
+fn int(literal: &str, offset: usize, negative: bool) -> Result<i64, NumberError> {
+    let (radix, name) = match literal.get(..2) {
+        Some("0x") => (16, "hex"),
+        Some("0o") => (8, "octal"),
+        Some("0b") => (2, "binary"),
+        _ => (10, "int"),
+    };
+    let prefix = if radix == 10 { 0 } else { 2 };
+    let digits = digits(literal, prefix, radix, name, offset)?;
+    if digits.is_empty() {
+        return Err(NumberError {
+            message: format!("Missing digits after \"{}\"", &literal[..prefix]),
+            help: format!("Write {} literals like {}", name, example(radix)),
+            span: Span::from(offset..offset + literal.len()),
+        });
+    }
+    let value = if radix == 10 {
+        let digits = if negative {
+            format!("-{}", digits)
+        } else {
+            digits
+        };
+        digits.parse::<i64>().ok()
+    } else {
+        let value = u64::from_str_radix(&digits, radix)
+            .ok()
+            .map(|value| value as i64);
+        if negative {
+            value.map(i64::wrapping_neg)
+        } else {
+            value
+        }
+    };
+    value.ok_or_else(|| NumberError {
+        message: if negative && radix == 10 {
+            format!("The {} literal -{} is too small", name, literal)
+        } else {
+            format!("The {} literal {} is too large", name, literal)
+        },
+        help: if negative && radix == 10 {
+            format!("The smallest int is {}", i64::MIN)
+        } else if radix == 10 {
+            format!("The largest int is {}", i64::MAX)
+        } else {
+            format!("{} literals can't use more than 64 bits", capitalize(name))
+        },
+        span: Span::from(offset..offset + literal.len()),
+    })
+}
+
+/// Parses a float literal like `1.5`, `.5`, `2.` or `1.5e-3`, `offset` is its position in the source
+pub fn parse_float(literal: &str, offset: usize) -> Result<f64, NumberError> {
+    let exponent = literal.find(['e', 'E']).unwrap_or(literal.len());
+    let mut mantissa = digits(&literal[..exponent], 0, 10, "float", offset)?;
+    if exponent < literal.len() {
+        let sign = literal[exponent + 1..].starts_with(['+', '-']) as usize;
+        let start = exponent + 1 + sign;
+        let exponent_digits = digits(literal, start, 10, "float", offset)?;
+        if exponent_digits.is_empty() {
+            return Err(NumberError {
+                message: "Missing digits in the exponent".to_owned(),
+                help: "Write exponents like 1.5e-3".to_owned(),
+                span: Span::from(offset + exponent..offset + literal.len()),
+            });
+        }
+        mantissa += &literal[exponent..start];
+        mantissa += &exponent_digits;
+    }
+    // Parsing a float can only fail on the digits, which are already checked
+    let value = mantissa.parse::<f64>().unwrap();
+    if value.is_infinite() {
+        return Err(NumberError {
+            message: format!("The float literal {} is too large", literal),
+            help: format!("The largest float is about {:e}", f64::MAX),
+            span: Span::from(offset..offset + literal.len()),
+        });
+    }
+    Ok(value)
+}
+
+/// Checks the digits of a literal from `start`, and gives them without separators.
+/// A `_` separator has to be between two digits, like `1_000_000`.
+fn digits(
+    literal: &str,
+    start: usize,
+    radix: u32,
+    name: &str,
+    offset: usize,
+) -> Result<String, NumberError> {
+    let bytes = literal.as_bytes();
+    let is_digit = |i: usize| (bytes.get(i).copied().unwrap_or(0) as char).is_digit(radix);
+    let mut digits = String::new();
+    for (i, ch) in literal.char_indices().skip(start) {
+        let span = Span::from(offset + i..offset + i + ch.len_utf8());
+        match ch {
+            '_' if i > start && is_digit(i - 1) && is_digit(i + 1) => {}
+            '_' => {
+                return Err(NumberError {
+                    message: "A \"_\" separator has to be between two digits".to_owned(),
+                    help: format!("Separate digits like {}", example(radix)),
+                    span,
+                })
+            }
+            // The fraction of a float
+            '.' if radix == 10 && name == "float" => digits.push(ch),
+            ch if ch.is_digit(radix) => digits.push(ch),
+            ch if ch.is_ascii_digit() || (radix == 16 && ch.is_ascii_alphabetic()) => {
+                return Err(NumberError {
+                    message: format!("Invalid digit \"{}\" in {} literal", ch, name),
+                    help: format!("Write {} literals like {}", name, example(radix)),
+                    span,
+                })
+            }
+            _ => {
+                return Err(NumberError {
+                    message: format!("Invalid suffix \"{}\" on {} literal", &literal[i..], name),
+                    help: "Put a space between a number and a name".to_owned(),
+                    span: Span::from(offset + i..offset + literal.len()),
+                })
+            }
+        }
+    }
+    Ok(digits)
+}
+
+fn example(radix: u32) -> &'static str {
+    match radix {
+        16 => "0xff_ff",
+        8 => "0o7_55",
+        2 => "0b1010_0101",
+        _ => "1_000_000",
+    }
+}
+
+fn capitalize(name: &str) -> String {
+    name[..1].to_uppercase() + &name[1..]
+}
@@ -1,9 +1,8 @@
-use std::f32::consts::E;
-
 use crate::{
-    ast::{Expr, ExprKind, Param, Type},
+    ast::{Expr, ExprKind, MethodSig, Param, Pattern, Type, TypeParam},
     error::*,
     lexer::Lexer,
+    number::{self, NumberError},
     token::{Span, Token, TokenKind},
     traits::Item,
 };
@@ -15,6 +14,10 @@ pub struct Parser {
     current: Token,
     filename: String,
     position: usize,
+    /// Type parameters of the generic function being parsed
+    type_params: Vec<String>,
+    /// The `*p` starting the next statement, which the last one took as a multiplication
+    deref_target: Option<Expr>,
 }
 impl Parser {
     pub fn new(input: &str, file_name: &str) -> Self {
@@ -26,6 +29,8 @@ impl Parser {
             has_lexing_errors: false,
             errors: Vec::new(),
             position: 0,
+            type_params: Vec::new(),
+            deref_target: None,
         };
         p.next();
         p
@@ -37,6 +42,13 @@ impl Parser {
             self.has_lexing_errors = true;
         }
     }
+    /// The token after the current one
+    fn peek(&mut self) -> Token {
+        let checkpoint = self.lexer.checkpoint();
+        let token = self.lexer.next();
+        self.lexer.restore(checkpoint);
+        token
+    }
     fn expect(&mut self, kind: TokenKind) {
         if self.current.kind != kind {
             self.errors.push(Error {
@@ -45,9 +57,10 @@ impl Parser {
                 message: format!("Expected \"{}\" found {}", kind, self.current.literal),
                 span: self.current.span,
                 help: "".to_owned(),
+                labels: Vec::new(),
             });
         }
-        self.current = self.lexer.next();
+        self.next();
     }
 
     pub fn parse(&mut self) -> Vec<Expr> {
@@ -62,7 +75,11 @@ impl Parser {
         }
     }
     fn declaration(&mut self) -> Expr {
-        match self.current.kind {
+        if let Some(target) = self.deref_target.take() {
+            return self.assignment(target);
+        }
+        let kind = self.current.kind;
+        match kind {
             TokenKind::Eof => Expr {
                 inner: ExprKind::Eof,
                 span: self.current.span,
@@ -83,6 +100,7 @@ impl Parser {
                             message: "Expected `end` at end of block.".to_owned(),
                             span: self.current.span,
                             help: "".to_owned(),
+                            labels: Vec::new(),
                         });
                         self.next();
                         return Expr {
@@ -92,67 +110,164 @@ impl Parser {
                     }
                     exprs.push(self.declaration());
                 }
-                return Expr {
+                Expr {
                     inner: ExprKind::Block(exprs),
                     span: Span::from(start..self.position),
+                }
+            }
+            // Anonymous functions are expressions
+            TokenKind::Fun if self.peek().kind == TokenKind::Identifier => self.function(),
+            TokenKind::TypeKw | TokenKind::Newtype => {
+                let start = self.current.span.start;
+                let newtype = self.current.kind == TokenKind::Newtype;
+                self.next();
+                let name = self.current.literal.clone();
+                self.expect(TokenKind::Identifier);
+                self.expect(TokenKind::Equal);
+                let ty = self.parse_type();
+                Expr {
+                    inner: if newtype {
+                        ExprKind::Newtype(name, ty)
+                    } else {
+                        ExprKind::TypeAlias(name, ty)
+                    },
+                    span: Span::from(start..self.position),
+                }
+            }
+            TokenKind::Extern => {
+                let start = self.current.span.start;
+                self.next();
+                let library = if self.current.kind == TokenKind::String {
+                    let library = self.current.literal.clone();
+                    self.next();
+                    Some(library)
+                } else {
+                    None
                 };
+                self.expect(TokenKind::Fun);
+                let name = self.current.literal.clone();
+                self.expect(TokenKind::Identifier);
+                let (params, variadic) = self.extern_params();
+                let return_type = self.return_type();
+                let span = Span::from(start..self.position);
+                let sig = MethodSig {
+                    name,
+                    params,
+                    return_type,
+                    span,
+                };
+                Expr {
+                    inner: ExprKind::Extern(library, sig, variadic),
+                    span,
+                }
             }
-            TokenKind::Fun => {
-                let start = self.position;
+            TokenKind::Interface => {
+                let start = self.current.span.start;
                 self.next();
                 let name = self.current.literal.clone();
                 self.expect(TokenKind::Identifier);
-
-                let mut params: Vec<Param> = vec![];
-                if self.current.kind == TokenKind::Identifier {
-                    loop {
-                        let param_name = self.current.literal.clone();
-                        self.next();
-                        let param_type = self.parse_type();
-                        params.push(Param {
-                            param_type,
-                            name: param_name,
-                        });
-                        if self.current.kind == TokenKind::Comma {
+                let mut methods = Vec::<MethodSig>::new();
+                loop {
+                    match self.current.kind {
+                        TokenKind::End => {
                             self.next();
-                            continue;
-                        } else {
                             break;
                         }
+                        TokenKind::Fun => {
+                            let sig_start = self.current.span.start;
+                            self.next();
+                            let method_name = self.current.literal.clone();
+                            self.expect(TokenKind::Identifier);
+                            let params = self.params();
+                            let mut return_type = Type::None;
+                            if self.current.kind == TokenKind::Colon {
+                                self.next();
+                                return_type = self.parse_type();
+                            }
+                            methods.push(MethodSig {
+                                name: method_name,
+                                params,
+                                return_type,
+                                span: Span::from(sig_start..self.position),
+                            });
+                        }
+                        _ => {
+                            self.errors.push(Error {
+                                source: self.source.clone(),
+                                file_name: self.filename.clone(),
+                                message: format!(
+                                    "Expected method signature or `end` found {}",
+                                    self.current.literal
+                                ),
+                                span: self.current.span,
+                                help: "like `fun show(self) : str`".to_owned(),
+                                labels: Vec::new(),
+                            });
+                            self.next();
+                            return Expr {
+                                inner: ExprKind::Error,
+                                span: self.current.span,
+                            };
+                        }
                     }
                 }
-                let mut return_type: Type = Type::None;
-                if self.current.kind == TokenKind::Colon {
-                    self.next();
-                    return_type = self.parse_type();
+                Expr {
+                    inner: ExprKind::Interface(name, methods),
+                    span: Span::from(start..self.position),
                 }
-                self.expect(TokenKind::Do);
-                let mut exprs: Vec<Expr> = Vec::new();
+            }
+            TokenKind::Impl => {
+                let start = self.current.span.start;
+                self.next();
+                let interface = self.current.literal.clone();
+                self.expect(TokenKind::Identifier);
+                self.expect(TokenKind::For);
+                let ty = self.parse_type();
+                let mut methods = Vec::<Expr>::new();
                 loop {
-                    if self.current.kind == TokenKind::End {
-                        self.next();
-                        break;
-                    }
-                    if self.current.kind == TokenKind::Eof {
-                        self.errors.push(Error {
-                            source: self.source.clone(),
-                            file_name: self.filename.clone(),
-                            message: "Expected `end` at end of block.".to_owned(),
-                            span: self.current.span,
-                            help: "".to_owned(),
-                        });
-                        self.next();
-                        return Expr {
-                            inner: ExprKind::Error,
-                            span: self.current.span,
-                        };
+                    match self.current.kind {
+                        TokenKind::End => {
+                            self.next();
+                            break;
+                        }
+                        TokenKind::Fun => methods.push(self.function()),
+                        _ => {
+                            self.errors.push(Error {
+                                source: self.source.clone(),
+                                file_name: self.filename.clone(),
+                                message: format!(
+                                    "Expected method or `end` found {}",
+                                    self.current.literal
+                                ),
+                                span: self.current.span,
+                                help: "".to_owned(),
+                                labels: Vec::new(),
+                            });
+                            self.next();
+                            return Expr {
+                                inner: ExprKind::Error,
+                                span: self.current.span,
+                            };
+                        }
                     }
-                    exprs.push(self.declaration());
                 }
-                return Expr {
-                    inner: ExprKind::FunctionDeclaration(name, params, return_type, exprs),
+                Expr {
+                    inner: ExprKind::Impl(interface, ty, methods),
                     span: Span::from(start..self.position),
+                }
+            }
+            TokenKind::Return => {
+                let start = self.current.span.start;
+                self.next();
+                let value = match self.current.kind {
+                    TokenKind::End | TokenKind::SemiColon | TokenKind::Eof => None,
+                    _ => Some(self.parse_expr(0).boxed()),
                 };
+                self.semicolon();
+                Expr {
+                    inner: ExprKind::Return(value),
+                    span: Span::from(start..self.position),
+                }
             }
 
             TokenKind::Enum => {
@@ -177,6 +292,7 @@ impl Parser {
                             message: "Expected `end` at end of block.".to_owned(),
                             span: self.current.span,
                             help: "".to_owned(),
+                            labels: Vec::new(),
                         });
                         self.next();
                         return Expr {
@@ -192,22 +308,343 @@ impl Parser {
                     }
                 }
                 self.semicolon();
-                return Expr {
+                Expr {
                     inner: ExprKind::Enum(name, fields),
                     span: Span::from(start..self.position),
-                };
+                }
             }
-            TokenKind::IfKw => return self.if_expr(),
+            TokenKind::IfKw => self.if_expr(),
+            TokenKind::For => self.for_loop(),
             _ => {
-                let expr = self.parse_expr(0);
+                let mut expr = self.parse_expr(0);
+                if let (ExprKind::Ident(first), TokenKind::Comma) = (&expr.inner, self.current.kind)
+                {
+                    return self.destructure(first.clone(), expr.span.start);
+                }
+                if self.split_deref(&mut expr) {
+                    return expr;
+                }
+                self.assignment(expr)
+            }
+        }
+    }
+    /// Parses the rest of a statement starting with `target`, which is an assignment when
+    /// followed by `=`
+    fn assignment(&mut self, mut target: Expr) -> Expr {
+        if self.current.kind == TokenKind::Equal {
+            self.next();
+            let mut value = self.parse_expr(0);
+            self.split_deref(&mut value);
+            target = Expr {
+                span: Span::from(target.span.start..value.span.end),
+                inner: ExprKind::Assign(target.boxed(), value.boxed()),
+            };
+        }
+        self.semicolon();
+        target
+    }
+    /// Splits the `*p` of a statement like `*p = 5` from the end of `expr`, the statement
+    /// before it, which took it as a multiplication.
+    /// Only when `expr` is followed by `=`, as nothing else can be assigned to
+    fn split_deref(&mut self, expr: &mut Expr) -> bool {
+        if self.current.kind != TokenKind::Equal {
+            return false;
+        }
+        match self.deref_at_end(expr) {
+            Some(target) => {
+                self.deref_target = Some(target);
                 self.semicolon();
-                return expr;
+                true
+            }
+            None => false,
+        }
+    }
+    /// Takes the last multiplication in `expr` out of it, when its `*` starts a line
+    fn deref_at_end(&self, expr: &mut Expr) -> Option<Expr> {
+        let target = match &mut expr.inner {
+            ExprKind::Var(_, value, _) => self.deref_at_end(value)?,
+            ExprKind::Binary(left, TokenKind::Asterisk, right) => {
+                let gap = self.source[left.span.end..right.span.start].trim_end();
+                let starts_line = gap
+                    .strip_suffix('*')
+                    .is_some_and(|before| before.contains('\n'));
+                if starts_line {
+                    let target = Expr {
+                        span: Span::from(left.span.end + gap.len() - 1..right.span.end),
+                        inner: ExprKind::Deref(right.clone()),
+                    };
+                    *expr = (**left).clone();
+                    return Some(target);
+                }
+                self.deref_at_end(right)?
+            }
+            ExprKind::Binary(_, _, right) => self.deref_at_end(right)?,
+            _ => return None,
+        };
+        let end = match &expr.inner {
+            ExprKind::Var(_, value, _) => value.span.end,
+            ExprKind::Binary(_, _, right) => right.span.end,
+            _ => unreachable!(),
+        };
+        expr.span = Span::from(expr.span.start..end);
+        Some(target)
+    }
+    fn function(&mut self) -> Expr {
+        let start = self.current.span.start;
+        self.next();
+        let name = self.current.literal.clone();
+        self.expect(TokenKind::Identifier);
+        let type_params = self.type_params();
+        let outer_type_params = std::mem::replace(
+            &mut self.type_params,
+            type_params.iter().map(|param| param.name.clone()).collect(),
+        );
+        let params = self.params();
+        let return_type = self.return_type();
+        let exprs = self.function_body();
+        self.type_params = outer_type_params;
+        match exprs {
+            Some(exprs) => Expr {
+                inner: ExprKind::FunctionDeclaration(name, type_params, params, return_type, exprs),
+                span: Span::from(start..self.position),
+            },
+            None => Expr {
+                inner: ExprKind::Error,
+                span: self.current.span,
+            },
+        }
+    }
+    /// Parses an anonymous function, like `fun(x int) : int do x + 1 end`.
+    /// When it returns a value, its last expression is returned.
+    fn closure(&mut self) -> Expr {
+        let start = self.current.span.start;
+        self.next();
+        let params = self.params();
+        let return_type = self.return_type();
+        let mut exprs = match self.function_body() {
+            Some(exprs) => exprs,
+            None => {
+                return Expr {
+                    inner: ExprKind::Error,
+                    span: self.current.span,
+                }
             }
+        };
+        if return_type != Type::None {
+            if let Some(last) = exprs.pop() {
+                exprs.push(match last.inner {
+                    ExprKind::Return(_) => last,
+                    _ => Expr {
+                        span: last.span,
+                        inner: ExprKind::Return(Some(last.boxed())),
+                    },
+                });
+            }
+        }
+        Expr {
+            inner: ExprKind::Closure(params, return_type, exprs),
+            span: Span::from(start..self.position),
         }
     }
+    /// Parses the optional return type of a function, like `: int`
+    fn return_type(&mut self) -> Type {
+        if self.current.kind != TokenKind::Colon {
+            return Type::None;
+        }
+        self.next();
+        self.parse_type()
+    }
+    /// Parses the body of a function from its `do` to its `end`
+    fn function_body(&mut self) -> Option<Vec<Expr>> {
+        self.expect(TokenKind::Do);
+        let mut exprs: Vec<Expr> = Vec::new();
+        loop {
+            if self.current.kind == TokenKind::End {
+                self.next();
+                return Some(exprs);
+            }
+            if self.current.kind == TokenKind::Eof {
+                self.errors.push(Error {
+                    source: self.source.clone(),
+                    file_name: self.filename.clone(),
+                    message: "Expected `end` at end of block.".to_owned(),
+                    span: self.current.span,
+                    help: "".to_owned(),
+                    labels: Vec::new(),
+                });
+                self.next();
+                return None;
+            }
+            exprs.push(self.declaration());
+        }
+    }
+    /// Parses the type parameters of a generic function, like `[T, U: Show + Eq]`
+    fn type_params(&mut self) -> Vec<TypeParam> {
+        let mut type_params = Vec::new();
+        if self.current.kind != TokenKind::OpeningSquare {
+            return type_params;
+        }
+        self.next();
+        while self.current.kind == TokenKind::Identifier {
+            let name = self.current.literal.clone();
+            self.next();
+            let mut bounds = Vec::new();
+            if self.current.kind == TokenKind::Colon {
+                loop {
+                    self.next();
+                    bounds.push(self.current.literal.clone());
+                    self.expect(TokenKind::Identifier);
+                    if self.current.kind != TokenKind::Plus {
+                        break;
+                    }
+                }
+            }
+            type_params.push(TypeParam { name, bounds });
+            if self.current.kind == TokenKind::Comma {
+                self.next();
+            } else {
+                break;
+            }
+        }
+        self.expect(TokenKind::ClosingSquare);
+        type_params
+    }
+    /// Parses function parameters, either bare `a int, b int` or wrapped in parentheses.
+    fn params(&mut self) -> Vec<Param> {
+        let mut params: Vec<Param> = vec![];
+        let parens = self.current.kind == TokenKind::OpeningParen;
+        if parens {
+            self.next();
+        }
+        if self.current.kind == TokenKind::Identifier {
+            loop {
+                params.push(self.param());
+                if self.current.kind == TokenKind::Comma {
+                    self.next();
+                    continue;
+                } else {
+                    break;
+                }
+            }
+        }
+        if parens {
+            self.expect(TokenKind::ClosingParen);
+        }
+        params
+    }
+    /// Parses a parameter, it can have a default value, like `greeting str = "hi"`,
+    /// or take the remaining arguments, like `numbers ...int`.
+    fn param(&mut self) -> Param {
+        let name = self.current.literal.clone();
+        self.expect(TokenKind::Identifier);
+        let variadic = self.current.kind == TokenKind::Dot;
+        if variadic {
+            for _ in 0..3 {
+                self.expect(TokenKind::Dot);
+            }
+        }
+        let param_type = if name == "self" {
+            Type::SelfType
+        } else if variadic {
+            Type::Vec(self.parse_type().boxed())
+        } else {
+            self.parse_type()
+        };
+        let default = if self.current.kind == TokenKind::Equal {
+            self.next();
+            // Stopping at the colon of the return type, which is a binary operator
+            Some(self.parse_expr(1))
+        } else {
+            None
+        };
+        Param {
+            param_type,
+            name,
+            default,
+            variadic,
+        }
+    }
+    /// Parses the parameters of a C function, which can end with `...`
+    /// when it takes more arguments, like `(format str, ...)`
+    fn extern_params(&mut self) -> (Vec<Param>, bool) {
+        let mut params = Vec::new();
+        let mut variadic = false;
+        self.expect(TokenKind::OpeningParen);
+        while self.current.kind != TokenKind::ClosingParen {
+            if self.current.kind == TokenKind::Dot {
+                for _ in 0..3 {
+                    self.expect(TokenKind::Dot);
+                }
+                variadic = true;
+                break;
+            }
+            params.push(self.param());
+            if self.current.kind == TokenKind::Comma {
+                self.next();
+            } else {
+                break;
+            }
+        }
+        self.expect(TokenKind::ClosingParen);
+        (params, variadic)
+    }
+    /// Parses call arguments, the opening paren should be already consumed.
+    /// Arguments can be given by name, like `greet(name: "Ada")`.
+    fn args(&mut self) -> Vec<Expr> {
+        let mut args = vec![];
+        while self.current.kind != TokenKind::ClosingParen {
+            let named =
+                self.current.kind == TokenKind::Identifier && self.peek().kind == TokenKind::Colon;
+            let arg = if named {
+                let start = self.current.span.start;
+                let name = self.current.literal.clone();
+                self.next();
+                self.next();
+                let value = self.parse_expr(0);
+                Expr {
+                    inner: ExprKind::NamedArg(name, value.boxed()),
+                    span: Span::from(start..self.position),
+                }
+            } else {
+                self.parse_expr(0)
+            };
+            args.push(arg);
+            if self.current.kind == TokenKind::Comma {
+                self.next();
+                continue;
+            } else {
+                break;
+            }
+        }
+        self.expect(TokenKind::ClosingParen);
+        args
+    }
     fn if_expr(&mut self) -> Expr {
         let start = self.position;
         self.next();
+        // `if let x = maybe` names the value of an optional,
+        // `if let ok(x) = result` and `if let err(e) = result` the value or error of a result
+        let mut pattern = None;
+        if self.current.kind == TokenKind::Let {
+            self.next();
+            let name = self.current.literal.clone();
+            self.expect(TokenKind::Identifier);
+            pattern = Some(match name.as_str() {
+                "ok" | "err" if self.current.kind == TokenKind::OpeningParen => {
+                    self.next();
+                    let inner = self.current.literal.clone();
+                    self.expect(TokenKind::Identifier);
+                    self.expect(TokenKind::ClosingParen);
+                    if name == "ok" {
+                        Pattern::Ok(inner)
+                    } else {
+                        Pattern::Err(inner)
+                    }
+                }
+                _ => Pattern::Value(name),
+            });
+            self.expect(TokenKind::Equal);
+        }
         let condition = self.parse_expr(0);
         let mut exprs = Vec::<Expr>::new();
         loop {
@@ -222,6 +659,7 @@ impl Parser {
                     message: "Expected `end` at end of block.".to_owned(),
                     span: self.current.span,
                     help: "".to_owned(),
+                    labels: Vec::new(),
                 });
                 self.next();
                 return Expr {
@@ -231,10 +669,56 @@ impl Parser {
             }
             exprs.push(self.declaration());
         }
-        return Expr {
-            inner: ExprKind::If(condition.boxed(), exprs),
-            span: Span::from(start..self.position),
+        let inner = match pattern {
+            Some(pattern) => ExprKind::IfLet(pattern, condition.boxed(), exprs),
+            None => ExprKind::If(condition.boxed(), exprs),
         };
+        Expr {
+            inner,
+            span: Span::from(start..self.position),
+        }
+    }
+    /// Parses a loop over the elements of a value, like `for x in v ... end` or `for k, v in m ... end`
+    fn for_loop(&mut self) -> Expr {
+        let start = self.current.span.start;
+        self.next();
+        let name = self.current.literal.clone();
+        self.expect(TokenKind::Identifier);
+        let mut second = None;
+        if self.current.kind == TokenKind::Comma {
+            self.next();
+            second = Some(self.current.literal.clone());
+            self.expect(TokenKind::Identifier);
+        }
+        self.expect(TokenKind::In);
+        let iterable = self.parse_expr(0);
+        let mut exprs = Vec::<Expr>::new();
+        loop {
+            if self.current.kind == TokenKind::End {
+                self.next();
+                break;
+            }
+            if self.current.kind == TokenKind::Eof {
+                self.errors.push(Error {
+                    source: self.source.clone(),
+                    file_name: self.filename.clone(),
+                    message: "Expected `end` at end of block.".to_owned(),
+                    span: self.current.span,
+                    help: "".to_owned(),
+                    labels: Vec::new(),
+                });
+                self.next();
+                return Expr {
+                    inner: ExprKind::Error,
+                    span: self.current.span,
+                };
+            }
+            exprs.push(self.declaration());
+        }
+        Expr {
+            inner: ExprKind::For(name, second, iterable.boxed(), exprs),
+            span: Span::from(start..self.position),
+        }
     }
     fn semicolon(&mut self) {
         if self.current.kind == TokenKind::SemiColon {
@@ -242,12 +726,76 @@ impl Parser {
         }
     }
     fn parse_expr(&mut self, rbp: i32) -> Expr {
-        let start = self.position;
+        let start = self.current.span.start;
         let tok = self.current.clone();
         let mut left = self.nud(&tok);
         while rbp < self.lbp(&self.current.kind) {
             let tok = self.current.clone();
             self.next();
+            if tok.kind == TokenKind::Question {
+                left = Expr {
+                    inner: ExprKind::Try(left.boxed()),
+                    span: Span::from(start..self.position),
+                };
+                continue;
+            }
+            if tok.kind == TokenKind::Dot && self.current.kind == TokenKind::Int {
+                let index = match self.current.literal.parse::<usize>() {
+                    Ok(index) => index,
+                    Err(_) => {
+                        self.errors.push(Error {
+                            source: self.source.clone(),
+                            file_name: self.filename.clone(),
+                            message: format!("Invalid tuple index {}", self.current.literal),
+                            span: self.current.span,
+                            help: "Get elements of tuples like `t.0`".to_owned(),
+                            labels: Vec::new(),
+                        });
+                        0
+                    }
+                };
+                self.next();
+                left = Expr {
+                    inner: ExprKind::TupleIndex(left.boxed(), index),
+                    span: Span::from(start..self.position),
+                };
+                continue;
+            }
+            if tok.kind == TokenKind::Dot {
+                let name = self.current.literal.clone();
+                self.expect(TokenKind::Identifier);
+                self.expect(TokenKind::OpeningParen);
+                let args = self.args();
+                left = Expr {
+                    inner: ExprKind::MethodCall(left.boxed(), name, args),
+                    span: Span::from(start..self.position),
+                };
+                continue;
+            }
+            if tok.kind == TokenKind::OpeningSquare {
+                // Stopping at the colon of slices, which is a binary operator
+                let index = match self.current.kind {
+                    TokenKind::Colon => None,
+                    _ => Some(self.parse_expr(1)),
+                };
+                let inner = if self.current.kind == TokenKind::Colon {
+                    self.next();
+                    let end = match self.current.kind {
+                        TokenKind::ClosingSquare => None,
+                        _ => Some(self.parse_expr(0).boxed()),
+                    };
+                    ExprKind::Slice(left.boxed(), index.map(Expr::boxed), end)
+                } else {
+                    // `index` is only missing before a colon
+                    ExprKind::Index(left.boxed(), index.unwrap().boxed())
+                };
+                self.expect(TokenKind::ClosingSquare);
+                left = Expr {
+                    span: Span::from(start..self.position),
+                    inner,
+                };
+                continue;
+            }
             let right = self.parse_expr(self.lbp(&tok.kind)).boxed();
             left = self.led(
                 left.boxed(),
@@ -258,11 +806,22 @@ impl Parser {
         }
         left
     }
+    /// The binding power of the token after the current one
+    fn peek_lbp(&mut self) -> i32 {
+        let next = self.peek();
+        self.lbp(&next.kind)
+    }
     fn lbp(&self, op: &TokenKind) -> i32 {
         match op {
+            TokenKind::Dot | TokenKind::OpeningSquare | TokenKind::Question => 50,
             TokenKind::Mod => 25,
             TokenKind::Plus | TokenKind::Minus => 10,
             TokenKind::Asterisk | TokenKind::Slash => 15,
+            // Bitwise operators bind tighter than comparisons, so `a & 1 == 0` needs no parentheses
+            TokenKind::ShiftLeft | TokenKind::ShiftRight | TokenKind::UnsignedShiftRight => 9,
+            TokenKind::Ampersand => 8,
+            TokenKind::Caret => 7,
+            TokenKind::Pipe => 6,
 
             TokenKind::GreaterThan
             | TokenKind::LessThan
@@ -274,32 +833,40 @@ impl Parser {
             _ => -1, // In another words stop the expr parsing
         }
     }
+    fn number_error(&mut self, err: NumberError) -> Expr {
+        self.errors.push(Error {
+            source: self.source.clone(),
+            file_name: self.filename.clone(),
+            message: err.message,
+            span: err.span,
+            help: err.help,
+            labels: Vec::new(),
+        });
+        Expr {
+            inner: ExprKind::Error,
+            span: err.span,
+        }
+    }
     fn led(&self, left: Box<Expr>, op: TokenKind, right: Box<Expr>, span: Span) -> Expr {
         let kind = ExprKind::Binary(left, op, right);
         Expr { inner: kind, span }
     }
-    pub fn finish(&self) -> bool {
+    /// The token being parsed, a bug in the parser is reported there
+    pub fn span(&self) -> Span {
+        self.current.span
+    }
+    /// The errors found while parsing, only the lexing errors when there are some
+    pub fn errors(&self) -> &[Error] {
         if self.has_lexing_errors {
-            for err in self.lexer.errors.clone() {
-                err.show();
-            }
-            return true;
-        } else if !self.errors.is_empty() {
-            for err in self.errors.clone() {
-                err.show()
-            }
-            return true;
+            &self.lexer.errors
+        } else {
+            &self.errors
         }
-        false
     }
     fn parse_type(&mut self) -> Type {
-        match self.current.kind {
-            TokenKind::IntTy
-            | TokenKind::FloatTy
-            | TokenKind::StringTy
-            | TokenKind::BoolTy
-            | TokenKind::Asterisk => {
-                let mut ty = match self.current.kind {
+        let mut ty = match self.current.kind {
+            TokenKind::IntTy | TokenKind::FloatTy | TokenKind::StringTy | TokenKind::BoolTy => {
+                let ty = match self.current.kind {
                     TokenKind::IntTy => Type::Int,
                     TokenKind::FloatTy => Type::Float,
                     TokenKind::StringTy => Type::String,
@@ -308,12 +875,86 @@ impl Parser {
                     _ => unreachable!(),
                 };
                 self.next();
-                if self.current.kind == TokenKind::Asterisk {
+                ty
+            }
+            TokenKind::VecTy => {
+                self.next();
+                self.expect(TokenKind::OpeningSquare);
+                let element = self.parse_type();
+                self.expect(TokenKind::ClosingSquare);
+                Type::Vec(element.boxed())
+            }
+            TokenKind::OpeningSquare => {
+                self.next();
+                let element = self.parse_type();
+                self.expect(TokenKind::SemiColon);
+                let size = match self.current.literal.parse::<usize>() {
+                    Ok(size) if self.current.kind == TokenKind::Int => size,
+                    _ => {
+                        self.errors.push(Error {
+                            source: self.source.clone(),
+                            file_name: self.filename.clone(),
+                            message: "Expected the size of the array".to_owned(),
+                            span: self.current.span,
+                            help: "like `[int; 3]`".to_owned(),
+                            labels: Vec::new(),
+                        });
+                        0
+                    }
+                };
+                self.next();
+                self.expect(TokenKind::ClosingSquare);
+                Type::Array(element.boxed(), size)
+            }
+            TokenKind::Fun => {
+                self.next();
+                self.expect(TokenKind::OpeningParen);
+                let mut params = Vec::new();
+                while self.current.kind != TokenKind::ClosingParen {
+                    params.push(self.parse_type());
+                    if self.current.kind != TokenKind::Comma {
+                        break;
+                    }
                     self.next();
-                    ty = Type::Ptr(ty.boxed())
                 }
-
-                return ty;
+                self.expect(TokenKind::ClosingParen);
+                let return_type = self.return_type();
+                Type::Function(params, return_type.boxed())
+            }
+            TokenKind::OpeningParen => {
+                self.next();
+                let mut elements = vec![self.parse_type()];
+                while self.current.kind == TokenKind::Comma {
+                    self.next();
+                    elements.push(self.parse_type());
+                }
+                self.expect(TokenKind::ClosingParen);
+                Type::Tuple(elements)
+            }
+            // `map` and `Result` are only types where a type is expected, so functions and
+            // variables can have these names too
+            TokenKind::Identifier => {
+                let name = self.current.literal.clone();
+                self.next();
+                if name == "map" && self.current.kind == TokenKind::OpeningSquare {
+                    self.next();
+                    let key = self.parse_type();
+                    self.expect(TokenKind::Comma);
+                    let value = self.parse_type();
+                    self.expect(TokenKind::ClosingSquare);
+                    Type::Map(key.boxed(), value.boxed())
+                } else if name == "Result" && self.current.kind == TokenKind::OpeningSquare {
+                    self.next();
+                    let value = self.parse_type();
+                    self.expect(TokenKind::Comma);
+                    let error = self.parse_type();
+                    self.expect(TokenKind::ClosingSquare);
+                    Type::Result(value.boxed(), error.boxed())
+                } else if self.type_params.contains(&name) {
+                    Type::Param(name)
+                } else {
+                    Type::Named(name)
+                }
             }
             _ => {
                 self.errors.push(Error {
@@ -322,220 +963,422 @@ impl Parser {
                     message: "Expected type".to_string(),
                     span: self.current.span,
                     help: "like `int`, `float` `str` `bool`".to_owned(),
+                    labels: Vec::new(),
                 });
                 self.next();
-                Type::None
+                return Type::None;
             }
+        };
+        // Pointers and optionals of any type, like `int*` or `vec[str]?`
+        loop {
+            match self.current.kind {
+                TokenKind::Asterisk => ty = Type::Ptr(ty.boxed()),
+                TokenKind::Question => ty = Type::Optional(ty.boxed()),
+                _ => return ty,
+            }
+            self.next();
         }
     }
 
+    /// Whether the `(` at the current token starts a tuple type like `(int, str)`,
+    /// rather than a call like `f(int("1"))`. The first element starts with a type.
+    fn tuple_type_ahead(&mut self) -> bool {
+        // Conversions like `int("1")` start with a type too
+        let not_conversion = |token: Token| token.kind != TokenKind::OpeningParen;
+        let checkpoint = self.lexer.checkpoint();
+        let mut token = self.lexer.next();
+        while token.kind == TokenKind::OpeningParen {
+            token = self.lexer.next();
+        }
+        let is_type = match token.kind {
+            TokenKind::IntTy
+            | TokenKind::FloatTy
+            | TokenKind::StringTy
+            | TokenKind::BoolTy
+            | TokenKind::VecTy => not_conversion(self.lexer.next()),
+            // Map types have a comma inside their brackets, unlike indexing a variable named `map`
+            TokenKind::Identifier if token.literal == "map" => {
+                let mut depth = 0;
+                loop {
+                    match self.lexer.next().kind {
+                        TokenKind::OpeningSquare => depth += 1,
+                        TokenKind::ClosingSquare => depth -= 1,
+                        TokenKind::Comma if depth == 1 => break true,
+                        TokenKind::Eof => break false,
+                        _ => {}
+                    }
+                    if depth == 0 {
+                        break false;
+                    }
+                }
+            }
+            TokenKind::Identifier
+                if self.type_params.contains(&token.literal) || token.literal == "Result" =>
+            {
+                not_conversion(self.lexer.next())
+            }
+            _ => false,
+        };
+        self.lexer.restore(checkpoint);
+        is_type
+    }
+    /// Whether the name at the current token is the type of a declaration, like `Meters` in
+    /// `d Meters = 1.5` or `Result` in `r Result[int, str]? = none`
+    fn named_type_ahead(&mut self) -> bool {
+        let checkpoint = self.lexer.checkpoint();
+        let mut token = self.lexer.next();
+        if token.kind == TokenKind::OpeningSquare {
+            let mut depth = 1;
+            while depth > 0 && token.kind != TokenKind::Eof {
+                token = self.lexer.next();
+                match token.kind {
+                    TokenKind::OpeningSquare => depth += 1,
+                    TokenKind::ClosingSquare => depth -= 1,
+                    _ => {}
+                }
+            }
+            token = self.lexer.next();
+        }
+        while matches!(token.kind, TokenKind::Asterisk | TokenKind::Question) {
+            token = self.lexer.next();
+        }
+        self.lexer.restore(checkpoint);
+        token.kind == TokenKind::Equal
+    }
+    /// Whether the `[` at the current token starts an array type like `[int; 3]`,
+    /// rather than indexing like `a[i]`. Array types have a `;` inside the brackets.
+    fn array_type_ahead(&mut self) -> bool {
+        let checkpoint = self.lexer.checkpoint();
+        let mut depth = 1;
+        let is_type = loop {
+            let token = self.lexer.next();
+            match token.kind {
+                TokenKind::OpeningSquare => depth += 1,
+                TokenKind::ClosingSquare if depth == 1 => break false,
+                TokenKind::ClosingSquare => depth -= 1,
+                TokenKind::SemiColon if depth == 1 => break true,
+                TokenKind::Eof => break false,
+                _ => {}
+            }
+        };
+        self.lexer.restore(checkpoint);
+        is_type
+    }
+    /// Parses a declaration of the elements of a tuple, like `a, b = divmod(7, 2)`
+    fn destructure(&mut self, first: String, start: usize) -> Expr {
+        let mut names = vec![first];
+        while self.current.kind == TokenKind::Comma {
+            self.next();
+            names.push(self.current.literal.clone());
+            self.expect(TokenKind::Identifier);
+        }
+        self.expect(TokenKind::Equal);
+        let mut value = self.parse_expr(0);
+        self.split_deref(&mut value);
+        self.semicolon();
+        Expr {
+            span: Span::from(start..value.span.end),
+            inner: ExprKind::Destructure(names, value.boxed()),
+        }
+    }
+    /// Parses a variable declaration, like `x int = 5`, after its name.
+    fn var(&mut self, ident: String, start: usize) -> Expr {
+        let ty = self.parse_type();
+        self.expect(TokenKind::Equal);
+        let expr = self.parse_expr(0);
+        Expr {
+            inner: ExprKind::Var(ident, expr.boxed(), ty),
+            span: Span::from(start..self.position),
+        }
+    }
     fn nud(&mut self, tok: &Token) -> Expr {
         match tok.kind {
             TokenKind::String => {
                 let _string = self.current.literal.clone();
                 let span = self.current.span;
                 self.next();
-                return Expr {
+                Expr {
                     inner: ExprKind::Str(_string),
                     span,
-                };
+                }
+            }
+            TokenKind::InterpolationStart => {
+                let start = self.current.span.start;
+                let mut parts = Vec::new();
+                loop {
+                    if !self.current.literal.is_empty() {
+                        parts.push(Expr {
+                            inner: ExprKind::Str(self.current.literal.clone()),
+                            span: self.current.span,
+                        });
+                    }
+                    if self.current.kind == TokenKind::InterpolationEnd {
+                        self.next();
+                        break;
+                    }
+                    self.next();
+                    if matches!(
+                        self.current.kind,
+                        TokenKind::InterpolationMiddle | TokenKind::InterpolationEnd
+                    ) {
+                        self.errors.push(Error {
+                            source: self.source.clone(),
+                            file_name: self.filename.clone(),
+                            message: "Expected an expression inside of the braces".to_owned(),
+                            span: self.current.span,
+                            help: "Use \"\\{\" to write a brace in a string".to_owned(),
+                            labels: Vec::new(),
+                        });
+                        continue;
+                    }
+                    parts.push(self.parse_expr(0));
+                    if !matches!(
+                        self.current.kind,
+                        TokenKind::InterpolationMiddle | TokenKind::InterpolationEnd
+                    ) {
+                        self.errors.push(Error {
+                            source: self.source.clone(),
+                            file_name: self.filename.clone(),
+                            message: format!(
+                                "Expected \"}}\" to close the interpolation found {}",
+                                self.current.literal
+                            ),
+                            span: self.current.span,
+                            help: "".to_owned(),
+                            labels: Vec::new(),
+                        });
+                        break;
+                    }
+                }
+                Expr {
+                    inner: ExprKind::Interpolation(parts),
+                    span: Span::from(start..self.position),
+                }
             }
             TokenKind::Int => {
                 let span = self.current.span;
-                let num = if self.current.literal.contains("x") {
-                    let mut chars = self.current.literal.chars();
-                    // Skip "0x"
-                    chars.next();
-                    chars.next();
-                    match i64::from_str_radix(chars.as_str(), 16) {
-                        Ok(i) => i,
-                        Err(err) => match err.kind() {
-                            std::num::IntErrorKind::Empty => {
-                                self.errors.push(Error {
-                                    source: self.source.clone(),
-                                    file_name: self.filename.clone(),
-                                    message: "Int parsing error: Empty.".to_owned(),
-                                    span,
-                                    help: "".to_owned(),
-                                });
-                                self.next();
-                                return Expr {
-                                    inner: ExprKind::Error,
-                                    span,
-                                };
-                            }
-                            std::num::IntErrorKind::InvalidDigit => {
-                                self.errors.push(Error {
-                                    source: self.source.clone(),
-                                    file_name: self.filename.clone(),
-                                    message: "Int parsing error: Invalid digit".to_owned(),
-                                    span,
-                                    help: "".to_owned(),
-                                });
-                                self.next();
-                                return Expr {
-                                    inner: ExprKind::Error,
-                                    span,
-                                };
-                            }
-                            std::num::IntErrorKind::PosOverflow => {
-                                self.errors.push(Error {
-                                    source: self.source.clone(),
-                                    file_name: self.filename.clone(),
-                                    message: "Int parsing error: Positive Overflow".to_owned(),
-                                    span,
-                                    help: "".to_owned(),
-                                });
-                                self.next();
-                                return Expr {
-                                    inner: ExprKind::Error,
-                                    span,
-                                };
-                            }
-                            std::num::IntErrorKind::NegOverflow => {
-                                self.errors.push(Error {
-                                    source: self.source.clone(),
-                                    file_name: self.filename.clone(),
-                                    message: "Int parsing error: Negative Overflow".to_owned(),
-                                    span,
-                                    help: "".to_owned(),
-                                });
-                                self.next();
-                                return Expr {
-                                    inner: ExprKind::Error,
-                                    span,
-                                };
-                            }
-                            std::num::IntErrorKind::Zero => {
-                                self.errors.push(Error {
-                                    source: self.source.clone(),
-                                    file_name: self.filename.clone(),
-                                    message: "Int parsing error: Zero".to_owned(),
-                                    span,
-                                    help: "".to_owned(),
-                                });
-                                self.next();
-                                return Expr {
-                                    inner: ExprKind::Error,
-                                    span,
-                                };
-                            }
-                            _ => unreachable!(),
-                        },
-                    }
-                } else {
-                    self.current.literal.parse().unwrap()
-                };
-
+                let int = number::parse_int(&self.current.literal, span.start);
                 self.next();
-                return Expr {
-                    inner: ExprKind::Int(num),
-                    span,
-                };
+                match int {
+                    Ok(int) => Expr {
+                        inner: ExprKind::Int(int),
+                        span,
+                    },
+                    Err(err) => self.number_error(err),
+                }
             }
 
             TokenKind::OpeningParen => {
+                let start = self.current.span.start;
                 self.next();
                 let expr = self.parse_expr(-1);
+                if self.current.kind != TokenKind::Comma {
+                    self.expect(TokenKind::ClosingParen);
+                    return expr;
+                }
+                // A tuple, like `(1, "one")`
+                let mut elements = vec![expr];
+                while self.current.kind == TokenKind::Comma {
+                    self.next();
+                    elements.push(self.parse_expr(-1));
+                }
                 self.expect(TokenKind::ClosingParen);
-                return expr;
+                Expr {
+                    inner: ExprKind::Tuple(elements),
+                    span: Span::from(start..self.position),
+                }
             }
 
             TokenKind::Float => {
                 let span = self.current.span;
-
-                let float: f64 = match self.current.literal.parse::<f64>() {
-                    Err(_) => {
-                        self.errors.push(Error {
-                            source: self.source.clone(),
-                            file_name: self.filename.clone(),
-                            message: "Error while parsing float.".to_owned(),
-                            span,
-                            help: "".to_owned(),
-                        });
-                        self.next();
-                        return Expr {
-                            inner: ExprKind::Error,
-                            span,
-                        };
-                    }
-                    Ok(val) => val,
-                };
+                let float = number::parse_float(&self.current.literal, span.start);
                 self.next();
-                Expr {
-                    inner: ExprKind::Float(float),
-                    span,
+                match float {
+                    Ok(float) => Expr {
+                        inner: ExprKind::Float(float),
+                        span,
+                    },
+                    Err(err) => self.number_error(err),
                 }
             }
-            TokenKind::Plus | TokenKind::Minus | TokenKind::Not => {
-                let start = self.position;
+            TokenKind::Plus | TokenKind::Minus | TokenKind::Not | TokenKind::Tilde => {
+                let start = self.current.span.start;
                 let op = self.current.kind;
                 self.next();
+                // The smallest int only fits with its sign, so it is parsed together with it,
+                // unless it has a method call or index, like `-5.abs()` which is `-(5.abs())`
+                if op == TokenKind::Minus
+                    && self.current.kind == TokenKind::Int
+                    && self.peek_lbp() <= 40
+                {
+                    let literal = self.current.literal.clone();
+                    let int = number::parse_negative_int(&literal, self.current.span.start);
+                    self.next();
+                    return match int {
+                        Ok(int) => Expr {
+                            inner: ExprKind::Int(int),
+                            span: Span::from(start..self.position),
+                        },
+                        Err(err) => self.number_error(err),
+                    };
+                }
+                let expr = self.parse_expr(40);
+                let span = Span::from(start..self.position);
+                // Negative literals are folded, so they are constants like any other literal
+                let inner = match (op, expr.inner) {
+                    (TokenKind::Minus, ExprKind::Int(int)) => ExprKind::Int(int.wrapping_neg()),
+                    (TokenKind::Minus, ExprKind::Float(float)) => ExprKind::Float(-float),
+                    (_, inner) => ExprKind::Unary(
+                        op,
+                        Expr {
+                            inner,
+                            span: expr.span,
+                        }
+                        .boxed(),
+                    ),
+                };
+                Expr { inner, span }
+            }
+            TokenKind::Asterisk => {
+                let start = self.current.span.start;
+                self.next();
                 let expr = self.parse_expr(40);
-                return Expr {
-                    inner: ExprKind::Unary(op, expr.boxed()),
+                Expr {
+                    inner: ExprKind::Deref(expr.boxed()),
                     span: Span::from(start..self.position),
-                };
+                }
             }
-            TokenKind::And => {
-                let start = self.position;
+            TokenKind::Ampersand => {
+                let start = self.current.span.start;
                 self.next();
                 let expr = self.parse_expr(40);
-                return Expr {
+                Expr {
                     inner: ExprKind::Ref(expr.boxed()),
                     span: Span::from(start..self.position),
-                };
+                }
+            }
+            TokenKind::OpeningSquare => {
+                let start = self.current.span.start;
+                self.next();
+                let mut elements = Vec::new();
+                while self.current.kind != TokenKind::ClosingSquare {
+                    elements.push(self.parse_expr(0));
+                    if self.current.kind == TokenKind::Comma {
+                        self.next();
+                    } else {
+                        break;
+                    }
+                }
+                self.expect(TokenKind::ClosingSquare);
+                Expr {
+                    inner: ExprKind::Array(elements),
+                    span: Span::from(start..self.position),
+                }
+            }
+            TokenKind::OpeningBracket => {
+                let start = self.current.span.start;
+                self.next();
+                let mut entries = Vec::new();
+                while self.current.kind != TokenKind::ClosingBracket {
+                    // Stopping at the colon, which is a binary operator
+                    let key = self.parse_expr(1);
+                    self.expect(TokenKind::Colon);
+                    let value = self.parse_expr(0);
+                    entries.push((key, value));
+                    if self.current.kind == TokenKind::Comma {
+                        self.next();
+                    } else {
+                        break;
+                    }
+                }
+                self.expect(TokenKind::ClosingBracket);
+                Expr {
+                    inner: ExprKind::Map(entries),
+                    span: Span::from(start..self.position),
+                }
+            }
+            TokenKind::VecTy => {
+                let start = self.current.span.start;
+                self.next();
+                self.expect(TokenKind::OpeningParen);
+                let elements = self.args();
+                Expr {
+                    inner: ExprKind::Vector(elements),
+                    span: Span::from(start..self.position),
+                }
+            }
+            TokenKind::IntTy | TokenKind::FloatTy | TokenKind::StringTy => {
+                let start = self.current.span.start;
+                let ty = self.parse_type();
+                self.expect(TokenKind::OpeningParen);
+                let value = self.parse_expr(0);
+                self.expect(TokenKind::ClosingParen);
+                Expr {
+                    inner: ExprKind::Convert(ty, value.boxed()),
+                    span: Span::from(start..self.position),
+                }
             }
             TokenKind::Bool(b) => {
                 let span = self.current.span;
                 self.next();
-                return Expr {
+                Expr {
                     inner: ExprKind::Bool(b),
                     span,
-                };
+                }
+            }
+            TokenKind::Fun => self.closure(),
+            TokenKind::NoneKw => {
+                let span = self.current.span;
+                self.next();
+                Expr {
+                    inner: ExprKind::None,
+                    span,
+                }
             }
             TokenKind::Identifier => {
-                let start = self.position;
+                let start = self.current.span.start;
                 let ident = self.current.literal.clone();
                 let span = self.current.span;
                 self.next();
-                match self.current.kind {
+                // A type right after a name on the same line starts a declaration, like `x int = 5`,
+                // on the next line it starts another statement, like `fun` or `int("5")`
+                let same_line = !self.source[span.end..self.current.span.start].contains('\n');
+                let kind = self.current.kind;
+                match kind {
                     TokenKind::IntTy
                     | TokenKind::FloatTy
                     | TokenKind::StringTy
-                    | TokenKind::BoolTy => {
-                        let ty = self.parse_type();
-                        self.expect(TokenKind::Equal);
-                        let expr = self.parse_expr(0);
-                        return Expr {
-                            inner: ExprKind::Var(ident, expr.boxed(), ty),
-                            span: Span::from(start + 1..self.lexer.position - 1),
-                        };
+                    | TokenKind::BoolTy
+                    | TokenKind::VecTy
+                    | TokenKind::Fun
+                        if same_line =>
+                    {
+                        self.var(ident, start)
+                    }
+                    // A named type, like `d Meters = 1.5`, is followed by `=` rather than being
+                    // an expression like `puts(x)`
+                    TokenKind::Identifier if same_line && self.named_type_ahead() => {
+                        self.var(ident, start)
+                    }
+                    TokenKind::OpeningSquare if same_line && self.array_type_ahead() => {
+                        self.var(ident, start)
+                    }
+                    TokenKind::OpeningParen if same_line && self.tuple_type_ahead() => {
+                        self.var(ident, start)
                     }
                     TokenKind::OpeningParen => {
                         self.next();
-                        let mut args = vec![];
-                        while self.current.kind != TokenKind::ClosingParen {
-                            let arg = self.parse_expr(0);
-                            args.push(arg);
-                            if self.current.kind == TokenKind::Comma {
-                                self.next();
-                                continue;
-                            } else {
-                                break;
-                            }
-                        }
-                        self.expect(TokenKind::ClosingParen);
-                        if ident == "puts".to_owned() {
+                        let args = self.args();
+                        if ident == "puts" {
                             return Expr {
                                 inner: ExprKind::Puts(args),
                                 span: Span::from(start..self.position),
                             };
                         }
-                        return Expr {
+                        Expr {
                             inner: ExprKind::FunctionCall(ident, args),
                             span: Span::from(start..self.position),
-                        };
+                        }
                     }
                     _ => Expr {
                         inner: ExprKind::Ident(ident),
@@ -550,12 +1393,13 @@ impl Parser {
                     message: "Expected expression".to_owned(),
                     span: self.current.span,
                     help: "".to_owned(),
+                    labels: Vec::new(),
                 });
                 self.next();
-                return Expr {
+                Expr {
                     inner: ExprKind::Error,
                     span: self.current.span,
-                };
+                }
             }
         }
     }
@@ -1,11 +1,5 @@
-use std::fs;
+use std::{fs, io};
 /// Function used for reading files,
-pub fn read_file(path: &str) -> String {
-    match fs::read_to_string(path) {
-        Ok(text) => text,
-        Err(err) => {
-            eprintln!("ERROR: {}", err);
-            std::process::exit(1)
-        }
-    }
+pub fn read_file(path: &str) -> io::Result<String> {
+    fs::read_to_string(path)
 }
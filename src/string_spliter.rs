@@ -1,32 +1,178 @@
-pub fn split_string(str: Vec<u8>) -> Vec<u8> {
+use std::ops::Range;
+
+use crate::token::Span;
+
+/// An escape sequence that couldn't be decoded
+pub struct EscapeError {
+    pub message: String,
+    pub help: String,
+    pub span: Span,
+}
+
+/// Decodes the escape sequences of a string's text, `offset` is the position of its first byte
+/// in the source. Every invalid escape gets an error, and is left out of the decoded text.
+pub fn split_string(str: &[u8], offset: usize) -> (Vec<u8>, Vec<EscapeError>) {
     let mut final_str: Vec<u8> = Vec::new();
+    let mut errors = Vec::new();
 
     let mut position = 0;
     while position < str.len() {
-        if str[position] == b'\\' {
+        if str[position] != b'\\' {
+            final_str.push(str[position]);
             position += 1;
-            match str.get(position) {
-                None => break,
-                Some(b'a') => final_str.push(b'\x07'),
-                Some(b'b') => final_str.push(b'\x08'),
-                Some(b't') => final_str.push(b'\t'),
-                Some(b'n') => final_str.push(b'\n'),
-                Some(b'v') => final_str.push(b'\x0b'),
-                Some(b'f') => final_str.push(b'\x0c'),
-                Some(b'r') => final_str.push(b'\r'),
-                Some(b' ') => final_str.push(b' '),
-                Some(b'\\') => final_str.push(b'\\'),
-                Some(b'x') => {
-                    while str.get(position).unwrap_or(&b'\0').is_ascii_hexdigit() {
-                        final_str.push(str[position])
+            continue;
+        }
+        let start = position;
+        position += 1;
+        let error = |message: String, help: &str, end: usize| EscapeError {
+            message,
+            help: help.to_owned(),
+            span: Span::from(offset + start..offset + end),
+        };
+        match str.get(position) {
+            None => errors.push(error(
+                "Unterminated escape sequence".to_owned(),
+                "Write \"\\\\\" for a backslash",
+                position,
+            )),
+            Some(b'a') => final_str.push(b'\x07'),
+            Some(b'b') => final_str.push(b'\x08'),
+            Some(b't') => final_str.push(b'\t'),
+            Some(b'n') => final_str.push(b'\n'),
+            Some(b'v') => final_str.push(b'\x0b'),
+            Some(b'f') => final_str.push(b'\x0c'),
+            Some(b'r') => final_str.push(b'\r'),
+            Some(b'0') => final_str.push(b'\0'),
+            Some(ch @ (b' ' | b'\\' | b'"' | b'\'' | b'{' | b'}')) => final_str.push(*ch),
+            Some(b'x') => {
+                let digits = hex_digits(&str[position + 1..]).min(2);
+                let end = position + 1 + digits;
+                match u8::from_str_radix(as_str(&str[position + 1..end]), 16) {
+                    Ok(byte) if digits == 2 && byte <= 0x7f => final_str.push(byte),
+                    Ok(_) if digits == 2 => errors.push(error(
+                        format!(
+                            "Hex escape \"{}\" is out of range",
+                            as_str(&str[start..end])
+                        ),
+                        "Hex escapes go up to \\x7F, use \\u{..} for other characters",
+                        end,
+                    )),
+                    _ => errors.push(error(
+                        "Invalid hex escape, expected two hex digits".to_owned(),
+                        "Hex escapes are written like \\x41",
+                        end,
+                    )),
+                }
+                position = end - 1;
+            }
+            Some(b'u') => {
+                if str.get(position + 1) != Some(&b'{') {
+                    errors.push(error(
+                        "Expected \"{\" after \"\\u\"".to_owned(),
+                        "Unicode escapes are written like \\u{1F600}",
+                        position + 1,
+                    ));
+                } else {
+                    let digits = hex_digits(&str[position + 2..]);
+                    let end = position + 2 + digits;
+                    if str.get(end) != Some(&b'}') {
+                        errors.push(error(
+                            "Unterminated unicode escape".to_owned(),
+                            "Unicode escapes are written like \\u{1F600}",
+                            end,
+                        ));
+                        position = end - 1;
+                    } else {
+                        let escape = as_str(&str[start..end + 1]);
+                        let ch = u32::from_str_radix(as_str(&str[position + 2..end]), 16)
+                            .ok()
+                            .filter(|_| digits <= 6)
+                            .and_then(char::from_u32);
+                        match ch {
+                            Some(ch) => {
+                                let mut buf = [0; 4];
+                                final_str.extend(ch.encode_utf8(&mut buf).as_bytes());
+                            }
+                            None if digits == 0 => errors.push(error(
+                                "Empty unicode escape".to_owned(),
+                                "Unicode escapes are written like \\u{1F600}",
+                                end + 1,
+                            )),
+                            None => errors.push(error(
+                                format!("\"{}\" is not a valid unicode character", escape),
+                                "Unicode characters go up to \\u{10FFFF}, without \\u{D800} to \\u{DFFF}",
+                                end + 1,
+                            )),
+                        }
+                        position = end;
                     }
                 }
-                Some(a) => final_str.push(*a),
             }
-        } else {
-            final_str.push(str[position]);
+            Some(ch) => {
+                let end = position + char_len(*ch).min(str.len() - position);
+                errors.push(error(
+                    format!("Unknown escape sequence \"{}\"", as_str(&str[start..end])),
+                    "Write \"\\\\\" for a backslash, or use a raw string like r\"...\"",
+                    end,
+                ));
+                position = end - 1;
+            }
         }
         position += 1;
     }
-    final_str
+    (final_str, errors)
+}
+/// Number of hex digits at the start of `str`
+fn hex_digits(str: &[u8]) -> usize {
+    str.iter().take_while(|ch| ch.is_ascii_hexdigit()).count()
+}
+/// Length in bytes of the UTF-8 character starting with `byte`
+fn char_len(byte: u8) -> usize {
+    match byte {
+        0xf0.. => 4,
+        0xe0.. => 3,
+        0xc0.. => 2,
+        _ => 1,
+    }
+}
+fn as_str(str: &[u8]) -> &str {
+    std::str::from_utf8(str).unwrap_or("")
+}
+/// Strips the indentation shared by every line of a triple quoted string, giving the part
+/// of each line that is kept. A line break right after the opening quotes is dropped, and so is
+/// a last line holding only the indentation of the closing quotes, which also counts as a line.
+pub fn dedent(str: &[u8]) -> Vec<Range<usize>> {
+    let indentation = |line: &Range<usize>| {
+        str[line.clone()]
+            .iter()
+            .take_while(|ch| matches!(ch, b' ' | b'\t'))
+            .count()
+    };
+    let blank = |line: &Range<usize>| {
+        str[line.clone()]
+            .iter()
+            .all(|ch| matches!(ch, b' ' | b'\t' | b'\r'))
+    };
+
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for line in str.split(|ch| *ch == b'\n') {
+        lines.push(start..start + line.len());
+        start += line.len() + 1;
+    }
+    if lines.len() > 1 && blank(&lines[0]) {
+        lines.remove(0);
+    }
+    let mut common = usize::MAX;
+    if lines.len() > 1 && blank(&lines[lines.len() - 1]) {
+        common = lines.pop().unwrap().len();
+    }
+    for line in lines.iter().filter(|line| !blank(line)) {
+        common = common.min(indentation(line));
+    }
+
+    lines
+        .into_iter()
+        .map(|line| line.start + common.min(indentation(&line))..line.end)
+        .collect()
 }
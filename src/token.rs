@@ -8,16 +8,22 @@ pub enum TokenKind {
     Minus,
     Not,
     Comma,
-    And,
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
+    Question,
     Colon,
+    Dot,
     Asterisk,
     Slash,
     Equal,
-    Or,
     OpeningParen,
     ClosingParen,
     OpeningBracket,
     ClosingBracket,
+    OpeningSquare,
+    ClosingSquare,
     Mod,
     GreaterThan,
     LessThan,
@@ -27,8 +33,19 @@ pub enum TokenKind {
     Illegal,
     // Multiple chars tokens
     String,
+    /// Text of a string up to its first interpolation
+    InterpolationStart,
+    /// Text of a string between two interpolations
+    InterpolationMiddle,
+    /// Text of a string after its last interpolation
+    InterpolationEnd,
     Arrow,
     EqualTo,
+    And,
+    Or,
+    ShiftLeft,
+    ShiftRight,
+    UnsignedShiftRight,
     Identifier,
     Bool(bool),
     Fun,
@@ -44,6 +61,17 @@ pub enum TokenKind {
     End,
     StringTy,
     IfKw,
+    Return,
+    Interface,
+    Impl,
+    For,
+    In,
+    Let,
+    NoneKw,
+    VecTy,
+    TypeKw,
+    Newtype,
+    Extern,
     // Eof
     Eof,
 }
@@ -56,6 +84,7 @@ impl Display for TokenKind {
             Not => write!(f, "!"),
             NotEqual => write!(f, "!="),
             Colon => write!(f, ":"),
+            Dot => write!(f, "."),
             Asterisk => write!(f, "*"),
             Comma => write!(f, ","),
             Slash => write!(f, "/"),
@@ -65,13 +94,29 @@ impl Display for TokenKind {
             Arrow => write!(f, "->"),
             StringTy => write!(f, "string type"),
             IfKw => write!(f, "if"),
+            Return => write!(f, "return"),
+            Interface => write!(f, "interface"),
+            Impl => write!(f, "impl"),
+            For => write!(f, "for"),
+            In => write!(f, "in"),
+            Let => write!(f, "let"),
+            NoneKw => write!(f, "none"),
+            VecTy => write!(f, "vec type"),
+            TypeKw => write!(f, "type"),
+            Newtype => write!(f, "newtype"),
+            Extern => write!(f, "extern"),
             Enum => write!(f, "enum"),
             Fun => write!(f, "fun"),
             End => write!(f, "end"),
             Do => write!(f, "do"),
             Equal => write!(f, "="),
-            And => write!(f, "&"),
-            Or => write!(f, "|"),
+            Ampersand => write!(f, "&"),
+            Pipe => write!(f, "|"),
+            Caret => write!(f, "^"),
+            Tilde => write!(f, "~"),
+            Question => write!(f, "?"),
+            And => write!(f, "&&"),
+            Or => write!(f, "||"),
             SemiColon => write!(f, ";"),
             Mod => write!(f, "%"),
             Bool(b) => write!(f, "{}", b),
@@ -83,10 +128,17 @@ impl Display for TokenKind {
             LessThan => write!(f, "<"),
             GreaterThanEqual => write!(f, ">="),
             LessThanEqual => write!(f, "<="),
+            ShiftLeft => write!(f, "<<"),
+            ShiftRight => write!(f, ">>"),
+            UnsignedShiftRight => write!(f, ">>>"),
             OpeningBracket => write!(f, "{{"),
             ClosingBracket => write!(f, "}}"),
+            OpeningSquare => write!(f, "["),
+            ClosingSquare => write!(f, "]"),
 
-            String => write!(f, "string"),
+            String | InterpolationStart | InterpolationMiddle | InterpolationEnd => {
+                write!(f, "string")
+            }
             Int => write!(f, "int"),
             Float => write!(f, "float"),
 
@@ -95,7 +147,7 @@ impl Display for TokenKind {
         }
     }
 }
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 /// Our own Implementation of a span, So we can use Copy derive
 pub struct Span {
     pub start: usize,
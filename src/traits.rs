@@ -1,4 +1,5 @@
 pub trait Item {
+    #[allow(dead_code)]
     fn show(&self);
     fn boxed(self) -> Box<Self>;
 }
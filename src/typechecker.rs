@@ -1,18 +1,62 @@
-use std::{collections::HashMap, hash::Hash};
+use std::collections::{HashMap, HashSet};
 
 use crate::{
-    ast::{Expr, ExprKind, Type},
+    ast::{Expr, ExprKind, MethodSig, Param, Pattern, Type, TypeParam},
     error::Error,
+    token::{Span, TokenKind},
     traits::Item,
 };
+#[derive(Clone, Debug, PartialEq)]
 pub struct FuncSig {
-    return_type: Type,
-    params_type: Vec<Type>,
+    pub type_params: Vec<TypeParam>,
+    pub return_type: Type,
+    pub params_type: Vec<Type>,
 }
+/// The variables, return type and type parameters of the function being checked,
+/// saved while checking a nested function.
+pub struct Frame {
+    variables: Vec<HashMap<String, Type>>,
+    return_type: Option<Type>,
+    type_params: Vec<TypeParam>,
+    type_args: HashMap<String, Type>,
+}
+/// Maximum number of instances of generic functions, so recursive generics
+/// that keep creating new types don't loop forever.
+const MAX_INSTANCES: usize = 1000;
+const C_TYPES_HELP: &str =
+    "C functions take ints, floats, bools, strings, pointers, and tuples of ints, floats and pointers";
 pub struct TypeChecker<'a> {
     ast: &'a Vec<Expr>,
     errors: Vec<Error>,
     sigs: HashMap<String, FuncSig>,
+    /// Parameters of every function, with their names and default values
+    params: HashMap<String, Vec<Param>>,
+    /// Interfaces and the methods they require
+    interfaces: HashMap<String, Vec<MethodSig>>,
+    /// Interfaces implemented by each type
+    impls: HashSet<(String, Type)>,
+    /// Methods of each type, `self` is replaced with the type itself
+    methods: HashMap<(Type, String), FuncSig>,
+    enums: HashMap<String, Vec<String>>,
+    /// Type aliases and the types they stand for
+    aliases: HashMap<String, Type>,
+    /// Newtypes and the types of their values
+    newtypes: HashMap<String, Type>,
+    /// C functions, and whether they take more arguments after their parameters
+    externs: HashMap<String, bool>,
+    /// Functions of the program embedding Illusio
+    hosts: HashSet<String>,
+    variables: Vec<HashMap<String, Type>>,
+    return_type: Option<Type>,
+    /// Type parameters of the function being checked
+    type_params: Vec<TypeParam>,
+    /// Concrete types of the type parameters, when checking an instance of a generic function
+    type_args: HashMap<String, Type>,
+    /// Instances of generic functions found at call sites
+    instances: HashSet<(String, Vec<Type>)>,
+    pending: Vec<(String, Vec<Type>)>,
+    /// The expression being checked
+    span: Span,
     source: String,
     filename: String,
 }
@@ -24,95 +68,1959 @@ impl<'a> TypeChecker<'a> {
             filename: filename.to_owned(),
             errors: Vec::new(),
             sigs: HashMap::new(),
+            params: HashMap::new(),
+            interfaces: HashMap::new(),
+            impls: HashSet::new(),
+            methods: HashMap::new(),
+            enums: HashMap::new(),
+            aliases: HashMap::new(),
+            newtypes: HashMap::new(),
+            externs: HashMap::new(),
+            hosts: HashSet::new(),
+            variables: vec![HashMap::new()],
+            return_type: None,
+            type_params: Vec::new(),
+            type_args: HashMap::new(),
+            instances: HashSet::new(),
+            pending: Vec::new(),
+            span: Span::from(0..0),
         }
     }
     pub fn check(&mut self) -> bool {
+        self.collect();
+        if self.nested_declarations() {
+            return true;
+        }
         for expr in self.ast.clone() {
             self.handle(&expr);
         }
+        // Generic functions are checked again with the types they are called with,
+        // unless their generic version already has errors.
+        while !self.had_errors() {
+            match self.pending.pop() {
+                Some((name, type_args)) => self.instance(&name, &type_args),
+                None => break,
+            }
+        }
         self.had_errors()
     }
+    /// Reports declarations that aren't at the top level of the file, giving whether there are some.
+    /// Only the methods of impls can be declared inside of something else.
+    fn nested_declarations(&mut self) -> bool {
+        let errors = self.errors.len();
+        let ast = self.ast;
+        for expr in ast {
+            let children = match &expr.inner {
+                ExprKind::Impl(_, _, methods) => methods
+                    .iter()
+                    .flat_map(|method| method.inner.children())
+                    .collect(),
+                inner => inner.children(),
+            };
+            for child in children {
+                self.nested_declaration(child);
+            }
+        }
+        self.errors.len() > errors
+    }
+    fn nested_declaration(&mut self, expr: &Expr) {
+        let declared = match &expr.inner {
+            ExprKind::FunctionDeclaration(..) => Some("Functions"),
+            ExprKind::Extern(..) => Some("C functions"),
+            ExprKind::Impl(..) => Some("Impls"),
+            ExprKind::Interface(..) => Some("Interfaces"),
+            ExprKind::Enum(..) => Some("Enums"),
+            ExprKind::TypeAlias(..) => Some("Type aliases"),
+            ExprKind::Newtype(..) => Some("Newtypes"),
+            _ => None,
+        };
+        if let Some(declared) = declared {
+            self.error_with_help(
+                format!(
+                    "{} declared inside of a block are not supported yet",
+                    declared
+                ),
+                expr.span,
+                "Declare it at the top level of the file".to_owned(),
+            );
+        }
+        for child in expr.inner.children() {
+            self.nested_declaration(child);
+        }
+    }
+    /// Registers every type, function, interface, enum and impl before checking,
+    /// so they can be used before their declaration.
+    fn collect(&mut self) {
+        let ast = self.ast;
+        for expr in ast {
+            match &expr.inner {
+                ExprKind::TypeAlias(name, ty) => {
+                    self.aliases.insert(name.to_owned(), ty.clone());
+                }
+                ExprKind::Newtype(name, ty) => {
+                    self.newtypes.insert(name.to_owned(), ty.clone());
+                }
+                _ => {}
+            }
+        }
+        // An alias that refers to itself would never finish expanding, it stands for an
+        // unknown type so its uses aren't reported again
+        for expr in ast {
+            if let ExprKind::TypeAlias(name, ty) = &expr.inner {
+                if self.refers_to(ty, name, &mut HashSet::new()) {
+                    self.aliases.insert(name.to_owned(), Type::Unknown);
+                    self.error(format!("Type alias `{}` refers to itself", name), expr.span);
+                }
+            }
+        }
+        for ty in self.newtypes.values_mut() {
+            *ty = expand(ty, &self.aliases);
+        }
+        for expr in ast {
+            match &expr.inner {
+                ExprKind::Interface(name, methods) => {
+                    self.interfaces.insert(name.to_owned(), methods.clone());
+                }
+                ExprKind::Enum(name, fields) => {
+                    self.enums.insert(name.to_owned(), fields.clone());
+                }
+                ExprKind::Impl(interface, ty, methods) => {
+                    let ty = &self.resolve(ty);
+                    self.impls.insert((interface.to_owned(), ty.clone()));
+                    for method in methods {
+                        if let ExprKind::FunctionDeclaration(name, _, params, return_type, _) =
+                            &method.inner
+                        {
+                            let sig = replace_self(&self.sig_of(params, return_type), ty);
+                            let key = (ty.clone(), name.to_owned());
+                            if self.methods.insert(key, sig).is_some() {
+                                self.error(
+                                    format!(
+                                        "Method `{}` is already implemented for type {}",
+                                        name, ty
+                                    ),
+                                    method.span,
+                                );
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        // Functions are collected last, since their parameters can be interfaces
+        for expr in ast {
+            match &expr.inner {
+                ExprKind::FunctionDeclaration(name, ..)
+                    if self.externs.contains_key(name) || self.hosts.contains(name) =>
+                {
+                    self.error(
+                        format!("Function `{}` is already declared", name),
+                        expr.span,
+                    );
+                }
+                ExprKind::FunctionDeclaration(name, type_params, params, return_type, _) => {
+                    let sig = self.generic_sig(type_params, params, return_type);
+                    self.sigs.insert(name.to_owned(), sig);
+                    self.params.insert(name.to_owned(), params.clone());
+                }
+                ExprKind::Extern(_, sig, variadic) => {
+                    if self.sigs.contains_key(&sig.name) {
+                        self.error(
+                            format!("Function `{}` is already declared", sig.name),
+                            expr.span,
+                        );
+                        continue;
+                    }
+                    let func_sig = self.sig_of(&sig.params, &sig.return_type);
+                    self.sigs.insert(sig.name.to_owned(), func_sig);
+                    self.externs.insert(sig.name.to_owned(), *variadic);
+                    // The arguments of variadic C functions are checked on their own
+                    if !variadic {
+                        self.params.insert(sig.name.to_owned(), sig.params.clone());
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    /// Builds the signature of a function, parameters typed with an interface
+    /// get their own type parameter bound by that interface.
+    fn generic_sig(
+        &self,
+        type_params: &[TypeParam],
+        params: &[Param],
+        return_type: &Type,
+    ) -> FuncSig {
+        let mut sig = self.sig_of(params, return_type);
+        sig.type_params = type_params.to_vec();
+        for (param, param_type) in params.iter().zip(sig.params_type.iter_mut()) {
+            if let Type::Named(interface) = param_type {
+                if self.interfaces.contains_key(interface) {
+                    let name = format!("{} of {}", interface, param.name);
+                    sig.type_params.push(TypeParam {
+                        name: name.clone(),
+                        bounds: vec![interface.clone()],
+                    });
+                    *param_type = Type::Param(name);
+                }
+            }
+        }
+        sig
+    }
     pub fn handle(&mut self, expr: &Expr) -> Type {
+        self.span = expr.span;
         match &expr.inner {
             ExprKind::Int(_) => Type::Int,
             ExprKind::Float(_) => Type::Float,
-            ExprKind::Ident(_) => {
-                Type::Int
-            }
+            ExprKind::Ident(name) => match self.get(name) {
+                Some(ty) => ty,
+                None => self.function_value(name, expr.span),
+            },
             ExprKind::Str(_) => Type::String,
-            ExprKind::Var(_, val, ty) => {
-                let ty_val = self.handle(&val);
-                if &ty_val != ty {
+            ExprKind::Interpolation(parts) => {
+                for part in parts {
+                    let ty = self.handle(part);
+                    if !unknown(&ty) && !printable(&self.underlying(&ty)) {
+                        self.error(
+                            format!("Cannot interpolate a value of type {}", ty),
+                            part.span,
+                        );
+                    }
+                }
+                Type::String
+            }
+            ExprKind::Var(name, val, written) => {
+                let ty = &self.resolve(written);
+                self.check_keys(ty, expr.span);
+                let ty_val = self.handle_expecting(val, ty);
+                if self.is_interface(ty) {
+                    self.error(
+                        format!("Interface `{}` can only be used as a parameter type", ty),
+                        expr.span,
+                    );
+                } else if &ty_val != ty && !unknown(&ty_val) && !unknown(ty) {
                     self.errors.push(Error {
                         source: self.source.clone(),
                         file_name: self.filename.clone(),
                         message: format!(
                             "Expected type {} found type {}",
-                            format!("{:?}", ty).to_lowercase(),
-                            format!("{:?}", ty_val).to_lowercase()
+                            described(written, ty),
+                            ty_val
                         ),
                         span: val.span,
-                        help: "".to_owned(),
+                        help: unwrap_help(ty, &ty_val),
+                        labels: Vec::new(),
                     })
                 }
+                self.declare(name, ty.clone());
                 Type::None
             }
-            ExprKind::Binary(lhs, _, rhs) => {
-                let lhs_ty = self.handle(&lhs);
-                let rhs_ty = self.handle(&rhs);
+            ExprKind::Binary(lhs, TokenKind::EqualTo | TokenKind::NotEqual, rhs)
+                if matches!(lhs.inner, ExprKind::None) || matches!(rhs.inner, ExprKind::None) =>
+            {
+                let value = if matches!(lhs.inner, ExprKind::None) {
+                    rhs
+                } else {
+                    lhs
+                };
+                let ty = self.handle(value);
+                if !matches!(ty, Type::Optional(_) | Type::None | Type::Unknown) {
+                    self.error(
+                        format!("Cannot compare a value of type {} to `none`", ty),
+                        expr.span,
+                    );
+                }
+                Type::Bool
+            }
+            ExprKind::Binary(lhs, op, rhs) => {
+                let lhs_ty = self.handle(lhs);
+                let rhs_ty = self.handle(rhs);
+                if unknown(&lhs_ty) || unknown(&rhs_ty) {
+                    return Type::Unknown;
+                }
                 if lhs_ty != rhs_ty {
                     self.errors.push(Error {
                         source: self.source.clone(),
                         file_name: self.filename.clone(),
                         message: "Cannot do binary operations, with different types".to_string(),
                         span: expr.span,
-                        help: "".to_owned(),
-                    })
+                        help: unwrap_help(&lhs_ty, &rhs_ty) + &unwrap_help(&rhs_ty, &lhs_ty),
+                        labels: Vec::new(),
+                    });
+                    return Type::Unknown;
+                }
+                if !supports(op, &self.underlying(&lhs_ty)) {
+                    self.error(
+                        format!("Cannot use operator `{}` on type {}", op, lhs_ty),
+                        expr.span,
+                    );
+                    return Type::Unknown;
+                }
+                match op {
+                    TokenKind::EqualTo
+                    | TokenKind::NotEqual
+                    | TokenKind::GreaterThan
+                    | TokenKind::LessThan
+                    | TokenKind::GreaterThanEqual
+                    | TokenKind::LessThanEqual => Type::Bool,
+                    // We could return lhs or rhs, doesn't matter
+                    _ => lhs_ty,
+                }
+            }
+            ExprKind::Unary(op, value) => {
+                let ty = self.handle(value);
+                if !unknown(&ty) && !supports(op, &self.underlying(&ty)) {
+                    self.error(
+                        format!("Cannot use operator `{}` on type {}", op, ty),
+                        expr.span,
+                    );
+                    return Type::Unknown;
                 }
-                // We could return lhs or rhs, doesn't matter
-                lhs_ty
+                ty
             }
-            ExprKind::Unary(_, _) => todo!(),
-            ExprKind::Ref(_) => {
-                let t = self.handle(expr);
-                Type::Ptr(t.boxed())
+            ExprKind::Ref(value) => {
+                if !matches!(value.inner, ExprKind::Ident(_)) {
+                    self.error(
+                        "Only variables can have their address taken".to_owned(),
+                        value.span,
+                    );
+                }
+                let ty = self.handle(value);
+                Type::Ptr(ty.boxed())
             }
+            ExprKind::Deref(value) => match self.handle(value) {
+                Type::Ptr(ty) => *ty,
+                Type::Unknown => Type::Unknown,
+                ty => {
+                    self.error(
+                        format!("Cannot dereference a value of type {}", ty),
+                        value.span,
+                    );
+                    Type::Unknown
+                }
+            },
             ExprKind::If(condition, exprs) => {
-                self.handle(&condition);
+                let condition_ty = self.handle(condition);
+                if condition_ty != Type::Bool && !unknown(&condition_ty) {
+                    self.error(
+                        format!(
+                            "Expected type bool found type {} in if condition",
+                            condition_ty
+                        ),
+                        condition.span,
+                    );
+                }
+                self.start_scope();
                 for expr in exprs {
                     self.handle(expr);
                 }
+                self.end_scope();
                 Type::None
             }
+            ExprKind::IfLet(pattern, value, exprs) => {
+                let ty = match (pattern, self.handle(value)) {
+                    (Pattern::Value(_), Type::Optional(inner)) => *inner,
+                    (Pattern::Ok(_), Type::Result(value, _)) => *value,
+                    (Pattern::Err(_), Type::Result(_, error)) => *error,
+                    (_, Type::Unknown) => Type::Unknown,
+                    (pattern, ty) => {
+                        let expected = match pattern {
+                            Pattern::Value(_) => "an optional",
+                            _ => "a result",
+                        };
+                        self.error(
+                            format!("Expected {} found type {} in if let", expected, ty),
+                            value.span,
+                        );
+                        Type::Unknown
+                    }
+                };
+                self.start_scope();
+                self.declare(pattern.name(), ty);
+                for expr in exprs {
+                    self.handle(expr);
+                }
+                self.end_scope();
+                Type::None
+            }
+            ExprKind::None => {
+                self.error_with_help(
+                    "Cannot infer the type of `none`".to_owned(),
+                    expr.span,
+                    "Give it a type, like `x int? = none`".to_owned(),
+                );
+                Type::Unknown
+            }
+            ExprKind::Try(value) => {
+                let (value_ty, error_ty) = match self.handle(value) {
+                    Type::Result(value, error) => (*value, *error),
+                    Type::Unknown => return Type::Unknown,
+                    ty => {
+                        self.error(
+                            format!("Cannot use `?` on a value of type {}", ty),
+                            value.span,
+                        );
+                        return Type::Unknown;
+                    }
+                };
+                // The error is returned as is, so the function has to return the same errors
+                match self.return_type.clone() {
+                    None => self.error("`?` outside of a function".to_owned(), expr.span),
+                    Some(Type::Result(_, error)) if *error == error_ty => {}
+                    Some(return_type) => self.error_with_help(
+                        format!(
+                            "Cannot use `?` in a function that returns type {}",
+                            return_type
+                        ),
+                        expr.span,
+                        format!(
+                            "`?` returns errors of type {}, so the function has to return a Result[_, {}]",
+                            error_ty,
+                            error_ty
+                        ),
+                    ),
+                }
+                value_ty
+            }
+            // Calling a closure, or a function, held in a variable
+            ExprKind::FunctionCall(name, args)
+                if matches!(self.get(name), Some(Type::Function(..))) =>
+            {
+                let (params, return_type) = match self.get(name) {
+                    Some(Type::Function(params, return_type)) => (params, return_type),
+                    _ => unreachable!(),
+                };
+                self.check_args(&params, args, expr.span);
+                *return_type
+            }
+            ExprKind::FunctionCall(name, _)
+                if (name == "ok" || name == "err") && !self.sigs.contains_key(name) =>
+            {
+                self.error_with_help(
+                    format!("Cannot infer the type of `{}(...)`", name),
+                    expr.span,
+                    format!("Give it a type, like `r Result[int, str] = {}(...)`", name),
+                );
+                Type::Unknown
+            }
             ExprKind::Enum(_, _) => Type::None,
-            ExprKind::FunctionCall(_, _) => todo!(),
-            ExprKind::FunctionDeclaration(name, params, return_type, exprs) => {
-                let mut params_type = Vec::new();
-                for param in params {
-                    params_type.push(param.param_type.clone());
+            // Named arguments of function calls are put in order before checking them
+            ExprKind::NamedArg(name, value) => {
+                self.error(
+                    format!(
+                        "Argument `{}` can only be given by name to a function",
+                        name
+                    ),
+                    expr.span,
+                );
+                self.handle(value)
+            }
+            ExprKind::TypeAlias(..) | ExprKind::Newtype(..) => Type::None,
+            ExprKind::Extern(_, sig, variadic) => {
+                self.plain_params(&sig.params, "a C function", sig.span);
+                for param in &sig.params {
+                    let ty = self.resolve(&param.param_type);
+                    if !self.c_type(&ty, *variadic) {
+                        self.error_with_help(
+                            format!("Type {} can't be given to C", param.param_type),
+                            sig.span,
+                            C_TYPES_HELP.to_owned(),
+                        );
+                    }
+                }
+                let ty = self.resolve(&sig.return_type);
+                if ty != Type::None && !self.c_type(&ty, *variadic) {
+                    self.error_with_help(
+                        format!("Type {} can't be returned from C", sig.return_type),
+                        sig.span,
+                        C_TYPES_HELP.to_owned(),
+                    );
+                }
+                Type::None
+            }
+            ExprKind::FunctionCall(name, args) if self.externs.get(name) == Some(&true) => {
+                self.variadic_call(name, args, expr.span)
+            }
+            // Making a value of a newtype from a value of its underlying type
+            ExprKind::FunctionCall(name, args)
+                if self.newtypes.contains_key(name) && !self.sigs.contains_key(name) =>
+            {
+                let underlying = self.newtypes[name].clone();
+                self.check_args(&[underlying], args, expr.span);
+                Type::Named(name.clone())
+            }
+            ExprKind::FunctionCall(name, args)
+                if name == "len" && !self.sigs.contains_key(name) =>
+            {
+                self.len(args, expr.span)
+            }
+            ExprKind::FunctionCall(name, args) => {
+                let sig = match (self.sigs.get(name), self.get(name)) {
+                    (Some(sig), _) => sig.clone(),
+                    (None, Some(ty)) => {
+                        if !unknown(&ty) {
+                            self.error(
+                                format!("Cannot call `{}`, it has type {}", name, ty),
+                                expr.span,
+                            );
+                        }
+                        return Type::Unknown;
+                    }
+                    (None, None) => {
+                        self.error(format!("Function `{}` not found", name), expr.span);
+                        return Type::Unknown;
+                    }
+                };
+                let args = match self.arguments(name, args, expr.span) {
+                    Some(args) => args,
+                    None => return Type::Unknown,
+                };
+                if sig.type_params.is_empty() {
+                    self.check_args(&sig.params_type, &args, expr.span);
+                    return sig.return_type;
+                }
+                match self.infer(name, &sig, &args, expr.span) {
+                    Some(type_args) => {
+                        if type_args.iter().all(is_concrete)
+                            && self.instances.insert((name.to_owned(), type_args.clone()))
+                        {
+                            self.pending.push((name.to_owned(), type_args.clone()));
+                        }
+                        substitute(&sig.return_type, &type_map(&sig.type_params, &type_args))
+                    }
+                    None => Type::Unknown,
+                }
+            }
+            ExprKind::MethodCall(receiver, name, args) => {
+                let receiver_ty = self.handle(receiver);
+                let sig = match self.method(&receiver_ty, name) {
+                    Some(sig) => sig,
+                    None => {
+                        if !unknown(&receiver_ty) {
+                            self.error(
+                                format!("No method `{}` found for type {}", name, receiver_ty),
+                                expr.span,
+                            );
+                        }
+                        for arg in args {
+                            self.handle(arg);
+                        }
+                        return Type::Unknown;
+                    }
+                };
+                // The first parameter is `self`, which is the receiver. A method
+                // without it was already reported where it is declared
+                let params = sig.params_type.get(1..).unwrap_or_default();
+                self.check_args(params, args, expr.span);
+                sig.return_type
+            }
+            ExprKind::FunctionDeclaration(name, _, params, _, exprs) => {
+                let sig = self.sigs[name].clone();
+                for type_param in &sig.type_params {
+                    for bound in &type_param.bounds {
+                        if !self.interfaces.contains_key(bound) {
+                            self.error(format!("Interface `{}` not found", bound), expr.span);
+                        }
+                    }
+                }
+                self.check_params(params, &sig, expr.span);
+                self.function(name, params, &sig, &[], exprs, expr.span);
+                Type::None
+            }
+            ExprKind::Closure(params, return_type, exprs) => {
+                self.plain_params(params, "a closure", expr.span);
+                let sig = FuncSig {
+                    type_params: Vec::new(),
+                    return_type: self.resolve(return_type),
+                    params_type: params.iter().map(|p| self.resolve(&p.param_type)).collect(),
+                };
+                for ty in sig.params_type.iter().chain([&sig.return_type]) {
+                    self.check_keys(ty, expr.span);
+                }
+                let frame = self.enter_closure(params, &sig);
+                for expr in exprs {
+                    self.handle(expr);
+                }
+                self.leave_function(frame);
+                if sig.return_type != Type::None && !returns(exprs) {
+                    self.error(
+                        "Closure does not return a value at its end".to_owned(),
+                        expr.span,
+                    );
+                }
+                Type::Function(sig.params_type, sig.return_type.boxed())
+            }
+            ExprKind::Interface(name, methods) => {
+                for method in methods {
+                    self.plain_params(
+                        &method.params,
+                        &format!("method `{}`", method.name),
+                        method.span,
+                    );
+                    if method.params.first().map(|p| &p.param_type) != Some(&Type::SelfType) {
+                        self.error(
+                            format!(
+                                "Method `{}` of interface `{}` must take `self` as its first parameter",
+                                method.name, name
+                            ),
+                            method.span,
+                        );
+                    }
+                }
+                Type::None
+            }
+            ExprKind::Impl(interface, ty, methods) => {
+                self.implementation(interface, ty, methods, expr.span);
+                Type::None
+            }
+            ExprKind::Return(value) => {
+                let value_ty = match value {
+                    Some(value) => {
+                        let expected = self.return_type();
+                        self.handle_expecting(value, &expected)
+                    }
+                    None => Type::None,
+                };
+                match self.return_type.clone() {
+                    None => self.error("`return` outside of a function".to_owned(), expr.span),
+                    Some(return_type) => {
+                        if value_ty != return_type && !unknown(&value_ty) && !unknown(&return_type)
+                        {
+                            self.error_with_help(
+                                format!(
+                                    "Expected return type {} found type {}",
+                                    return_type, value_ty
+                                ),
+                                expr.span,
+                                unwrap_help(&return_type, &value_ty),
+                            );
+                        }
+                    }
+                }
+                Type::None
+            }
+            ExprKind::Array(elements) => {
+                if elements.is_empty() {
+                    self.errors.push(Error {
+                        source: self.source.clone(),
+                        file_name: self.filename.clone(),
+                        message: "Cannot infer the type of an empty array".to_owned(),
+                        span: expr.span,
+                        help: "Arrays have a fixed size, so they need at least one element"
+                            .to_owned(),
+                        labels: Vec::new(),
+                    });
+                    return Type::Unknown;
+                }
+                let ty = self.elements(elements);
+                Type::Array(ty.boxed(), elements.len())
+            }
+            ExprKind::Vector(elements) => {
+                if elements.is_empty() {
+                    self.errors.push(Error {
+                        source: self.source.clone(),
+                        file_name: self.filename.clone(),
+                        message: "Cannot infer the type of an empty vector".to_owned(),
+                        span: expr.span,
+                        help: "Give it a type, like `v vec[int] = vec()`".to_owned(),
+                        labels: Vec::new(),
+                    });
+                    return Type::Unknown;
+                }
+                let ty = self.elements(elements);
+                Type::Vec(ty.boxed())
+            }
+            ExprKind::Tuple(elements) => Type::Tuple(
+                elements
+                    .iter()
+                    .map(|element| self.handle(element))
+                    .collect(),
+            ),
+            ExprKind::TupleIndex(value, index) => match self.handle(value) {
+                Type::Tuple(fields) if *index < fields.len() => fields[*index].clone(),
+                Type::Tuple(fields) => {
+                    self.error_with_help(
+                        format!("Tuple has no element {}", index),
+                        expr.span,
+                        format!("It has {} elements", fields.len()),
+                    );
+                    Type::Unknown
+                }
+                Type::Unknown => Type::Unknown,
+                ty => {
+                    self.error(
+                        format!("Cannot get element {} of a value of type {}", index, ty),
+                        value.span,
+                    );
+                    Type::Unknown
+                }
+            },
+            ExprKind::Destructure(names, value) => {
+                let fields = match self.handle(value) {
+                    Type::Tuple(fields) if fields.len() == names.len() => fields,
+                    Type::Tuple(fields) => {
+                        self.error_with_help(
+                            format!(
+                                "Expected {} names for a tuple with {} elements",
+                                fields.len(),
+                                fields.len()
+                            ),
+                            expr.span,
+                            format!("Found {} names", names.len()),
+                        );
+                        vec![]
+                    }
+                    Type::Unknown => vec![],
+                    ty => {
+                        self.error_with_help(
+                            format!("Cannot destructure a value of type {}", ty),
+                            value.span,
+                            "Only tuples can be destructured".to_owned(),
+                        );
+                        vec![]
+                    }
+                };
+                for (i, name) in names.iter().enumerate() {
+                    self.declare(name, fields.get(i).cloned().unwrap_or(Type::Unknown));
+                }
+                Type::None
+            }
+            ExprKind::For(name, second, iterable, exprs) => {
+                let iterable_ty = self.handle(iterable);
+                let (first_ty, second_ty) = match loop_types(&iterable_ty, second.is_some()) {
+                    Some(types) => types,
+                    None => {
+                        if !unknown(&iterable_ty) {
+                            self.error(
+                                format!("Cannot loop over a value of type {}", iterable_ty),
+                                iterable.span,
+                            );
+                        }
+                        (Type::Unknown, Type::Unknown)
+                    }
+                };
+                self.start_scope();
+                self.declare(name, first_ty);
+                if let Some(second) = second {
+                    self.declare(second, second_ty);
+                }
+                for expr in exprs {
+                    self.handle(expr);
+                }
+                self.end_scope();
+                Type::None
+            }
+            ExprKind::Map(entries) => {
+                if entries.is_empty() {
+                    self.errors.push(Error {
+                        source: self.source.clone(),
+                        file_name: self.filename.clone(),
+                        message: "Cannot infer the type of an empty map".to_owned(),
+                        span: expr.span,
+                        help: "Give it a type, like `m map[str, int] = {}`".to_owned(),
+                        labels: Vec::new(),
+                    });
+                    return Type::Unknown;
+                }
+                let keys: Vec<Expr> = entries.iter().map(|(key, _)| key.clone()).collect();
+                let values: Vec<Expr> = entries.iter().map(|(_, value)| value.clone()).collect();
+                let key = self.elements(&keys);
+                let value = self.elements(&values);
+                Type::Map(key.boxed(), value.boxed())
+            }
+            ExprKind::Index(value, index) => {
+                let value_ty = self.handle(value);
+                let index_ty = self.handle(index);
+                if index_ty != Type::Int && !unknown(&index_ty) {
+                    self.error(
+                        format!("Expected type int found type {} as index", index_ty),
+                        index.span,
+                    );
+                }
+                match value_ty {
+                    Type::Array(element, _) | Type::Vec(element) => *element,
+                    Type::Unknown => Type::Unknown,
+                    _ => {
+                        self.error(
+                            format!("Cannot index into a value of type {}", value_ty),
+                            value.span,
+                        );
+                        Type::Unknown
+                    }
+                }
+            }
+            ExprKind::Slice(value, start, end) => {
+                let value_ty = self.handle(value);
+                if value_ty != Type::String && !unknown(&value_ty) {
+                    self.error(
+                        format!("Cannot slice a value of type {}", value_ty),
+                        value.span,
+                    );
+                }
+                for bound in [start, end].into_iter().flatten() {
+                    let ty = self.handle(bound);
+                    if ty != Type::Int && !unknown(&ty) {
+                        self.error(
+                            format!("Expected type int found type {} as slice bound", ty),
+                            bound.span,
+                        );
+                    }
+                }
+                Type::String
+            }
+            ExprKind::Convert(ty, value) => {
+                let value_ty = self.handle(value);
+                // The value of a newtype is converted, like `int(id)`
+                let from = self.underlying(&value_ty);
+                let convertible = match ty {
+                    Type::Int => {
+                        matches!(from, Type::Int | Type::Float | Type::String | Type::Bool)
+                    }
+                    Type::Float => matches!(from, Type::Int | Type::Float | Type::String),
+                    _ => matches!(from, Type::Int | Type::Float | Type::String | Type::Bool),
+                };
+                if !convertible && !unknown(&value_ty) {
+                    self.error(
+                        format!("Cannot convert a value of type {} to {}", value_ty, ty),
+                        value.span,
+                    );
+                }
+                ty.clone()
+            }
+            ExprKind::Assign(target, value) => {
+                let target_ty = match target.inner {
+                    ExprKind::Ident(_) | ExprKind::Index(..) | ExprKind::Deref(_) => {
+                        self.handle(target)
+                    }
+                    _ => {
+                        self.error("Cannot assign to this expression".to_owned(), target.span);
+                        return Type::None;
+                    }
+                };
+                let value_ty = self.handle_expecting(value, &target_ty);
+                if value_ty != target_ty && !unknown(&target_ty) && !unknown(&value_ty) {
+                    self.error_with_help(
+                        format!("Expected type {} found type {}", target_ty, value_ty),
+                        value.span,
+                        unwrap_help(&target_ty, &value_ty),
+                    );
                 }
-                self.sigs.insert(name.to_owned(), FuncSig { return_type: return_type.clone(), params_type: params_type });
                 Type::None
             }
             ExprKind::Bool(_) => Type::Bool,
             ExprKind::Block(exprs) => {
+                self.start_scope();
                 for expr in exprs {
                     self.handle(expr);
                 }
+                self.end_scope();
                 Type::None
             }
-            ExprKind::Error => Type::None,
+            ExprKind::Error => Type::Unknown,
             ExprKind::Eof => Type::None,
-            ExprKind::Puts(_) => Type::None
+            ExprKind::Puts(args) => {
+                if args.len() != 1 {
+                    self.error(
+                        format!("`puts` takes 1 argument but {} were given", args.len()),
+                        expr.span,
+                    );
+                }
+                for arg in args {
+                    let ty = self.handle(arg);
+                    if !unknown(&ty) && !printable(&self.underlying(&ty)) {
+                        self.error(format!("Cannot print a value of type {}", ty), arg.span);
+                    }
+                }
+                Type::None
+            }
+        }
+    }
+    fn function(
+        &mut self,
+        name: &str,
+        params: &[Param],
+        sig: &FuncSig,
+        type_args: &[Type],
+        exprs: &[Expr],
+        span: Span,
+    ) {
+        let frame = self.enter_function(params, sig, type_args);
+        for ty in sig.params_type.iter().chain([&sig.return_type]) {
+            let ty = self.resolve(ty);
+            self.check_keys(&ty, span);
+        }
+        for expr in exprs {
+            self.handle(expr);
+        }
+        self.leave_function(frame);
+        if sig.return_type != Type::None && !returns(exprs) {
+            self.errors.push(Error {
+                source: self.source.clone(),
+                file_name: self.filename.clone(),
+                message: format!("Function `{}` does not return a value at its end", name),
+                span,
+                help: "Try adding a `return` at the end of the function".to_owned(),
+                labels: Vec::new(),
+            });
+        }
+    }
+    /// Checks that `methods` implement every method of `interface` for `ty`
+    /// with the same signatures, and checks the methods' bodies.
+    fn implementation(&mut self, interface: &str, ty: &Type, methods: &[Expr], span: Span) {
+        let ty = &self.resolve(ty);
+        let required = match self.interfaces.get(interface) {
+            Some(required) => required.clone(),
+            None => {
+                self.error(format!("Interface `{}` not found", interface), span);
+                return;
+            }
+        };
+        let ty_name = ty.to_string();
+        let missing: Vec<&MethodSig> = required
+            .iter()
+            .filter(|sig| {
+                !methods.iter().any(|method| {
+                    matches!(&method.inner, ExprKind::FunctionDeclaration(name, ..) if name == &sig.name)
+                })
+            })
+            .collect();
+        if !missing.is_empty() {
+            self.errors.push(Error {
+                source: self.source.clone(),
+                file_name: self.filename.clone(),
+                message: format!(
+                    "Impl of `{}` for type {} is missing methods: {}",
+                    interface,
+                    ty_name,
+                    missing
+                        .iter()
+                        .map(|sig| format!("`{}`", sig.name))
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                ),
+                span,
+                help: "".to_owned(),
+                labels: Vec::new(),
+            });
+        }
+        for method in methods {
+            let (name, params, return_type, exprs) = match &method.inner {
+                ExprKind::FunctionDeclaration(name, _, params, return_type, exprs) => {
+                    (name, params, return_type, exprs)
+                }
+                _ => continue,
+            };
+            self.plain_params(params, &format!("method `{}`", name), method.span);
+            if let Some(param) = params.iter().find(|p| self.is_interface(&p.param_type)) {
+                self.error(
+                    format!(
+                        "Parameter `{}` of method `{}` can't be an interface",
+                        param.name, name
+                    ),
+                    method.span,
+                );
+            }
+            match required.iter().find(|sig| &sig.name == name) {
+                None => self.error(
+                    format!(
+                        "Method `{}` is not a member of interface `{}`",
+                        name, interface
+                    ),
+                    method.span,
+                ),
+                Some(sig) => {
+                    if self.sig_of(&sig.params, &sig.return_type)
+                        != self.sig_of(params, return_type)
+                    {
+                        self.errors.push(Error {
+                            source: self.source.clone(),
+                            file_name: self.filename.clone(),
+                            message: format!(
+                                "Method `{}` does not match its signature in interface `{}`",
+                                name, interface
+                            ),
+                            span: method.span,
+                            help: format!("Expected `{}`", signature(sig)),
+                            labels: Vec::new(),
+                        });
+                    }
+                }
+            }
+            let sig = replace_self(&self.sig_of(params, return_type), ty);
+            self.function(name, params, &sig, &[], exprs, method.span);
+        }
+    }
+    /// Checks that the keys of the maps in `ty` can be hashed
+    fn check_keys(&mut self, ty: &Type, span: Span) {
+        match ty {
+            Type::Map(key, value) => {
+                let hashable = matches!(
+                    self.underlying(key),
+                    Type::Int | Type::String | Type::Bool | Type::Param(_)
+                ) || self.is_enum(key);
+                if !hashable {
+                    self.errors.push(Error {
+                        source: self.source.clone(),
+                        file_name: self.filename.clone(),
+                        message: format!("Type {} cannot be used as a map key", key),
+                        span,
+                        help: "Map keys can be ints, strings, bools or enums".to_owned(),
+                        labels: Vec::new(),
+                    });
+                }
+                self.check_keys(value, span);
+            }
+            Type::Ptr(inner) | Type::Optional(inner) | Type::Array(inner, _) | Type::Vec(inner) => {
+                self.check_keys(inner, span)
+            }
+            Type::Result(value, error) => {
+                self.check_keys(value, span);
+                self.check_keys(error, span);
+            }
+            Type::Tuple(fields) => {
+                for field in fields {
+                    self.check_keys(field, span);
+                }
+            }
+            Type::Function(params, return_type) => {
+                for ty in params.iter().chain([&**return_type]) {
+                    self.check_keys(ty, span);
+                }
+            }
+            _ => {}
+        }
+    }
+    /// Like `handle`, but `expected` gives the type of an empty `vec()`, `{}`, `none`,
+    /// `ok(...)` or `err(...)`, and the values that are wrapped in an optional
+    fn handle_expecting(&mut self, expr: &Expr, expected: &Type) -> Type {
+        match (&expr.inner, expected) {
+            // Elements are checked with the expected types, so they can be wrapped too
+            (ExprKind::Vector(elements), Type::Vec(element)) => {
+                self.expected_elements(elements.iter(), element);
+                expected.clone()
+            }
+            (ExprKind::Array(elements), Type::Array(element, size)) if elements.len() == *size => {
+                self.expected_elements(elements.iter(), element);
+                expected.clone()
+            }
+            (ExprKind::Map(entries), Type::Map(key, value)) => {
+                self.expected_elements(entries.iter().map(|(key, _)| key), key);
+                self.expected_elements(entries.iter().map(|(_, value)| value), value);
+                expected.clone()
+            }
+            (ExprKind::Tuple(elements), Type::Tuple(fields)) if elements.len() == fields.len() => {
+                for (element, field) in elements.iter().zip(fields) {
+                    self.expected_elements(std::iter::once(element), field);
+                }
+                expected.clone()
+            }
+            (ExprKind::None, Type::Optional(_)) => expected.clone(),
+            (ExprKind::FunctionCall(name, args), Type::Result(value, error))
+                if (name == "ok" || name == "err") && !self.sigs.contains_key(name) =>
+            {
+                if args.len() != 1 {
+                    self.error(
+                        format!("`{}` takes 1 argument but {} were given", name, args.len()),
+                        expr.span,
+                    );
+                }
+                let side = if name == "ok" { value } else { error };
+                self.expected_elements(args.iter(), side);
+                expected.clone()
+            }
+            // Values are wrapped in an optional where one is expected
+            (_, Type::Optional(inner)) => match self.handle_expecting(expr, inner) {
+                ty if ty == **inner => expected.clone(),
+                ty => ty,
+            },
+            _ => self.handle(expr),
+        }
+    }
+    /// Checks that all the elements of an array, a vector, a map or a tuple,
+    /// or the value of a result have the `expected` type
+    fn expected_elements<'e>(&mut self, elements: impl Iterator<Item = &'e Expr>, expected: &Type) {
+        for element in elements {
+            let ty = self.handle_expecting(element, expected);
+            if ty != *expected && !unknown(&ty) && !unknown(expected) {
+                self.error_with_help(
+                    format!("Expected type {} found type {}", expected, ty),
+                    element.span,
+                    unwrap_help(expected, &ty),
+                );
+            }
+        }
+    }
+    /// Checks that all the elements of an array or a vector have the same type, and gives it
+    fn elements(&mut self, elements: &[Expr]) -> Type {
+        let first = self.handle(&elements[0]);
+        for element in &elements[1..] {
+            let ty = self.handle(element);
+            if ty != first && !unknown(&ty) && !unknown(&first) {
+                self.error(
+                    format!("Expected type {} found type {}", first, ty),
+                    element.span,
+                );
+            }
+        }
+        first
+    }
+    /// The `len` builtin, which gives the number of elements of an array, a vector or a map,
+    /// or the number of bytes of a string
+    fn len(&mut self, args: &[Expr], span: Span) -> Type {
+        if args.len() != 1 {
+            self.error(
+                format!("`len` takes 1 argument but {} were given", args.len()),
+                span,
+            );
+        }
+        for arg in args {
+            let ty = self.handle(arg);
+            if !matches!(
+                ty,
+                Type::Array(..) | Type::Vec(_) | Type::Map(..) | Type::String | Type::Unknown
+            ) {
+                self.error(
+                    format!("Cannot get the length of a value of type {}", ty),
+                    arg.span,
+                );
+            }
+        }
+        Type::Int
+    }
+    /// Puts the arguments of a call to the function `name` in the order of its parameters,
+    /// the parameters that aren't given get their default values, and the remaining
+    /// arguments are put in a vector for a variadic parameter.
+    /// Gives `None` when the arguments don't match the parameters.
+    pub fn arguments(&mut self, name: &str, args: &[Expr], span: Span) -> Option<Vec<Expr>> {
+        let params = match self.params.get(name) {
+            Some(params) => params.clone(),
+            None => return Some(args.to_vec()),
+        };
+        let mut given: Vec<Option<Expr>> = vec![None; params.len()];
+        let variadic = params.last().is_some_and(|param| param.variadic);
+        let mut rest = Vec::new();
+        let mut named = false;
+        let mut ok = true;
+        for (i, arg) in args.iter().enumerate() {
+            match &arg.inner {
+                ExprKind::NamedArg(param, value) => {
+                    named = true;
+                    match params.iter().position(|p| &p.name == param) {
+                        Some(i) if given[i].is_none() => given[i] = Some(*value.clone()),
+                        Some(_) => {
+                            self.error(format!("Parameter `{}` is given twice", param), arg.span);
+                            ok = false;
+                        }
+                        None => {
+                            self.error(
+                                format!("Function `{}` has no parameter `{}`", name, param),
+                                arg.span,
+                            );
+                            ok = false;
+                        }
+                    }
+                }
+                _ if named => {
+                    self.error(
+                        "Positional arguments have to come before named ones".to_owned(),
+                        arg.span,
+                    );
+                    ok = false;
+                }
+                _ if variadic && i + 1 >= params.len() => rest.push(arg.clone()),
+                _ if i < params.len() => given[i] = Some(arg.clone()),
+                _ => {
+                    self.error(
+                        format!("Expected {} arguments found {}", params.len(), args.len()),
+                        span,
+                    );
+                    return None;
+                }
+            }
+        }
+        // A vector can also be given by name for a variadic parameter, like `sum(numbers: v)`
+        if variadic {
+            let last = params.len() - 1;
+            if given[last].is_none() {
+                let span = match (rest.first(), rest.last()) {
+                    (Some(first), Some(last)) => Span::from(first.span.start..last.span.end),
+                    _ => span,
+                };
+                given[last] = Some(Expr {
+                    inner: ExprKind::Vector(rest),
+                    span,
+                });
+            } else if let Some(first) = rest.first() {
+                self.error(
+                    format!("Parameter `{}` is given twice", params[last].name),
+                    first.span,
+                );
+                ok = false;
+            }
+        }
+        if !ok {
+            return None;
+        }
+        let missing: Vec<String> = given
+            .iter()
+            .zip(&params)
+            .filter(|(arg, param)| arg.is_none() && param.default.is_none())
+            .map(|(_, param)| format!("`{}`", param.name))
+            .collect();
+        if !missing.is_empty() {
+            let what = if missing.len() == 1 {
+                "argument for parameter"
+            } else {
+                "arguments for parameters"
+            };
+            self.error(format!("Missing {} {}", what, missing.join(", ")), span);
+            return None;
+        }
+        Some(
+            given
+                .into_iter()
+                .zip(params)
+                .map(|(arg, param)| arg.or(param.default).unwrap())
+                .collect(),
+        )
+    }
+    /// Checks that the default values of `params` have the types of the parameters in `sig`,
+    /// they are used in the callers, so they can't use any variable.
+    /// Only the last parameter can be variadic, and it doesn't have a default value.
+    fn check_params(&mut self, params: &[Param], sig: &FuncSig, span: Span) {
+        if let Some(param) = params.iter().rev().skip(1).find(|param| param.variadic) {
+            self.error(
+                format!("Variadic parameter `{}` has to be the last one", param.name),
+                span,
+            );
+        }
+        let empty = FuncSig {
+            type_params: Vec::new(),
+            return_type: Type::None,
+            params_type: Vec::new(),
+        };
+        let frame = self.enter_function(&[], &empty, &[]);
+        for (param, ty) in params.iter().zip(&sig.params_type) {
+            let default = match &param.default {
+                Some(default) => default,
+                None => continue,
+            };
+            if param.variadic {
+                self.error(
+                    format!(
+                        "Variadic parameter `{}` can't have a default value",
+                        param.name
+                    ),
+                    default.span,
+                );
+                continue;
+            }
+            let default_ty = self.handle_expecting(default, ty);
+            if default_ty != *ty && !unknown(&default_ty) {
+                self.error(
+                    format!(
+                        "Expected type {} found type {} as the default of `{}`",
+                        ty, default_ty, param.name
+                    ),
+                    default.span,
+                );
+            }
         }
+        self.leave_function(frame);
     }
-    pub fn finish(&self) {
-        for err in &self.errors {
-            err.show();
+    /// Reports default values and variadic parameters, which only functions can have
+    fn plain_params(&mut self, params: &[Param], of: &str, span: Span) {
+        for param in params {
+            if let Some(default) = &param.default {
+                self.error(
+                    format!(
+                        "Parameter `{}` of {} can't have a default value",
+                        param.name, of
+                    ),
+                    default.span,
+                );
+            }
+            if param.variadic {
+                self.error(
+                    format!("Parameter `{}` of {} can't be variadic", param.name, of),
+                    span,
+                );
+            }
+        }
+    }
+    /// Checks a call to the variadic C function `name`, the arguments after its parameters
+    /// can be of any type C can take on its own
+    fn variadic_call(&mut self, name: &str, args: &[Expr], span: Span) -> Type {
+        let sig = self.sigs[name].clone();
+        let fixed = sig.params_type.len();
+        if args.len() < fixed {
+            self.error(
+                format!("Expected at least {} arguments found {}", fixed, args.len()),
+                span,
+            );
+            return sig.return_type;
+        }
+        self.check_args(&sig.params_type, &args[..fixed], span);
+        for arg in &args[fixed..] {
+            let ty = self.handle(arg);
+            if !unknown(&ty) && !self.c_type(&ty, true) {
+                self.error_with_help(
+                    format!("Type {} can't be given to C", ty),
+                    arg.span,
+                    C_TYPES_HELP.to_owned(),
+                );
+            }
+        }
+        sig.return_type
+    }
+    /// Whether values of type `ty` can be given to C or returned from it. Tuples are given
+    /// as structs, which is only supported on x86-64 and not to variadic functions.
+    fn c_type(&self, ty: &Type, variadic: bool) -> bool {
+        match self.underlying(ty) {
+            Type::Int | Type::Float | Type::Bool | Type::String | Type::Ptr(_) => true,
+            Type::Tuple(fields) => {
+                !variadic
+                    && cfg!(all(target_arch = "x86_64", unix))
+                    && fields.iter().all(|field| {
+                        matches!(
+                            self.underlying(field),
+                            Type::Int | Type::Float | Type::Ptr(_)
+                        )
+                    })
+            }
+            _ => false,
+        }
+    }
+    fn check_args(&mut self, params: &[Type], args: &[Expr], span: Span) {
+        if params.len() != args.len() {
+            self.error(
+                format!("Expected {} arguments found {}", params.len(), args.len()),
+                span,
+            );
+        }
+        for (param, arg) in params.iter().zip(args) {
+            let arg_ty = self.handle_expecting(arg, param);
+            if *param != arg_ty && !unknown(&arg_ty) && !unknown(param) {
+                self.error_with_help(
+                    format!("Expected type {} found type {}", param, arg_ty),
+                    arg.span,
+                    unwrap_help(param, &arg_ty),
+                );
+            }
+        }
+    }
+    /// Infers the type arguments of a call to the generic function `name`,
+    /// from the types of its arguments.
+    pub fn infer(
+        &mut self,
+        name: &str,
+        sig: &FuncSig,
+        args: &[Expr],
+        span: Span,
+    ) -> Option<Vec<Type>> {
+        if sig.params_type.len() != args.len() {
+            self.error(
+                format!(
+                    "Expected {} arguments found {}",
+                    sig.params_type.len(),
+                    args.len()
+                ),
+                span,
+            );
+            return None;
+        }
+        let mut ok = true;
+        // Type arguments can't be inferred from arguments with errors
+        let mut poisoned = false;
+        let mut map = HashMap::new();
+        // Empty vectors, like the arguments of a variadic parameter when there are none,
+        // get their type from the other arguments
+        let (empty, others): (Vec<_>, Vec<_>) = sig.params_type.iter().zip(args).partition(
+            |(_, arg)| matches!(&arg.inner, ExprKind::Vector(elements) if elements.is_empty()),
+        );
+        for (param, arg) in others.into_iter().chain(empty) {
+            let expected = substitute(param, &map);
+            let arg_ty = if is_concrete(&expected) {
+                self.handle_expecting(arg, &expected)
+            } else {
+                self.handle(arg)
+            };
+            if unknown(&arg_ty) {
+                poisoned = true;
+            } else if !unify(param, &arg_ty, &mut map) {
+                self.error(
+                    format!(
+                        "Expected type {} found type {}",
+                        substitute(param, &map),
+                        arg_ty
+                    ),
+                    arg.span,
+                );
+                ok = false;
+            }
+        }
+        if poisoned {
+            return None;
+        }
+        let mut type_args = Vec::new();
+        for type_param in &sig.type_params {
+            let ty = match map.get(&type_param.name) {
+                Some(ty) => ty.clone(),
+                None => {
+                    self.error(
+                        format!(
+                            "Cannot infer type parameter `{}` of function `{}`",
+                            type_param.name, name
+                        ),
+                        span,
+                    );
+                    ok = false;
+                    continue;
+                }
+            };
+            for bound in &type_param.bounds {
+                if !self.implements(&ty, bound) {
+                    // Pointing at the argument that decided the type, if there is one
+                    let arg_span = sig
+                        .params_type
+                        .iter()
+                        .zip(args)
+                        .find(|(param, _)| **param == Type::Param(type_param.name.clone()))
+                        .map_or(span, |(_, arg)| arg.span);
+                    self.error(
+                        format!("Type {} does not implement interface `{}`", ty, bound),
+                        arg_span,
+                    );
+                    ok = false;
+                }
+            }
+            type_args.push(ty);
+        }
+        if ok {
+            Some(type_args)
+        } else {
+            None
         }
     }
+    /// Checks the body of a generic function again, with concrete types.
+    fn instance(&mut self, name: &str, type_args: &[Type]) {
+        if self.instances.len() > MAX_INSTANCES {
+            self.error(
+                format!(
+                    "Too many instances of generic functions, while instantiating `{}`",
+                    name
+                ),
+                Span::from(0..0),
+            );
+            return;
+        }
+        let ast = self.ast;
+        for expr in ast {
+            if let ExprKind::FunctionDeclaration(n, _, params, _, exprs) = &expr.inner {
+                if n == name {
+                    let sig = self.sigs[name].clone();
+                    let errors = self.errors.len();
+                    self.function(name, params, &sig, type_args, exprs, expr.span);
+                    let types: Vec<String> = type_args.iter().map(|ty| ty.to_string()).collect();
+                    for error in &mut self.errors[errors..] {
+                        if error.help.is_empty() {
+                            error.help = format!(
+                                "In `{}` called with type arguments [{}]",
+                                name,
+                                types.join(", ")
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+    /// Whether `ty` implements `interface`, type parameters implement the interfaces they are bound by.
+    fn implements(&self, ty: &Type, interface: &str) -> bool {
+        match ty {
+            Type::Param(name) => self
+                .type_params
+                .iter()
+                .any(|param| &param.name == name && param.bounds.iter().any(|b| b == interface)),
+            _ => self.impls.contains(&(interface.to_owned(), ty.clone())),
+        }
+    }
+    /// Replaces the type parameters in `ty` with the types of the instance being checked,
+    /// and type aliases with the types they stand for.
+    pub fn resolve(&self, ty: &Type) -> Type {
+        expand(&substitute(ty, &self.type_args), &self.aliases)
+    }
+    /// The type of the values of a newtype, other types are their own underlying type.
+    pub fn underlying(&self, ty: &Type) -> Type {
+        match ty {
+            Type::Named(name) => self
+                .newtypes
+                .get(name)
+                .cloned()
+                .unwrap_or_else(|| ty.clone()),
+            _ => ty.clone(),
+        }
+    }
+    pub fn is_newtype(&self, ty: &Type) -> bool {
+        matches!(ty, Type::Named(name) if self.newtypes.contains_key(name))
+    }
+    /// Whether expanding `ty` leads back to the alias `name`
+    fn refers_to(&self, ty: &Type, name: &str, seen: &mut HashSet<String>) -> bool {
+        let mut found = false;
+        map_type(ty, &mut |ty| {
+            if let Type::Named(alias) = ty {
+                if alias == name {
+                    found = true;
+                } else if seen.insert(alias.clone()) {
+                    if let Some(aliased) = self.aliases.get(alias) {
+                        found |= self.refers_to(aliased, name, seen);
+                    }
+                }
+            }
+            None
+        });
+        found
+    }
+    /// The signature of a function with `params` and `return_type`, without type aliases
+    fn sig_of(&self, params: &[Param], return_type: &Type) -> FuncSig {
+        FuncSig {
+            type_params: Vec::new(),
+            return_type: expand(return_type, &self.aliases),
+            params_type: params
+                .iter()
+                .map(|p| expand(&p.param_type, &self.aliases))
+                .collect(),
+        }
+    }
+    /// Looks up the signature of method `name` for values of type `ty`,
+    /// methods of type parameters come from the interfaces bounding them.
+    pub fn method(&self, ty: &Type, name: &str) -> Option<FuncSig> {
+        match ty {
+            Type::Param(param) => self
+                .type_params
+                .iter()
+                .filter(|type_param| &type_param.name == param)
+                .flat_map(|type_param| type_param.bounds.iter())
+                .filter_map(|bound| self.interfaces.get(bound))
+                .flatten()
+                .find(|sig| sig.name == name)
+                .map(|sig| replace_self(&self.sig_of(&sig.params, &sig.return_type), ty)),
+            Type::Vec(element) => vec_method(ty, element, name),
+            Type::Map(key, value) => map_method(ty, key, value, name),
+            _ => self.methods.get(&(ty.clone(), name.to_owned())).cloned(),
+        }
+    }
+    pub fn sig(&self, name: &str) -> Option<&FuncSig> {
+        self.sigs.get(name)
+    }
+    pub fn is_extern(&self, name: &str) -> bool {
+        self.externs.contains_key(name)
+    }
+    /// Declares a function of the program embedding Illusio, before checking
+    pub fn declare_host(&mut self, name: &str, params: Vec<Type>, return_type: Type) {
+        let sig = FuncSig {
+            type_params: Vec::new(),
+            return_type,
+            params_type: params,
+        };
+        self.sigs.insert(name.to_owned(), sig);
+        self.hosts.insert(name.to_owned());
+    }
+    pub fn is_host(&self, name: &str) -> bool {
+        self.hosts.contains(name)
+    }
+    pub fn is_interface(&self, ty: &Type) -> bool {
+        matches!(ty, Type::Named(name) if self.interfaces.contains_key(name))
+    }
+    pub fn is_enum(&self, ty: &Type) -> bool {
+        matches!(ty, Type::Named(name) if self.enums.contains_key(name))
+    }
+    /// Starts checking a function body, the current scopes are saved in the returned frame.
+    /// `params` gives the names of the parameters, and `sig` their types.
+    /// When `type_args` isn't empty, the type parameters of `sig` are replaced with them.
+    pub fn enter_function(&mut self, params: &[Param], sig: &FuncSig, type_args: &[Type]) -> Frame {
+        let type_args = if type_args.is_empty() {
+            HashMap::new()
+        } else {
+            type_map(&sig.type_params, type_args)
+        };
+        let mut scope = HashMap::new();
+        for (param, ty) in params.iter().zip(&sig.params_type) {
+            scope.insert(param.name.clone(), substitute(ty, &type_args));
+        }
+        Frame {
+            variables: std::mem::replace(&mut self.variables, vec![scope]),
+            return_type: self
+                .return_type
+                .replace(substitute(&sig.return_type, &type_args)),
+            type_params: std::mem::replace(&mut self.type_params, sig.type_params.clone()),
+            type_args: std::mem::replace(&mut self.type_args, type_args),
+        }
+    }
+    /// Starts checking the body of a closure, which sees the variables around it.
+    /// The types of `sig` should already be resolved.
+    pub fn enter_closure(&mut self, params: &[Param], sig: &FuncSig) -> Frame {
+        let mut variables = self.variables.clone();
+        variables.push(
+            params
+                .iter()
+                .zip(&sig.params_type)
+                .map(|(param, ty)| (param.name.clone(), ty.clone()))
+                .collect(),
+        );
+        Frame {
+            variables: std::mem::replace(&mut self.variables, variables),
+            return_type: self.return_type.replace(sig.return_type.clone()),
+            type_params: self.type_params.clone(),
+            type_args: self.type_args.clone(),
+        }
+    }
+    pub fn leave_function(&mut self, frame: Frame) {
+        self.variables = frame.variables;
+        self.return_type = frame.return_type;
+        self.type_params = frame.type_params;
+        self.type_args = frame.type_args;
+    }
+    /// The return type of the function being checked
+    pub fn return_type(&self) -> Type {
+        self.return_type.clone().unwrap_or(Type::None)
+    }
+    pub fn start_scope(&mut self) {
+        self.variables.push(HashMap::new());
+    }
+    pub fn end_scope(&mut self) {
+        self.variables.pop();
+    }
+    pub fn declare(&mut self, name: &str, ty: Type) {
+        self.variables
+            .last_mut()
+            .unwrap()
+            .insert(name.to_owned(), ty);
+    }
+    /// The type of the function `name` used as a value, generic functions can't be
+    fn function_value(&mut self, name: &str, span: Span) -> Type {
+        if self.externs.get(name) == Some(&true) {
+            self.error(
+                format!("Variadic C function `{}` can't be used as a value", name),
+                span,
+            );
+            return Type::Unknown;
+        }
+        match self.sigs.get(name) {
+            Some(sig) if sig.type_params.is_empty() => {
+                Type::Function(sig.params_type.clone(), sig.return_type.clone().boxed())
+            }
+            Some(_) => {
+                self.error_with_help(
+                    format!("Generic function `{}` can't be used as a value", name),
+                    span,
+                    "Wrap it in a closure that calls it with concrete types".to_owned(),
+                );
+                Type::Unknown
+            }
+            None => Type::None,
+        }
+    }
+    /// The type of the variable `name`
+    pub fn get(&self, name: &str) -> Option<Type> {
+        self.variables
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .cloned()
+    }
+    pub fn error(&mut self, message: String, span: Span) {
+        self.error_with_help(message, span, "".to_owned())
+    }
+    fn error_with_help(&mut self, message: String, span: Span, help: String) {
+        self.errors.push(Error {
+            source: self.source.clone(),
+            file_name: self.filename.clone(),
+            message,
+            span,
+            help,
+            labels: Vec::new(),
+        })
+    }
+    pub fn errors(&self) -> &[Error] {
+        &self.errors
+    }
+    /// The last expression checked, a bug in the checker is reported there
+    pub fn span(&self) -> Span {
+        self.span
+    }
 
     pub fn had_errors(&self) -> bool {
-        return !self.errors.is_empty();
+        !self.errors.is_empty()
+    }
+}
+/// Whether the body of a function ends with a `return`
+fn returns(exprs: &[Expr]) -> bool {
+    matches!(
+        exprs.last(),
+        Some(Expr {
+            inner: ExprKind::Return(_),
+            ..
+        })
+    )
+}
+fn replace_self(sig: &FuncSig, ty: &Type) -> FuncSig {
+    FuncSig {
+        type_params: sig.type_params.clone(),
+        return_type: replace_self_type(&sig.return_type, ty),
+        params_type: sig
+            .params_type
+            .iter()
+            .map(|param| replace_self_type(param, ty))
+            .collect(),
+    }
+}
+/// Formats a method signature the way it is written, like `fun show(self) : str`
+fn signature(sig: &MethodSig) -> String {
+    let params: Vec<String> = sig
+        .params
+        .iter()
+        .map(|param| match param.param_type {
+            Type::SelfType => param.name.clone(),
+            _ => format!("{} {}", param.name, param.param_type),
+        })
+        .collect();
+    match sig.return_type {
+        Type::None => format!("fun {}({})", sig.name, params.join(", ")),
+        _ => format!(
+            "fun {}({}) : {}",
+            sig.name,
+            params.join(", "),
+            sig.return_type
+        ),
+    }
+}
+/// Signatures of the builtin methods of vectors
+fn vec_method(ty: &Type, element: &Type, name: &str) -> Option<FuncSig> {
+    let (params_type, return_type) = match name {
+        "push" => (vec![ty.clone(), element.clone()], Type::None),
+        "pop" => (vec![ty.clone()], element.clone()),
+        _ => return None,
+    };
+    Some(FuncSig {
+        type_params: Vec::new(),
+        return_type,
+        params_type,
+    })
+}
+/// Signatures of the builtin methods of maps
+fn map_method(ty: &Type, key: &Type, value: &Type, name: &str) -> Option<FuncSig> {
+    let (params_type, return_type) = match name {
+        "get" => (vec![ty.clone(), key.clone()], value.clone()),
+        "set" => (vec![ty.clone(), key.clone(), value.clone()], Type::None),
+        "remove" => (vec![ty.clone(), key.clone()], Type::None),
+        "contains" => (vec![ty.clone(), key.clone()], Type::Bool),
+        _ => return None,
+    };
+    Some(FuncSig {
+        type_params: Vec::new(),
+        return_type,
+        params_type,
+    })
+}
+/// The types of the loop variables when looping over a value of type `ty`, with one or two names.
+/// Maps give their keys and values, arrays and vectors their elements,
+/// or their indexes and elements with two names.
+pub fn loop_types(ty: &Type, two_names: bool) -> Option<(Type, Type)> {
+    match ty {
+        Type::Array(element, _) | Type::Vec(element) if two_names => {
+            Some((Type::Int, *element.clone()))
+        }
+        Type::Array(element, _) | Type::Vec(element) => Some((*element.clone(), Type::None)),
+        Type::Map(key, value) => Some((*key.clone(), *value.clone())),
+        _ => None,
+    }
+}
+/// Shows a type as it was written, and what it stands for when that differs
+fn described(written: &Type, ty: &Type) -> String {
+    if written == ty {
+        ty.to_string()
+    } else {
+        format!("{} ({})", written, ty)
+    }
+}
+/// Suggests unwrapping a value of type `found` when it is an optional of `expected`
+fn unwrap_help(expected: &Type, found: &Type) -> String {
+    match found {
+        Type::Optional(inner) if **inner == *expected => {
+            "The value can be missing, check it with `if let value = ...` first".to_owned()
+        }
+        _ => "".to_owned(),
+    }
+}
+/// Whether a value of type `ty` can be printed by `puts` or interpolated in a string
+fn printable(ty: &Type) -> bool {
+    matches!(
+        ty,
+        Type::String | Type::Int | Type::Float | Type::Bool | Type::Param(_)
+    )
+}
+/// Replaces `self` in a type with the implementing type
+fn replace_self_type(ty: &Type, with: &Type) -> Type {
+    map_type(ty, &mut |ty| match ty {
+        Type::SelfType => Some(with.clone()),
+        _ => None,
+    })
+}
+/// Replaces type aliases in a type with the types they stand for
+fn expand(ty: &Type, aliases: &HashMap<String, Type>) -> Type {
+    map_type(ty, &mut |ty| match ty {
+        Type::Named(name) => aliases.get(name).map(|aliased| expand(aliased, aliases)),
+        _ => None,
+    })
+}
+/// Rebuilds `ty`, replacing the types for which `f` gives a new type
+fn map_type(ty: &Type, f: &mut impl FnMut(&Type) -> Option<Type>) -> Type {
+    if let Some(new) = f(ty) {
+        return new;
+    }
+    let mut map = |ty: &Type| Box::new(map_type(ty, f));
+    match ty {
+        Type::Ptr(inner) => Type::Ptr(map(inner)),
+        Type::Optional(inner) => Type::Optional(map(inner)),
+        Type::Result(value, error) => {
+            let value = map(value);
+            Type::Result(value, map(error))
+        }
+        Type::Array(inner, size) => Type::Array(map(inner), *size),
+        Type::Tuple(fields) => Type::Tuple(fields.iter().map(|field| *map(field)).collect()),
+        Type::Function(params, return_type) => {
+            let params = params.iter().map(|param| *map(param)).collect();
+            Type::Function(params, map(return_type))
+        }
+        Type::Vec(inner) => Type::Vec(map(inner)),
+        Type::Map(key, value) => {
+            let key = map(key);
+            Type::Map(key, map(value))
+        }
+        _ => ty.clone(),
+    }
+}
+/// Maps the names of `type_params` to `type_args`
+pub fn type_map(type_params: &[TypeParam], type_args: &[Type]) -> HashMap<String, Type> {
+    type_params
+        .iter()
+        .map(|param| param.name.clone())
+        .zip(type_args.iter().cloned())
+        .collect()
+}
+/// Replaces the type parameters in `ty` using `map`
+pub fn substitute(ty: &Type, map: &HashMap<String, Type>) -> Type {
+    map_type(ty, &mut |ty| match ty {
+        Type::Param(name) => map.get(name).cloned(),
+        _ => None,
+    })
+}
+/// Matches the type of a parameter with the type of an argument,
+/// recording the types of the type parameters in `map`.
+fn unify(param: &Type, arg: &Type, map: &mut HashMap<String, Type>) -> bool {
+    match (param, arg) {
+        (Type::Param(name), _) => match map.get(name) {
+            Some(ty) => ty == arg,
+            None => {
+                map.insert(name.clone(), arg.clone());
+                true
+            }
+        },
+        (Type::Ptr(param), Type::Ptr(arg))
+        | (Type::Optional(param), Type::Optional(arg))
+        | (Type::Vec(param), Type::Vec(arg)) => unify(param, arg, map),
+        (Type::Map(param_key, param_value), Type::Map(arg_key, arg_value))
+        | (Type::Result(param_key, param_value), Type::Result(arg_key, arg_value)) => {
+            unify(param_key, arg_key, map) && unify(param_value, arg_value, map)
+        }
+        (Type::Array(param, param_size), Type::Array(arg, arg_size)) => {
+            param_size == arg_size && unify(param, arg, map)
+        }
+        (Type::Tuple(params), Type::Tuple(args)) => {
+            params.len() == args.len()
+                && params
+                    .iter()
+                    .zip(args)
+                    .all(|(param, arg)| unify(param, arg, map))
+        }
+        (Type::Function(params, param_return), Type::Function(args, arg_return)) => {
+            params.len() == args.len()
+                && params
+                    .iter()
+                    .zip(args)
+                    .all(|(param, arg)| unify(param, arg, map))
+                && unify(param_return, arg_return, map)
+        }
+        _ => param == arg,
+    }
+}
+/// Whether errors were already reported about a value of type `ty`,
+/// nothing more is reported about such values
+fn unknown(ty: &Type) -> bool {
+    match ty {
+        Type::Unknown => true,
+        Type::Ptr(inner) | Type::Optional(inner) | Type::Array(inner, _) | Type::Vec(inner) => {
+            unknown(inner)
+        }
+        Type::Map(key, value) | Type::Result(key, value) => unknown(key) || unknown(value),
+        Type::Tuple(fields) => fields.iter().any(unknown),
+        Type::Function(params, return_type) => params.iter().any(unknown) || unknown(return_type),
+        _ => false,
+    }
+}
+/// Whether a type has no type parameters left in it
+fn is_concrete(ty: &Type) -> bool {
+    match ty {
+        Type::Param(_) => false,
+        Type::Ptr(inner) | Type::Optional(inner) | Type::Array(inner, _) | Type::Vec(inner) => {
+            is_concrete(inner)
+        }
+        Type::Map(key, value) | Type::Result(key, value) => is_concrete(key) && is_concrete(value),
+        Type::Tuple(fields) => fields.iter().all(is_concrete),
+        Type::Function(params, return_type) => {
+            params.iter().all(is_concrete) && is_concrete(return_type)
+        }
+        _ => true,
+    }
+}
+/// Whether operator `op` can be used with values of type `ty`,
+/// type parameters are checked again once their type is known.
+fn supports(op: &TokenKind, ty: &Type) -> bool {
+    match op {
+        // Strings are concatenated and compared by their contents
+        TokenKind::Plus
+        | TokenKind::GreaterThan
+        | TokenKind::LessThan
+        | TokenKind::GreaterThanEqual
+        | TokenKind::LessThanEqual => {
+            matches!(ty, Type::Int | Type::Float | Type::String | Type::Param(_))
+        }
+        TokenKind::Minus | TokenKind::Asterisk | TokenKind::Slash => {
+            matches!(ty, Type::Int | Type::Float | Type::Param(_))
+        }
+        TokenKind::Mod => matches!(ty, Type::Int | Type::Param(_)),
+        TokenKind::Ampersand
+        | TokenKind::Pipe
+        | TokenKind::Caret
+        | TokenKind::Tilde
+        | TokenKind::ShiftLeft
+        | TokenKind::ShiftRight
+        | TokenKind::UnsignedShiftRight => *ty == Type::Int,
+        TokenKind::Not | TokenKind::And | TokenKind::Or => *ty == Type::Bool,
+        // Optionals are compared with `none`, their values have to be unwrapped first.
+        // Tuples and collections have to be compared element by element.
+        TokenKind::EqualTo | TokenKind::NotEqual => !matches!(
+            ty,
+            Type::Optional(_)
+                | Type::Result(..)
+                | Type::Tuple(_)
+                | Type::Function(..)
+                | Type::Array(..)
+                | Type::Vec(_)
+                | Type::Map(..)
+        ),
+        _ => false,
     }
 }
@@ -0,0 +1,52 @@
+//! Default values of parameters and arguments given by name
+mod common;
+use common::{compile_errors, example, output};
+
+#[test]
+fn defaults_can_come_before_the_return_type() {
+    let source = "fun greet name str, greeting str = \"hi\" : str do\n\
+                  \x20   return \"{greeting} {name}\"\nend\n\
+                  puts(greet(\"Ada\"))\nputs(greet(\"Ken\", greeting: \"hey\"))";
+    assert_eq!(output(source), "hi Ada\nhey Ken\n");
+}
+
+#[test]
+fn arguments_example() {
+    assert_eq!(
+        example("arguments"),
+        "Hello, Ada!\nHi, Grace!\nHello, Linus?\nHello, Ken.\n3.000000\n1.500000\n6\n0\n30\n"
+    );
+}
+
+#[test]
+fn parameters_without_defaults_can_be_given_by_name() {
+    let source = "fun f(a int = 1, b int) do\nputs(a - b)\nend\nf(b: 2)\nf(5, 3)\nf(b: 1, a: 4)";
+    assert_eq!(output(source), "-1\n2\n3\n");
+}
+
+#[test]
+fn bad_defaults_are_reported() {
+    assert_eq!(
+        compile_errors("fun f(a int = \"s\") do\nend"),
+        ["Expected type int found type str as the default of `a`"]
+    );
+}
+
+#[test]
+fn arguments_are_matched_with_parameters() {
+    let f = "fun f(a int, b int = 2) do\nend\n";
+    let errors = |call: &str| compile_errors(&format!("{}{}", f, call));
+    assert_eq!(errors("f(b: 1)"), ["Missing argument for parameter `a`"]);
+    assert_eq!(errors("f(1, c: 1)"), ["Function `f` has no parameter `c`"]);
+    assert_eq!(errors("f(1, a: 1)"), ["Parameter `a` is given twice"]);
+    assert_eq!(errors("f(1, b: 1, b: 2)"), ["Parameter `b` is given twice"]);
+    assert_eq!(
+        errors("f(a: 1, 2)"),
+        ["Positional arguments have to come before named ones"]
+    );
+    assert_eq!(errors("f(1, 2, 3)"), ["Expected 2 arguments found 3"]);
+    assert_eq!(
+        errors("f(1, b: \"s\")"),
+        ["Expected type int found type str"]
+    );
+}
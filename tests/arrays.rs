@@ -0,0 +1,48 @@
+//! Fixed-size arrays, whose indexing is checked while the program runs
+mod common;
+use common::{compile_errors, output, run_file, runtime_error};
+
+#[test]
+fn arrays_example() {
+    let run = run_file(concat!(env!("CARGO_MANIFEST_DIR"), "/examples/arrays.is"));
+    assert_eq!(run.stdout, "16\n4\n");
+    assert!(run
+        .stderr
+        .starts_with("error: Index out of bounds: the length is 4 but the index is 4"));
+    assert!(run.stderr.contains("examples/arrays.is:15:6"));
+    assert_eq!(run.code, 101);
+}
+
+#[test]
+fn arrays_are_indexed_and_iterated() {
+    let source = "a [int; 3] = [1, 2, 3]\na[1] = 5\nfor x in a\n    puts(x)\nend\nputs(len(a))";
+    assert_eq!(output(source), "1\n5\n3\n3\n");
+}
+
+#[test]
+fn negative_indexes_are_out_of_bounds() {
+    assert_eq!(
+        runtime_error("a [int; 2] = [1, 2]\ni int = -1\nputs(a[i])"),
+        "Index out of bounds: the length is 2 but the index is -1"
+    );
+}
+
+#[test]
+fn sizes_and_elements_are_checked() {
+    assert_eq!(
+        compile_errors("a [int; 2] = [1, 2, 3]"),
+        ["Expected type [int; 2] found type [int; 3]"]
+    );
+    assert_eq!(
+        compile_errors("a [int; 2] = [1, \"x\"]"),
+        ["Expected type int found type str"]
+    );
+    assert_eq!(
+        compile_errors("a [int; 2] = [1, 2]\nputs(a[\"x\"])"),
+        ["Expected type int found type str as index"]
+    );
+    assert_eq!(
+        compile_errors("a [int; 2] = [1, 2]\na[0] = \"x\""),
+        ["Expected type int found type str"]
+    );
+}
@@ -0,0 +1,44 @@
+//! Bitwise and shift operators on ints
+mod common;
+use common::{compile_errors, example, output};
+
+#[test]
+fn bits_example() {
+    assert_eq!(
+        example("bits"),
+        "4\n7\n9\n-7\nsecond bit is set\n16\n-8\n15\n"
+    );
+}
+
+#[test]
+fn operators_have_the_precedence_of_c() {
+    let source = "puts(1 | 2 ^ 3 & 4)\nputs(6 & 3 == 2)\nputs(1 + 1 << 2)\n\
+                  x int = 6\np int* = &x\nputs(*p & 3)";
+    assert_eq!(output(source), "3\ntrue\n8\n2\n");
+}
+
+#[test]
+fn right_shifts_are_arithmetic_or_logical() {
+    let source = "puts(-16 >> 2)\nputs(-16 >>> 60)\nputs(16 >>> 2)";
+    assert_eq!(output(source), "-4\n15\n4\n");
+}
+
+#[test]
+fn operands_are_ints() {
+    assert_eq!(
+        compile_errors("puts(1.5 & 2.0)"),
+        ["Cannot use operator `&` on type float"]
+    );
+    assert_eq!(
+        compile_errors("puts(~true)"),
+        ["Cannot use operator `~` on type bool"]
+    );
+    assert_eq!(
+        compile_errors("puts(\"a\" | \"b\")"),
+        ["Cannot use operator `|` on type str"]
+    );
+    assert_eq!(
+        compile_errors("puts(1 << 1.0)"),
+        ["Cannot do binary operations, with different types"]
+    );
+}
@@ -0,0 +1,65 @@
+//! The command line, which runs a file or prints what a stage of the compiler gives for it
+mod common;
+use common::{cli, example, run};
+
+const USAGE: &str = "USAGE: illusio [--emit tokens|ast|ir] <file>\n";
+
+fn example_path() -> String {
+    format!("{}/examples/example.is", env!("CARGO_MANIFEST_DIR"))
+}
+
+#[test]
+fn runs_a_file() {
+    assert_eq!(example("example"), "Hi\n");
+}
+
+#[test]
+fn emits_the_stages() {
+    let path = example_path();
+    let tokens = cli(&["--emit", "tokens", &path]);
+    assert_eq!(tokens.code, 0);
+    assert_eq!(
+        tokens.stdout,
+        "Identifier@0..4 \"puts\"\nOpeningParen@4..5 \"(\"\n\
+         String@6..10 \"Hi\"\nClosingParen@11..12 \")\"\n"
+    );
+    let ast = cli(&["--emit", "ast", &path]);
+    assert_eq!(ast.code, 0);
+    assert!(ast.stdout.contains("Puts("));
+    let ir = cli(&["--emit", "ir", &path]);
+    assert_eq!(ir.code, 0);
+    assert!(ir.stdout.starts_with("; main\nfunction u0:0() -> i64"));
+}
+
+#[test]
+fn invalid_arguments_print_the_usage() {
+    let path = example_path();
+    for args in [
+        &[][..],
+        &["--emit", "bogus", &path],
+        &["--emit", &path],
+        &[&path, &path],
+    ] {
+        let run = cli(args);
+        assert_eq!(run.code, 1, "{:?}", args);
+        assert_eq!(run.stderr, USAGE);
+        assert_eq!(run.stdout, "");
+    }
+}
+
+#[test]
+fn missing_files_are_reported() {
+    let run = cli(&["/no/such/file.is"]);
+    assert_eq!(run.code, 1);
+    assert!(run.stderr.starts_with("ERROR: "));
+}
+
+#[test]
+fn errors_set_the_exit_code() {
+    let compile = run("puts(");
+    assert_eq!(compile.code, 1);
+    assert!(compile.stderr.contains("error: Expected expression"));
+    let runtime = run("a [int; 2] = [1, 2]\ni int = 2\nputs(a[i])");
+    assert_eq!(runtime.code, 101);
+    assert!(runtime.stderr.starts_with("error: "));
+}
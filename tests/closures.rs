@@ -0,0 +1,41 @@
+//! Anonymous functions, which capture the variables they use
+mod common;
+use common::{compile_errors, example, ir, output};
+
+#[test]
+fn closures_example() {
+    assert_eq!(example("closures"), "1\n4\n9\n16\n25\n3\n4\n5\n2\n");
+}
+
+#[test]
+fn closures_capture_copies_of_variables() {
+    let source = "fun adder(n int) : fun(int) : int do\n    return fun(x int) : int do\n        return x + n\n    end\nend\n\
+                  add2 fun(int) : int = adder(2)\nadd5 fun(int) : int = adder(5)\nputs(add2(1))\nputs(add5(1))\n\
+                  count int = 0\ninc fun() = fun() do\n    count = count + 1\nend\ninc()\nputs(count)";
+    assert_eq!(output(source), "3\n6\n0\n");
+    assert!(ir(source).contains("call_indirect"));
+}
+
+#[test]
+fn function_types_are_checked() {
+    let identity = "f fun(int) : int = fun(x int) : int do\n    return x\nend\n";
+    assert_eq!(
+        compile_errors(&format!("{identity}puts(f(\"a\"))")),
+        ["Expected type int found type str"]
+    );
+    assert_eq!(
+        compile_errors("f fun(int) : int = fun(x str) : int do\n    return 1\nend"),
+        ["Expected type fun(int) : int found type fun(str) : int"]
+    );
+    assert_eq!(
+        compile_errors("x int = 1\nx(2)"),
+        ["Cannot call `x`, it has type int"]
+    );
+}
+
+#[test]
+fn functions_can_follow_a_name() {
+    let source = "g int = 0\ngp int* = &g\nfun f(x int) : int do\n    return x\nend\n\
+                  y int = f(2)\nx int = y\nint(\"5\")\nputs(x)";
+    assert_eq!(output(source), "2\n");
+}
@@ -0,0 +1,100 @@
+//! Helpers for running Illusio code in tests
+#![allow(dead_code)]
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use illusio_2::{Engine, EngineError};
+
+/// What the command line printed for a program, and how it exited
+pub struct Run {
+    pub stdout: String,
+    /// Without the colors of the diagnostics
+    pub stderr: String,
+    pub code: i32,
+}
+/// Runs the file at `path` with the command line
+pub fn run_file(path: &str) -> Run {
+    cli(&[path])
+}
+/// Runs the command line with `args`
+pub fn cli(args: &[&str]) -> Run {
+    let output = Command::new(env!("CARGO_BIN_EXE_illusio-2"))
+        .args(args)
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    Run {
+        stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        stderr: strip_colors(&stderr),
+        code: output.status.code().unwrap_or(-1),
+    }
+}
+/// Runs `source` with the command line, from a file of its own
+pub fn run(source: &str) -> Run {
+    static FILES: AtomicUsize = AtomicUsize::new(0);
+    let mut path = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+    path.push(format!(
+        "{}-{}.is",
+        std::process::id(),
+        FILES.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::write(&path, source).unwrap();
+    let run = run_file(path.to_str().unwrap());
+    std::fs::remove_file(&path).unwrap();
+    run
+}
+/// What `source` prints, it has to run without errors
+pub fn output(source: &str) -> String {
+    let run = run(source);
+    assert_eq!(run.code, 0, "The program failed:\n{}", run.stderr);
+    run.stdout
+}
+/// What the example `name` in the examples directory prints, it has to run without errors
+pub fn example(name: &str) -> String {
+    let run = run_file(&format!(
+        "{}/examples/{}.is",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    ));
+    assert_eq!(run.code, 0, "The example failed:\n{}", run.stderr);
+    run.stdout
+}
+/// The Cranelift IR of `source`
+pub fn ir(source: &str) -> String {
+    let engine = Engine::new();
+    let ast = engine.parse(source).unwrap();
+    let analysis = engine.analyze(source, &ast).unwrap();
+    let checked = engine.check(source, &ast).unwrap();
+    let lowered = engine.lower(source, analysis, checked).unwrap();
+    engine.emit(&lowered).unwrap()
+}
+/// The messages of the errors that stop `source` from compiling
+pub fn compile_errors(source: &str) -> Vec<String> {
+    match Engine::new().compile(source) {
+        Err(EngineError::Compile(errors)) => errors.into_iter().map(|err| err.message).collect(),
+        Err(err) => panic!("Expected errors in the code, found {:?}", err),
+        Ok(_) => panic!("Expected errors in the code, it compiled"),
+    }
+}
+/// The message of the error that stops `source` while it runs
+pub fn runtime_error(source: &str) -> String {
+    match Engine::new().run(source) {
+        Err(EngineError::Runtime(err)) => err.message,
+        Err(err) => panic!("Expected a runtime error, found {:?}", err),
+        Ok(()) => panic!("Expected a runtime error, it ran"),
+    }
+}
+fn strip_colors(text: &str) -> String {
+    let mut plain = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // An escape sequence ends with a letter, like `\x1b[1m`
+            chars.by_ref().find(|c| c.is_ascii_alphabetic());
+        } else {
+            plain.push(c);
+        }
+    }
+    plain
+}
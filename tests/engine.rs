@@ -0,0 +1,210 @@
+//! Compiling and running Illusio code through the embedding API
+use std::cell::Cell;
+use std::rc::Rc;
+
+use illusio_2::error::Error;
+use illusio_2::token::Span;
+use illusio_2::{Engine, EngineError};
+
+/// The messages of the errors that stop `source` from compiling
+fn compile_errors(source: &str) -> Vec<String> {
+    match Engine::new().compile(source) {
+        Err(EngineError::Compile(errors)) => errors.into_iter().map(|err| err.message).collect(),
+        Err(err) => panic!("Expected errors in the code, found {:?}", err),
+        Ok(_) => panic!("Expected errors in the code, it compiled"),
+    }
+}
+/// The error that stops `source` while it runs
+fn runtime_error(source: &str) -> Error {
+    match Engine::new().run(source) {
+        Err(EngineError::Runtime(err)) => *err,
+        Err(err) => panic!("Expected a runtime error, found {:?}", err),
+        Ok(()) => panic!("Expected a runtime error, it ran"),
+    }
+}
+/// The span of the first occurrence of `text` in `source`
+fn span_of(source: &str, text: &str) -> Span {
+    let start = source.find(text).unwrap();
+    Span::from(start..start + text.len())
+}
+
+#[test]
+fn host_functions_are_called() {
+    let mut engine = Engine::new();
+    engine.register_fn("twice", |n: i64| n * 2);
+    engine.register_fn("shout", |s: String| s.to_uppercase());
+    let n: i64 = engine.eval("twice(21)").unwrap();
+    assert_eq!(n, 42);
+    let s: String = engine.eval("shout(\"hi\") + \"!\"").unwrap();
+    assert_eq!(s, "HI!");
+}
+
+#[test]
+fn host_functions_can_keep_state() {
+    let calls = Rc::new(Cell::new(0));
+    let mut engine = Engine::new();
+    let counter = calls.clone();
+    engine.register_fn("tick", move || counter.set(counter.get() + 1));
+    engine.run("tick()\ntick()\ntick()").unwrap();
+    assert_eq!(calls.get(), 3);
+}
+
+#[test]
+fn strings_are_borrowed_from_the_compiled_program() {
+    let engine = Engine::new();
+    let compiled = engine.compile("\"to\" + \"gether\"").unwrap();
+    let first: &str = compiled.eval().unwrap();
+    let second: &str = compiled.eval().unwrap();
+    assert_eq!(first, "together");
+    assert_eq!(second, "together");
+}
+
+#[test]
+fn eval_checks_the_type_of_the_result() {
+    let err = Engine::new().eval::<i64>("\"text\"").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "The code ends with a value of type str, not int"
+    );
+    let err = Engine::new().eval::<i64>("puts(1)").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "The code doesn't end with a value of type int"
+    );
+}
+
+#[test]
+fn runtime_errors_stop_the_program() {
+    let source = "a [int; 2] = [1, 2]\ni int = 5\nputs(a[i])";
+    let err = runtime_error(source);
+    assert_eq!(
+        err.message,
+        "Index out of bounds: the length is 2 but the index is 5"
+    );
+    assert_eq!(err.span, span_of(source, "a[i]"));
+}
+
+#[test]
+fn host_panics_are_runtime_errors() {
+    let mut engine = Engine::new();
+    engine.register_fn("boom", || -> i64 { panic!("no more") });
+    let source = "puts(boom())";
+    match engine.run(source) {
+        Err(EngineError::Runtime(err)) => {
+            assert_eq!(err.message, "Host function `boom` panicked: no more");
+            assert_eq!(err.span, span_of(source, "boom()"));
+        }
+        other => panic!("Expected a runtime error, found {:?}", other),
+    }
+    // The engine still works after a program stopped
+    engine.register_fn("fine", || 1i64);
+    assert_eq!(engine.eval::<i64>("fine()").unwrap(), 1);
+}
+
+#[test]
+fn division_is_signed() {
+    let engine = Engine::new();
+    assert_eq!(engine.eval::<i64>("a int = -7\na / 2").unwrap(), -3);
+    assert_eq!(engine.eval::<i64>("a int = 7\na / -2").unwrap(), -3);
+    assert_eq!(engine.eval::<i64>("a int = -7\na % 3").unwrap(), -1);
+    assert_eq!(engine.eval::<i64>("a int = -8\na / -2").unwrap(), 4);
+    let min = "m int = -9223372036854775807 - 1\n";
+    assert_eq!(engine.eval::<i64>(&format!("{min}m % -1")).unwrap(), 0);
+}
+
+#[test]
+fn division_errors_are_reported_at_the_division() {
+    let source = "x int = 0\nputs(7 / x)";
+    let err = runtime_error(source);
+    assert_eq!(err.message, "Attempt to divide by zero");
+    assert_eq!(err.span, span_of(source, "7 / x"));
+
+    let source = "x int = 0\nputs(7 % x)";
+    assert_eq!(runtime_error(source).message, "Attempt to divide by zero");
+
+    let source = "m int = -9223372036854775807 - 1\nputs(m / -1)";
+    let err = runtime_error(source);
+    assert_eq!(err.message, "Attempt to divide with overflow");
+    assert_eq!(err.span, span_of(source, "m / -1"));
+}
+
+#[test]
+fn logical_operators_short_circuit() {
+    let calls = Rc::new(Cell::new(0));
+    let mut engine = Engine::new();
+    let counter = calls.clone();
+    engine.register_fn("yes", move || {
+        counter.set(counter.get() + 1);
+        true
+    });
+    assert!(!engine.eval::<bool>("false && yes()").unwrap());
+    assert!(engine.eval::<bool>("true || yes()").unwrap());
+    assert_eq!(calls.get(), 0);
+    assert!(engine.eval::<bool>("true && yes()").unwrap());
+    assert!(engine.eval::<bool>("false || yes()").unwrap());
+    assert_eq!(calls.get(), 2);
+}
+
+#[test]
+fn collections_cannot_be_compared() {
+    assert_eq!(
+        compile_errors("a [int; 2] = [1, 2]\nputs(a == a)"),
+        ["Cannot use operator `==` on type [int; 2]"]
+    );
+    assert_eq!(
+        compile_errors("v vec[int] = vec(1)\nputs(v != v)"),
+        ["Cannot use operator `!=` on type vec[int]"]
+    );
+    assert_eq!(
+        compile_errors("m map[str, int] = {\"a\": 1}\nputs(m == m)"),
+        ["Cannot use operator `==` on type map[str, int]"]
+    );
+}
+
+#[test]
+fn operators_are_checked_before_lowering() {
+    assert_eq!(
+        compile_errors("puts(1 && 2)"),
+        ["Cannot use operator `&&` on type int"]
+    );
+    assert_eq!(
+        compile_errors("puts(1.5 % 2.0)"),
+        ["Cannot use operator `%` on type float"]
+    );
+}
+
+#[test]
+fn a_missing_type_is_an_error() {
+    assert_eq!(compile_errors("fun f(x *) do\nend"), ["Expected type"]);
+}
+
+#[test]
+fn errors_do_not_cascade() {
+    assert_eq!(
+        compile_errors("x int? = 1\nputs(x + 1)"),
+        ["Cannot do binary operations, with different types"]
+    );
+    assert_eq!(
+        compile_errors("y int = nope(2) * 3\nputs(nope(1))"),
+        ["Function `nope` not found", "Function `nope` not found"]
+    );
+}
+
+#[test]
+fn methods_without_self_are_reported() {
+    let source = "interface Show\n    fun show() : str\nend\n\
+                  impl Show for int\n    fun show() : str do\n        return \"x\"\n    end\nend\n\
+                  puts(5.show())";
+    assert_eq!(
+        compile_errors(source),
+        ["Method `show` of interface `Show` must take `self` as its first parameter"]
+    );
+}
+
+#[test]
+fn bad_defaults_are_reported_once() {
+    assert_eq!(
+        compile_errors("fun f(n int = nope()) : int do\n    return n\nend"),
+        ["Function `nope` not found"]
+    );
+}
@@ -0,0 +1,58 @@
+//! Code the compiler can't take is reported as errors, never as a crash
+mod common;
+use common::compile_errors;
+
+#[test]
+fn nested_declarations_are_not_supported_yet() {
+    let cases = [
+        ("fun g() do\nend", "Functions"),
+        ("extern fun strlen(s str) : int", "C functions"),
+        ("type A = int", "Type aliases"),
+        ("newtype A = int", "Newtypes"),
+    ];
+    for (declaration, declared) in cases {
+        let in_function = format!("fun f() do\n{}\nend", declaration);
+        let in_block = format!("if true\n{}\nend", declaration);
+        for source in [in_function, in_block] {
+            assert_eq!(
+                compile_errors(&source),
+                [format!(
+                    "{} declared inside of a block are not supported yet",
+                    declared
+                )]
+            );
+        }
+    }
+}
+
+#[test]
+fn broken_code_gives_errors() {
+    let sources = [
+        "puts(",
+        "fun",
+        "fun f(",
+        "x int =",
+        "}}}{{{",
+        "impl do\nend",
+        "a = b",
+        "return 1",
+        "for x in 1 do\nend",
+        "puts(undefined_fn(1))",
+        "x int = 1\nx()",
+        "v vec[int] = vec()\nv.nope()",
+        "m map[int, int] = {}\nputs(m[1][2])",
+        "t (int, int) = (1, 2)\nputs(t.5)",
+        "p int* = &1",
+        "puts(*1)",
+        "x int? = none\nputs(x + 1)",
+        "r Result[int, str] = 1\nputs(r?)",
+        "interface I do\nfun m(self)\nend\nimpl I for int do\nend",
+        "puts(\"{\")",
+        "puts(\"\\u{zz}\")",
+        "x int = 99999999999999999999",
+        "x int = 0b",
+    ];
+    for source in sources {
+        assert!(!compile_errors(source).is_empty(), "{}", source);
+    }
+}
@@ -0,0 +1,80 @@
+//! References to local variables can't outlive the variables
+mod common;
+use common::{compile_errors, output};
+use illusio_2::error::Error;
+use illusio_2::token::Span;
+use illusio_2::{Engine, EngineError};
+
+/// The error that stops `source` from compiling, there has to be one
+fn escape_error(source: &str) -> Error {
+    match Engine::new().compile(source) {
+        Err(EngineError::Compile(mut errors)) if errors.len() == 1 => errors.remove(0),
+        Err(err) => panic!("Expected an error in the code, found {:?}", err),
+        Ok(_) => panic!("Expected an error in the code, it compiled"),
+    }
+}
+/// The span of the first occurrence of `text` in `source`
+fn span_of(source: &str, text: &str) -> Span {
+    let start = source.find(text).unwrap();
+    Span::from(start..start + text.len())
+}
+
+#[test]
+fn references_that_stay_in_their_scope_are_allowed() {
+    let source = "fun first(p int*) : int* do\n    return p\nend\nx int = 1\nputs(*first(&x))\n\
+                  globals vec[int*] = vec(&x)\nputs(*globals[0])";
+    assert_eq!(output(source), "1\n1\n");
+}
+
+#[test]
+fn returned_references_show_the_borrow() {
+    let source = "fun f() : int* do\n    a int = 1\n    b int* = &a\n    return b\nend";
+    let err = escape_error(source);
+    assert_eq!(
+        err.message,
+        "Cannot return a reference to the local variable `a`"
+    );
+    let returned = source.rfind('b').unwrap();
+    assert_eq!(err.span, Span::from(returned..returned + 1));
+    assert_eq!(
+        err.labels,
+        [(span_of(source, "&a"), "`a` is borrowed here".to_owned())]
+    );
+
+    let source = "fun f() : int* do\n    x int = 1\n    return &x\nend";
+    let err = escape_error(source);
+    assert_eq!(
+        err.message,
+        "Cannot return a reference to the local variable `x`"
+    );
+    assert_eq!(err.span, span_of(source, "&x"));
+    assert!(err.labels.is_empty());
+}
+
+#[test]
+fn references_cannot_outlive_their_block() {
+    let source = "y int = 0\np int* = &y\nif true\n    x int = 1\n    p = &x\nend";
+    assert_eq!(compile_errors(source), ["`x` doesn't live as long as `p`"]);
+    let source = "fun f(out int**) do\n    x int = 1\n    *out = &x\nend";
+    assert_eq!(
+        compile_errors(source),
+        ["Cannot store a reference to `x` through this pointer"]
+    );
+}
+
+#[test]
+fn references_cannot_be_stored_on_the_heap() {
+    let stores = [
+        "v vec[int*] = vec(&x)",
+        "a [int*; 1] = [&x]",
+        "m map[int, int*] = {1: &x}",
+        "v vec[int*] = vec()\n    v.push(&x)",
+    ];
+    for store in stores {
+        let source = format!("fun f() do\n    x int = 1\n    {}\nend", store);
+        assert_eq!(
+            compile_errors(&source),
+            ["Cannot store a reference to the local variable `x` on the heap"]
+        );
+    }
+}
@@ -0,0 +1,67 @@
+//! Calling C functions declared with `extern`
+mod common;
+use common::{compile_errors, example, output, run};
+
+use illusio_2::{Engine, EngineError};
+
+/// The message of the error from linking `source` with the C functions it declares
+fn link_error(source: &str) -> String {
+    match Engine::new().compile(source) {
+        Err(EngineError::Link(message)) => message,
+        Err(err) => panic!("Expected a link error, found {:?}", err),
+        Ok(_) => panic!("Expected a link error, it compiled"),
+    }
+}
+
+#[test]
+fn ffi_example() {
+    assert_eq!(
+        example("ffi"),
+        "5\n42\n1.000000\n1024.000000\nhello has 5 letters\n0.540\n0\n"
+    );
+}
+
+#[test]
+fn c_functions_are_called_with_c_types() {
+    let source = "extern fun strlen(s str) : int\nextern fun labs(n int) : int\n\
+                  extern \"libm.so.6\" fun floor(x float) : float\n\
+                  puts(strlen(\"hey\"))\nputs(labs(-3))\nputs(floor(2.5))";
+    assert_eq!(output(source), "3\n3\n2.000000\n");
+}
+
+#[test]
+fn c_functions_only_take_c_types() {
+    assert_eq!(
+        compile_errors("extern fun f(v vec[int]) : int"),
+        ["Type vec[int] can't be given to C"]
+    );
+    assert_eq!(
+        compile_errors("extern fun f(x int) : vec[int]"),
+        ["Type vec[int] can't be returned from C"]
+    );
+    assert_eq!(
+        compile_errors("extern fun f(x int = 1) : int"),
+        ["Parameter `x` of a C function can't have a default value"]
+    );
+    assert_eq!(
+        compile_errors("extern fun strlen(s str) : int\nputs(strlen(1))"),
+        ["Expected type str found type int"]
+    );
+}
+
+#[test]
+fn missing_functions_and_libraries_are_reported() {
+    assert_eq!(
+        link_error("extern fun no_such_function_here(x int) : int\nno_such_function_here(1)"),
+        "Cannot find C function `no_such_function_here` in the process"
+    );
+    assert!(
+        link_error("extern \"libnotthere.so\" fun cos(x float) : float\ncos(1.0)")
+            .starts_with("Cannot load library `libnotthere.so`")
+    );
+    let run = run("extern fun no_such_function_here() : int\nno_such_function_here()");
+    assert_ne!(run.code, 0);
+    assert!(run
+        .stderr
+        .contains("Cannot find C function `no_such_function_here`"));
+}
@@ -0,0 +1,47 @@
+//! Generic functions, compiled once for every set of types they are called with
+mod common;
+use common::{compile_errors, example, ir, output};
+
+#[test]
+fn generics_example() {
+    assert_eq!(example("generics"), "9\n2.500000\nfirst\n");
+}
+
+#[test]
+fn each_instantiation_is_compiled_once() {
+    let source =
+        "fun max[T](a T, b T) : T do\n    if a > b\n        return a\n    end\n    return b\nend\n\
+                  puts(max(1, 2))\nputs(max(4, 3))\nputs(max(\"a\", \"b\"))";
+    assert_eq!(output(source), "2\n4\nb\n");
+    let ir = ir(source);
+    assert_eq!(ir.matches("; max[int]\n").count(), 1);
+    assert_eq!(ir.matches("; max[str]\n").count(), 1);
+}
+
+#[test]
+fn type_parameters_are_bounded_by_interfaces() {
+    let show = "interface Show\n    fun show(self) : str\nend\n\
+                fun display[T: Show](x T) do\n    puts(x.show())\nend\n";
+    let source = format!(
+        "{show}impl Show for int\n    fun show(self) : str do\n        return \"int {{self}}\"\n    end\nend\n\
+         display(1)"
+    );
+    assert_eq!(output(&source), "int 1\n");
+    assert_eq!(
+        compile_errors(&format!("{show}display(1.5)")),
+        ["Type float does not implement interface `Show`"]
+    );
+}
+
+#[test]
+fn type_parameters_take_one_type() {
+    let max = "fun max[T](a T, b T) : T do\n    return a\nend\n";
+    assert_eq!(
+        compile_errors(&format!("{max}puts(max(1, 2.5))")),
+        ["Expected type int found type float"]
+    );
+    assert_eq!(
+        compile_errors(&format!("{max}f fun(int, int) : int = max")),
+        ["Generic function `max` can't be used as a value"]
+    );
+}
@@ -0,0 +1,35 @@
+//! Interfaces, and functions that take any type implementing one
+mod common;
+use common::{compile_errors, example};
+
+const SHOW: &str = "interface Show\nfun show(self) : str\nend\n";
+
+#[test]
+fn interfaces_example() {
+    assert_eq!(example("interfaces"), "a small int\na big int\na float\n");
+}
+
+#[test]
+fn impls_are_checked_against_their_interface() {
+    let errors = |source: &str| compile_errors(&format!("{}{}", SHOW, source));
+    assert_eq!(
+        errors("impl Show for int\nend"),
+        ["Impl of `Show` for type int is missing methods: `show`"]
+    );
+    assert_eq!(
+        errors("impl Show for int\nfun show(self) : int do\nreturn 1\nend\nend"),
+        ["Method `show` does not match its signature in interface `Show`"]
+    );
+    assert_eq!(
+        errors("fun d(x Show) do\nputs(x.show())\nend\nd(1)"),
+        ["Type int does not implement interface `Show`"]
+    );
+    assert_eq!(
+        errors("x Show = 1"),
+        ["Interface `Show` can only be used as a parameter type"]
+    );
+    assert_eq!(
+        compile_errors("impl Nope for int\nend"),
+        ["Interface `Nope` not found"]
+    );
+}
@@ -0,0 +1,53 @@
+//! Built-in hash maps
+mod common;
+use common::{compile_errors, example, output, runtime_error};
+
+#[test]
+fn maps_example() {
+    assert_eq!(example("maps"), "1\nto\n2\nbe\n2\nor\n1\nnot\n1\n");
+}
+
+#[test]
+fn map_is_only_a_type_where_a_type_is_expected() {
+    let source = "fun map(v vec[int], f fun(int) : int) : vec[int] do\n\
+                  \x20   out vec[int] = vec()\n    for x in v\n        out.push(f(x))\n    end\n\
+                  \x20   return out\nend\n\
+                  doubled vec[int] = map(vec(1, 2), fun(x int) : int do\n    return x * 2\nend)\n\
+                  puts(doubled[1])\n\
+                  ages map[str, int] = {\"ada\": 36}\npair (map[str, int], int) = (ages, 2)\n\
+                  puts(len(pair.0))\nmap int = 5\nputs(map)";
+    assert_eq!(output(source), "4\n1\n5\n");
+}
+
+#[test]
+fn keys_can_be_ints_strings_bools_or_enums() {
+    let source =
+        "enum Color\n    Red, Green\nend\ncolors map[Color, int] = {}\nputs(len(colors))\n\
+                  b map[bool, str] = {true: \"yes\"}\nputs(b.get(true))\nputs(b.contains(false))\n\
+                  for k, v in {1: 2, 3: 4}\n    puts(k + v)\nend";
+    assert_eq!(output(source), "0\nyes\nfalse\n3\n7\n");
+    assert_eq!(
+        compile_errors("m map[float, int] = {}"),
+        ["Type float cannot be used as a map key"]
+    );
+    assert_eq!(
+        compile_errors("m map[vec[int], int] = {}"),
+        ["Type vec[int] cannot be used as a map key"]
+    );
+}
+
+#[test]
+fn keys_and_values_are_checked() {
+    assert_eq!(
+        compile_errors("m map[str, int] = {\"a\": \"x\"}"),
+        ["Expected type int found type str"]
+    );
+    assert_eq!(
+        compile_errors("m map[str, int] = {\"a\": 1}\nm.set(1, 2)"),
+        ["Expected type str found type int"]
+    );
+    assert_eq!(
+        runtime_error("m map[str, int] = {\"a\": 1}\nputs(m.get(\"b\"))"),
+        "Key not found in map"
+    );
+}
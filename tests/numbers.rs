@@ -0,0 +1,54 @@
+//! Numeric literals and the diagnostics of malformed ones
+mod common;
+use common::{compile_errors, example, output};
+
+#[test]
+fn numbers_example() {
+    let expected = "165\n493\n1000000\n65535\n602200000000000000000000\n\
+                    0.0000000000000000000000000000000006626\n-2500.000000\n";
+    assert_eq!(example("numbers"), expected);
+}
+
+#[test]
+fn the_smallest_int_can_be_written() {
+    let source = "m int = -9223372036854775808\nputs(m)\nputs(m == -9223372036854775807 - 1)\n\
+                  puts(2 - -3)\nputs(-0x10)";
+    assert_eq!(output(source), "-9223372036854775808\ntrue\n5\n-16\n");
+    assert_eq!(
+        compile_errors("puts(-9223372036854775809)"),
+        ["The int literal -9223372036854775809 is too small"]
+    );
+}
+
+#[test]
+fn malformed_literals_are_reported() {
+    let cases = [
+        ("0b102", "Invalid digit \"2\" in binary literal"),
+        ("0o8", "Invalid digit \"8\" in octal literal"),
+        ("0x", "Missing digits after \"0x\""),
+        (
+            "9223372036854775808",
+            "The int literal 9223372036854775808 is too large",
+        ),
+        (
+            "0x1_0000_0000_0000_0000",
+            "The hex literal 0x1_0000_0000_0000_0000 is too large",
+        ),
+        ("1.5e", "Missing digits in the exponent"),
+        ("1e400", "The float literal 1e400 is too large"),
+        ("5px", "Invalid suffix \"px\" on int literal"),
+        ("1__0", "A \"_\" separator has to be between two digits"),
+        ("1_", "A \"_\" separator has to be between two digits"),
+    ];
+    for (literal, message) in cases {
+        assert_eq!(compile_errors(&format!("puts({})", literal)), [message]);
+    }
+}
+
+#[test]
+fn hex_literals_use_all_64_bits() {
+    assert_eq!(
+        output("puts(0xffff_ffff_ffff_ffff)\nputs(0x1e3)"),
+        "-1\n483\n"
+    );
+}
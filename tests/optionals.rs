@@ -0,0 +1,62 @@
+//! Optional values, which have to be checked before they are used
+mod common;
+use common::{compile_errors, example, output};
+use illusio_2::{Engine, EngineError};
+
+/// The help of the first error that stops `source` from compiling
+fn first_help(source: &str) -> String {
+    match Engine::new().compile(source) {
+        Err(EngineError::Compile(errors)) => errors[0].help.clone(),
+        _ => panic!("Expected errors in the code"),
+    }
+}
+
+#[test]
+fn optionals_example() {
+    assert_eq!(example("optionals"), "1\ntrue\n90\n75\n");
+}
+
+#[test]
+fn optionals_of_any_type_can_be_missing() {
+    let source = "o str? = none\nputs(o == none)\no = \"x\"\nif let s = o\n    puts(s)\nend\n\
+                  p int*? = none\nx int = 1\np = &x\nif let q = p\n    puts(*q)\nend\n\
+                  b bool? = false\nif let v = b\n    puts(v)\nend";
+    assert_eq!(output(source), "true\nx\n1\nfalse\n");
+}
+
+#[test]
+fn optionals_are_checked_before_use() {
+    let help = "The value can be missing, check it with `if let value = ...` first";
+    let source = "o int? = 1\nputs(o + 1)";
+    assert_eq!(
+        compile_errors(source),
+        ["Cannot do binary operations, with different types"]
+    );
+    assert_eq!(first_help(source), help);
+    let source = "o int? = none\nx int = o";
+    assert_eq!(
+        compile_errors(source),
+        ["Expected type int found type int?"]
+    );
+    assert_eq!(first_help(source), help);
+    assert_eq!(
+        compile_errors("if let n = 5\n    puts(n)\nend"),
+        ["Expected an optional found type int in if let"]
+    );
+    assert_eq!(
+        compile_errors("o int? = 1\nif let n = o\n    puts(n)\nend\nputs(n)"),
+        ["Variable not found in the current scope"]
+    );
+}
+
+#[test]
+fn untyped_none_is_reported_once() {
+    assert_eq!(
+        compile_errors("x int = none"),
+        ["Cannot infer the type of `none`"]
+    );
+    assert_eq!(
+        compile_errors("puts(none)"),
+        ["Cannot infer the type of `none`"]
+    );
+}
@@ -0,0 +1,54 @@
+//! Taking the address of variables and reading or writing through it
+mod common;
+use common::{compile_errors, example, output};
+
+#[test]
+fn pointers_example() {
+    assert_eq!(example("pointers"), "2\n1\ntrue\n");
+}
+
+#[test]
+fn functions_write_through_pointers() {
+    let source = "fun inc(p int*) do\n    *p = *p + 1\nend\nv int = 1\ninc(&v)\ninc(&v)\nputs(v)\n\
+                  p int* = &v\npp int** = &p\n**pp = 7\nputs(v)";
+    assert_eq!(output(source), "3\n7\n");
+}
+
+#[test]
+fn operators_continue_on_the_next_line() {
+    let source = "x int = 10\ny int = 3\ntotal int = x\n    - y\nputs(total)\n\
+                  n int = x\n    * 2\nputs(n)";
+    assert_eq!(output(source), "7\n20\n");
+}
+
+#[test]
+fn dereferences_start_statements() {
+    let source = "x int = 1\np int* = &x\n*p = 4\nputs(x)\n\
+                  y int = 3\n*p = y\n*p = *p * 2\nputs(x)\n\
+                  a, b = (5, 6)\n*p = b\nputs(x)";
+    assert_eq!(output(source), "4\n6\n6\n");
+}
+
+#[test]
+fn pointers_are_checked() {
+    assert_eq!(
+        compile_errors("x int = 1\nputs(*x)"),
+        ["Cannot dereference a value of type int"]
+    );
+    assert_eq!(
+        compile_errors("p int* = &5"),
+        ["Only variables can have their address taken"]
+    );
+    assert_eq!(
+        compile_errors("x int = 1\np int* = &x\n*p = \"a\""),
+        ["Expected type int found type str"]
+    );
+    assert_eq!(
+        compile_errors("x int = 1\np float* = &x"),
+        ["Expected type float* found type int*"]
+    );
+    assert_eq!(
+        compile_errors("x int = 1\np int* = &x\nputs(p)"),
+        ["Cannot print a value of type int*"]
+    );
+}
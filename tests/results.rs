@@ -0,0 +1,56 @@
+//! Results, whose errors `?` returns from the function
+mod common;
+use common::{compile_errors, example, output};
+
+#[test]
+fn results_example() {
+    assert_eq!(example("results"), "3\n\"2\" is not a bit\n");
+}
+
+#[test]
+fn errors_return_early() {
+    let source = "fun half(n int) : Result[int, str] do\n    if n % 2 == 1\n        return err(\"{n} is odd\")\n    end\n\
+                  \x20   return ok(n / 2)\nend\n\
+                  fun quarter(n int) : Result[int, str] do\n    h int = half(n)?\n    return ok(half(h)?)\nend\n\
+                  for n in vec(8, 6, 5)\n    if let ok(q) = quarter(n)\n        puts(q)\n    end\n\
+                  \x20   if let err(e) = quarter(n)\n        puts(e)\n    end\nend";
+    assert_eq!(output(source), "2\n3 is odd\n5 is odd\n");
+}
+
+#[test]
+fn question_marks_need_a_compatible_function() {
+    assert_eq!(
+        compile_errors("fun f() : int do\n    r Result[int, str] = ok(1)\n    return r?\nend"),
+        ["Cannot use `?` in a function that returns type int"]
+    );
+    assert_eq!(
+        compile_errors(
+            "fun f() : Result[int, int] do\n    r Result[int, str] = err(\"x\")\n    return ok(r?)\nend"
+        ),
+        ["Cannot use `?` in a function that returns type Result[int, int]"]
+    );
+    assert_eq!(
+        compile_errors("r Result[int, str] = err(\"bad\")\nputs(r?)"),
+        ["`?` outside of a function"]
+    );
+    assert_eq!(
+        compile_errors("x int = 1\nputs(x?)"),
+        ["Cannot use `?` on a value of type int"]
+    );
+}
+
+#[test]
+fn ok_and_err_are_checked() {
+    assert_eq!(
+        compile_errors("r Result[int, str] = ok(\"x\")"),
+        ["Expected type int found type str"]
+    );
+    assert_eq!(
+        compile_errors("r Result[int, str] = ok(1, 2)"),
+        ["`ok` takes 1 argument but 2 were given"]
+    );
+    assert_eq!(
+        compile_errors("r int = ok(1)"),
+        ["Cannot infer the type of `ok(...)`"]
+    );
+}
@@ -0,0 +1,33 @@
+//! Variables declared in a block are only visible inside of it
+mod common;
+use common::output;
+
+#[test]
+fn inner_declarations_shadow_until_the_end_of_the_block() {
+    let source = "x int = 1\nif true\n    x int = 2\n    puts(x)\nend\nputs(x)";
+    assert_eq!(output(source), "2\n1\n");
+}
+
+#[test]
+fn addressed_variables_are_scoped() {
+    let source = "y int = 3\np int* = &y\nif true\n    y int = 5\n    y = 7\nend\nputs(y)";
+    assert_eq!(output(source), "3\n");
+}
+
+#[test]
+fn loop_variables_are_scoped() {
+    let source = "x int = 1\nfor x in vec(10, 20)\n    puts(x)\nend\nputs(x)";
+    assert_eq!(output(source), "10\n20\n1\n");
+}
+
+#[test]
+fn if_let_bindings_are_scoped() {
+    let source = "n int = 1\no int? = 9\nif let n = o\n    puts(n)\nend\nputs(n)";
+    assert_eq!(output(source), "9\n1\n");
+}
+
+#[test]
+fn assignments_in_blocks_change_outer_variables() {
+    let source = "x int = 1\nif true\n    x = 2\nend\ndo\n    x = x + 1\nend\nputs(x)";
+    assert_eq!(output(source), "3\n");
+}
@@ -0,0 +1,156 @@
+//! Strings, which know their length and are compared by their contents
+mod common;
+use common::{compile_errors, example, output, runtime_error};
+use illusio_2::token::Span;
+use illusio_2::{Engine, EngineError};
+
+/// The message and span of the first error that stops `source` from compiling
+fn first_error(source: &str) -> (String, Span) {
+    match Engine::new().compile(source) {
+        Err(EngineError::Compile(errors)) => (errors[0].message.clone(), errors[0].span),
+        _ => panic!("Expected errors in the code"),
+    }
+}
+
+#[test]
+fn strings_example() {
+    let expected = "hello, world\n12\nhello\nworld\nstarts with hello\ntotal: 42\n\
+                    hello, world has 12 characters, twice 2.5 is 5\n\
+                    braces are written as { and }\nescapes: A é 😀\nC:\\games\\{saves}\n\
+                    a \"quoted\" word\nSELECT name\nFROM users\nWHERE id = 1\ngröße = 9\n";
+    assert_eq!(example("strings"), expected);
+}
+
+#[test]
+fn strings_are_compared_by_contents() {
+    let source = "puts(\"ab\" == \"a\" + \"b\")\nputs(\"abc\" < \"abd\")\nputs(\"b\" > \"abc\")\n\
+                  puts(len(\"\"))\nputs(\"hello\"[1:3])";
+    assert_eq!(output(source), "true\ntrue\ntrue\n0\nel\n");
+}
+
+#[test]
+fn strings_convert_to_and_from_numbers() {
+    let source =
+        "puts(float(\"2.5\") * 2.0)\nputs(int(\"-7\") + 1)\nputs(str(1.5))\nputs(str(true))";
+    assert_eq!(output(source), "5.000000\n-6\n1.5\ntrue\n");
+    assert_eq!(
+        runtime_error("puts(int(\"abc\"))"),
+        "Cannot convert \"abc\" to an int"
+    );
+    assert_eq!(
+        runtime_error("puts(float(\"x\"))"),
+        "Cannot convert \"x\" to a float"
+    );
+}
+
+#[test]
+fn slices_are_checked() {
+    assert_eq!(
+        runtime_error("s str = \"hello\"\nputs(s[2:9])"),
+        "Slice out of bounds: the length is 5 but the slice is 2..9"
+    );
+    assert_eq!(
+        runtime_error("s str = \"héllo\"\nputs(s[0:2])"),
+        "Slice 0..2 is not on character boundaries"
+    );
+    assert_eq!(
+        compile_errors("s str = \"abc\"\nputs(s[1])"),
+        ["Cannot index into a value of type str"]
+    );
+}
+
+#[test]
+fn interpolations_nest() {
+    let source = "puts(\"{true} {\"x\" + \"{1 + 1}\"}\")\nm map[str, int] = {\"a\": 1}\n\
+                  puts(\"{m.get(\"a\")} {{1: 2}.get(1)}\")";
+    assert_eq!(output(source), "true x2\n1 2\n");
+}
+
+#[test]
+fn interpolations_are_checked() {
+    assert_eq!(
+        compile_errors("v vec[int] = vec(1)\nputs(\"{v}\")"),
+        ["Cannot interpolate a value of type vec[int]"]
+    );
+    assert_eq!(
+        compile_errors("o int? = 1\nputs(\"{o}\")"),
+        ["Cannot interpolate a value of type int?"]
+    );
+    assert_eq!(
+        compile_errors("puts(\"{x}\")"),
+        ["Variable not found in the current scope"]
+    );
+    assert_eq!(
+        compile_errors("puts(\"a {1"),
+        [
+            "Expected \"}\" to close the interpolation found end of file",
+            "Expected \")\" found end of file"
+        ]
+    );
+}
+
+#[test]
+fn raw_strings_skip_escapes() {
+    let source = "puts(r\"a\\nb {c}\")\nputs(r##\"say \"#hi\"#\"##)";
+    assert_eq!(output(source), "a\\nb {c}\nsay \"#hi\"#\n");
+    assert_eq!(compile_errors("puts(r#\"abc\")"), ["Unterminated String"]);
+}
+
+#[test]
+fn multi_line_strings_lose_their_shared_indentation() {
+    let source = "s str = \"\"\"\n      one\n        two\n      \"\"\"\nputs(s)\n\
+                  puts(\"\"\"inline\"\"\")";
+    assert_eq!(output(source), "one\n  two\ninline\n");
+    assert_eq!(compile_errors("puts(\"\"\"abc\n"), ["Unterminated String"]);
+}
+
+#[test]
+fn multi_line_strings_keep_their_spans() {
+    let source = "x int = \"\"\"\n    a\n    \"\"\"";
+    assert_eq!(
+        first_error(source),
+        (
+            "Expected type int found type str".to_owned(),
+            Span::from(8..source.len())
+        )
+    );
+}
+
+#[test]
+fn escapes_are_decoded() {
+    let source = r#"puts("\0|\t|\\|\"|\u{1F600}|\x7e" == "\u{0}|\u{9}|\u{5c}|\u{22}|😀|~")"#;
+    assert_eq!(output(source), "true\n");
+}
+
+#[test]
+fn invalid_escapes_are_reported_at_the_escape() {
+    let cases = [
+        (
+            r#"puts("a\x4")"#,
+            "Invalid hex escape, expected two hex digits",
+            r"\x4",
+        ),
+        (r#"puts("a\q")"#, "Unknown escape sequence \"\\q\"", r"\q"),
+        (
+            r#"puts("a\u{110000}")"#,
+            "\"\\u{110000}\" is not a valid unicode character",
+            r"\u{110000}",
+        ),
+        (
+            r#"puts("a\xff")"#,
+            "Hex escape \"\\xff\" is out of range",
+            r"\xff",
+        ),
+    ];
+    for (source, message, escape) in cases {
+        let start = source.find(escape).unwrap();
+        assert_eq!(
+            first_error(source),
+            (message.to_owned(), Span::from(start..start + escape.len()))
+        );
+    }
+    assert_eq!(
+        first_error(r#"puts("\u{41")"#).0,
+        "Unterminated unicode escape"
+    );
+}
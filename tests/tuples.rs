@@ -0,0 +1,57 @@
+//! Tuples, their indexing and destructuring
+mod common;
+use common::{compile_errors, example, ir, output};
+
+#[test]
+fn tuples_example() {
+    assert_eq!(example("tuples"), "17 = 5 * 3 + 2\nAda is 36\n2\n12\n");
+}
+
+#[test]
+fn small_tuples_are_returned_in_registers() {
+    let source = "fun divmod(a int, b int) : (int, int) do\n    return (a / b, a % b)\nend\n\
+                  q, r = divmod(17, 5)\nputs(q + r)";
+    assert_eq!(output(source), "5\n");
+    assert!(ir(source).contains("; divmod\nfunction u0:0(i64, i64) -> i64, i64 "));
+}
+
+#[test]
+fn nested_tuples_are_destructured() {
+    let source = "fun f() : (int, (str, float)) do\n    return (1, (\"a\", 2.5))\nend\n\
+                  n, rest = f()\ns, x = rest\nputs(n)\nputs(s)\nputs(x)";
+    assert_eq!(output(source), "1\na\n2.500000\n");
+}
+
+#[test]
+fn tuples_are_checked() {
+    assert_eq!(
+        compile_errors("t (int, str) = (1, \"a\")\nputs(t.2)"),
+        ["Tuple has no element 2"]
+    );
+    assert_eq!(
+        compile_errors("t (int, str) = (1, 2)"),
+        ["Expected type str found type int"]
+    );
+    assert_eq!(
+        compile_errors("a, b = (1, 2, 3)"),
+        ["Expected 3 names for a tuple with 3 elements"]
+    );
+    assert_eq!(
+        compile_errors("x int = 1\na, b = x"),
+        ["Cannot destructure a value of type int"]
+    );
+    assert_eq!(
+        compile_errors("t (int, str) = (1, \"a\")\nputs(t.99999999999999999999)"),
+        ["Invalid tuple index 99999999999999999999"]
+    );
+}
+
+#[test]
+fn tuple_indexes_are_not_floats() {
+    let source = "t ((int, int), int) = ((1, 2), 3)\nputs(t.0.1)\nputs(.5 + 0.)";
+    assert_eq!(output(source), "2\n0.500000\n");
+    assert_eq!(
+        compile_errors("puts(0..3)").first().unwrap(),
+        "Expected \")\" found .3"
+    );
+}
@@ -0,0 +1,72 @@
+//! Type aliases and newtypes
+mod common;
+use common::{compile_errors, example, output};
+
+#[test]
+fn named_types_are_declared_before_an_equal_sign() {
+    let source = "type Meters = float\nd Meters = 1.5\np Meters* = &d\n\
+                  done bool = true\nif done puts(\"yes\") end\nputs(*p)";
+    assert_eq!(output(source), "yes\n1.500000\n");
+}
+
+#[test]
+fn aliases_that_refer_to_themselves_are_reported_once() {
+    assert_eq!(
+        compile_errors("type A = B\ntype B = A\nx A = 1\ny [B; 1] = [\"s\"]"),
+        ["Type alias `A` refers to itself"]
+    );
+    assert_eq!(
+        compile_errors("type A = A\nfun f(a A): A do\nreturn 1\nend\nf(1)"),
+        ["Type alias `A` refers to itself"]
+    );
+}
+
+#[test]
+fn types_example() {
+    assert_eq!(
+        example("types"),
+        "3.750000\norder 4200 belongs to user 42\nGrace\n"
+    );
+}
+
+#[test]
+fn aliases_are_the_type_they_name() {
+    let source = "type Pair = (int, str)\np Pair = (1, \"a\")\nputs(p.1)\n\
+                  type Meters = float\nm Meters = 2.5\nf float = m * 2.0\nputs(f)";
+    assert_eq!(output(source), "a\n5.000000\n");
+    assert_eq!(
+        compile_errors("type Meters = float\nm Meters = \"a\""),
+        ["Expected type Meters (float) found type str"]
+    );
+}
+
+#[test]
+fn newtypes_are_converted_explicitly() {
+    let source = "newtype N = float\nx N = N(1.0) * N(2.0)\nputs(float(x))\n\
+                  newtype UserId = int\ny int = int(UserId(3)) + 1\nputs(y)";
+    assert_eq!(output(source), "2.000000\n4\n");
+    assert_eq!(
+        compile_errors("newtype UserId = int\nu UserId = 1"),
+        ["Expected type UserId found type int"]
+    );
+    assert_eq!(
+        compile_errors("newtype N = int\nx int = N(3)"),
+        ["Expected type int found type N"]
+    );
+    assert_eq!(
+        compile_errors("newtype UserId = int\nx UserId = UserId(\"a\")"),
+        ["Expected type int found type str"]
+    );
+}
+
+#[test]
+fn newtypes_of_the_same_type_are_distinct() {
+    assert_eq!(
+        compile_errors("newtype UserId = int\nnewtype OrderId = int\no OrderId = UserId(1)"),
+        ["Expected type OrderId found type UserId"]
+    );
+    assert_eq!(
+        compile_errors("newtype UserId = int\nx UserId = UserId(1) + 1"),
+        ["Cannot do binary operations, with different types"]
+    );
+}
@@ -0,0 +1,25 @@
+//! UTF-8 source text, in identifiers, strings and comments
+mod common;
+use common::output;
+use illusio_2::token::Span;
+use illusio_2::{Engine, EngineError};
+
+#[test]
+fn identifiers_strings_and_comments_can_use_any_language() {
+    let source = "# コメント: 日本語\n名前 str = \"Ünïcödé\"\nputs(名前)\nputs(len(名前))\n\
+                  _größe int = 1\nputs(_größe)";
+    assert_eq!(output(source), "Ünïcödé\n11\n1\n");
+}
+
+#[test]
+fn spans_count_bytes() {
+    let source = "é int = \"日本\"";
+    match Engine::new().compile(source) {
+        Err(EngineError::Compile(errors)) => {
+            assert_eq!(errors[0].message, "Expected type int found type str");
+            assert_eq!(errors[0].span, Span::from(9..17));
+            assert_eq!(&source[9..17], "\"日本\"");
+        }
+        _ => panic!("Expected errors in the code"),
+    }
+}
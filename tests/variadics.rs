@@ -0,0 +1,53 @@
+//! Variadic parameters, and calls to variadic C functions
+mod common;
+use common::{compile_errors, output};
+
+#[test]
+fn variadic_parameters_take_the_remaining_arguments() {
+    let source = "fun count(a ...int) : int do\nreturn len(a)\nend\n\
+                  fun first(a str, b ...int) : int do\nreturn b[0]\nend\n\
+                  puts(count(1, 2))\nputs(count())\nputs(first(\"x\", 7, 8))\n\
+                  puts(count(a: vec(4, 5, 6)))";
+    assert_eq!(output(source), "2\n0\n7\n3\n");
+}
+
+#[test]
+fn variadic_parameters_are_checked() {
+    assert_eq!(
+        compile_errors("fun f(a ...int, b int) do\nend"),
+        ["Variadic parameter `a` has to be the last one"]
+    );
+    assert_eq!(
+        compile_errors("fun f(a ...int = 1) do\nend"),
+        ["Variadic parameter `a` can't have a default value"]
+    );
+    let f = "fun f(a ...int) do\nend\n";
+    assert_eq!(
+        compile_errors(&format!("{}f(1, \"s\")", f)),
+        ["Expected type int found type str"]
+    );
+    assert_eq!(
+        compile_errors(&format!("{}f(a: 1)", f)),
+        ["Expected type vec[int] found type int"]
+    );
+    assert_eq!(
+        compile_errors(&format!("{}f(1, a: vec(1))", f)),
+        ["Parameter `a` is given twice"]
+    );
+}
+
+#[test]
+fn c_variadics_take_arguments_of_any_c_type() {
+    let source = "extern fun printf(format str, ...) : int\n\
+                  extern fun fflush(stream int) : int\n\
+                  printf(\"%ld %.2f %s %d\\n\", 5, 1.5, \"hi\", true)\nfflush(0)\nputs(\"after\")";
+    assert_eq!(output(source), "5 1.50 hi 1\nafter\n");
+    assert_eq!(
+        compile_errors("extern fun printf(format str, ...) : int\nprintf()"),
+        ["Expected at least 1 arguments found 0"]
+    );
+    assert_eq!(
+        compile_errors("extern fun printf(format str, ...) : int\nprintf(\"x\", vec(1))"),
+        ["Type vec[int] can't be given to C"]
+    );
+}
@@ -0,0 +1,43 @@
+//! Growable vectors, backed by the runtime
+mod common;
+use common::{compile_errors, example, output, runtime_error};
+
+#[test]
+fn vectors_example() {
+    assert_eq!(example("vectors"), "2\n4\n6\n8\n8\n6\n");
+}
+
+#[test]
+fn vectors_grow() {
+    let source = "v vec[int] = vec()\nfor x in vec(1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17)\n\
+                  \x20   v.push(x * x)\nend\nv[0] = 5\nputs(len(v))\nputs(v[0])\nputs(v[16])";
+    assert_eq!(output(source), "17\n5\n289\n");
+}
+
+#[test]
+fn vectors_are_checked_while_running() {
+    assert_eq!(
+        runtime_error("v vec[int] = vec()\nputs(v.pop())"),
+        "Cannot pop from an empty vector"
+    );
+    assert_eq!(
+        runtime_error("v vec[int] = vec(1)\nputs(v[3])"),
+        "Index out of bounds: the length is 1 but the index is 3"
+    );
+}
+
+#[test]
+fn elements_have_the_type_of_the_vector() {
+    assert_eq!(
+        compile_errors("v vec[int] = vec(1)\nv.push(\"a\")"),
+        ["Expected type int found type str"]
+    );
+    assert_eq!(
+        compile_errors("v vec[str] = vec(\"a\", 1)"),
+        ["Expected type str found type int"]
+    );
+    assert_eq!(
+        compile_errors("v vec[int] = vec(1)\nv.shove(2)"),
+        ["No method `shove` found for type vec[int]"]
+    );
+}